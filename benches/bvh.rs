@@ -0,0 +1,68 @@
+// Compare the BVH layouts on the Stanford bunny.
+// cargo bench --bench bvh
+use rand::{Rng, SeedableRng};
+use raytracer_ramel::prelude::*;
+use std::time::Instant;
+
+const RAYS: usize = 1_000_000;
+
+fn main() {
+  let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/stanford-bunny.obj");
+  let triangles: Vec<Arc<dyn Hittable>> = TriangleMesh::load_obj_ignore_material(path.to_str().unwrap())
+    .into_iter()
+    .flat_map(|mesh| mesh.triangles())
+    .map(|tri| Arc::new(tri) as Arc<dyn Hittable>)
+    .collect();
+  println!("{} triangles, {} rays", triangles.len(), RAYS);
+
+  // from random points around the bunny towards random points in its box,
+  // so that most of the rays hit and some miss.
+  let bbox = Aggregate::new(triangles.clone()).bounding_box();
+  let center = bbox.centroid();
+  let radius = (bbox.max - bbox.min).length();
+  let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+  let rays: Vec<Ray> = (0..RAYS)
+    .map(|_| {
+      let origin = center + sampler::sample_uniform_sphere((rng.random(), rng.random())) * radius;
+      let mut lerp = |lo: Float, hi: Float| lo + (hi - lo) * rng.random::<Float>();
+      let target = Point::new(
+        lerp(bbox.min.x, bbox.max.x),
+        lerp(bbox.min.y, bbox.max.y),
+        lerp(bbox.min.z, bbox.max.z),
+      );
+      Ray::new(origin, (target - origin).normalize())
+    })
+    .collect();
+
+  let run = |name: &str, build: &dyn Fn() -> (Arc<dyn Hittable>, geometry::BvhStats)| {
+    let build_start = Instant::now();
+    let (bvh, stats) = build();
+    let build_elapsed = build_start.elapsed();
+    let start = Instant::now();
+    let hits = rays.iter().filter(|ray| bvh.hit(ray, RAY_EPSILON, Float::MAX).is_some()).count();
+    let elapsed = start.elapsed();
+    println!(
+      "{:<14} build {:>6}ms  trace {:>6}ms  {:>6.2} Mrays/s  {} hits  {}",
+      name,
+      build_elapsed.as_millis(),
+      elapsed.as_millis(),
+      RAYS as f64 / elapsed.as_secs_f64() / 1e6,
+      hits,
+      stats
+    );
+  };
+
+  let objects = || triangles.clone();
+  run("tree, median", &|| BvhAggregate::build(objects(), &BvhOptions::median()));
+  run("tree, SAH", &|| BvhAggregate::build(objects(), &BvhOptions::sah()));
+  run("linear, median", &|| {
+    let bvh = geometry::LinearBvh::build(objects(), &BvhOptions::median());
+    let stats = *bvh.stats();
+    (Arc::new(bvh), stats)
+  });
+  run("linear, SAH", &|| {
+    let bvh = geometry::LinearBvh::build(objects(), &BvhOptions::sah());
+    let stats = *bvh.stats();
+    (Arc::new(bvh), stats)
+  });
+}
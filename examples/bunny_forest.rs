@@ -0,0 +1,84 @@
+// 400 Stanford bunnies sharing one MeshAccel: the triangles are loaded and packed once,
+// and the world only holds the instances.
+// cargo run --release --example bunny_forest
+use rand::{Rng, SeedableRng};
+use raytracer_ramel::prelude::*;
+
+fn main() {
+  let path = std::path::Path::new("output/bunny-forest.png");
+  std::fs::create_dir_all(path.parent().unwrap()).expect("Cannot create all the parents");
+
+  let aspect_ratio = 16.0 / 9.0;
+  let width = 400;
+  let height = (width as Float / aspect_ratio) as u32;
+  let film = Arc::new(Mutex::new(film::SimpleFilm::new(width, height)));
+
+  let mut filters = FilterList::new();
+  filters.add_filter(Arc::new(filter::AcesFilmicFilter));
+
+  let mut world = World::default();
+
+  let bunny_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/stanford-bunny.obj");
+  let mat_bunny = material::Lambertian::new_arc(texture::SolidColorTexture::new_arc(ColorRgb::new(
+    0.8, 0.7, 0.6,
+  )));
+  let meshes = TriangleMesh::load_obj_with_material(bunny_path.to_str().unwrap(), mat_bunny)
+    .expect("Cannot load the bunny");
+  let bunny: Arc<dyn Hittable> = Arc::new(MeshAccel::from_meshes(meshes));
+
+  let mut rng = rand::rngs::StdRng::seed_from_u64(2025);
+  for i in 0..20 {
+    for j in 0..20 {
+      let x = (i as Float - 9.5) * 0.3 + rng.random_range(-0.1..0.1);
+      let z = -(j as Float) * 0.3 + rng.random_range(-0.1..0.1);
+      let scale = rng.random_range(0.8..1.4);
+      world.add_object(geometry::Instance::new_arc(
+        bunny.clone(),
+        Mat4d::from_translation(Direction::new(x, -0.035 * scale, z))
+          * Mat4d::from_rotation_y(rng.random_range(0.0..2.0 * PI))
+          * Mat4d::from_scaling(scale, scale, scale),
+      ));
+    }
+  }
+
+  let mat_ground = material::Lambertian::new_arc(texture::SolidColorTexture::new_arc(ColorRgb::new(
+    0.3, 0.5, 0.3,
+  )));
+  world.add_object(geometry::Instance::new_arc(
+    Arc::new(geometry::UnitQuad::new(mat_ground)),
+    Mat4d::from_rotation_x(-PI / 2.0) * Mat4d::from_scaling(40.0, 40.0, 1.0),
+  ));
+
+  let mat_light = material::DiffusionLight::arc_from_color(ColorRgb::WHITE * 8.0);
+  world.add_light(geometry::Instance::new_arc(
+    geometry::UnitSphere::new_arc(mat_light),
+    Mat4d::from_translation(Direction::new(2.0, 4.0, 1.0)),
+  ));
+
+  world.bvh_finalize();
+  println!("{} instances, {}", world.objects().len(), world.objects().bvh_stats().unwrap());
+
+  let camera = camera::PerspectiveCamera::new(
+    Point::new(0.0, 1.2, 2.5),
+    Point::new(0.0, 0.0, -2.0),
+    Direction::new(0.0, 1.0, 0.0),
+    45.0,
+    aspect_ratio,
+  );
+  let renderer = renderer::SimpleRenderer::new(32, 16);
+
+  let render_start = std::time::Instant::now();
+  renderer.render(RenderConfig {
+    camera: Arc::new(camera),
+    film: film.clone(),
+    filters: Arc::new(filters),
+    world: Arc::new(world),
+    seed: 0,
+  });
+  let render_elapsed = render_start.elapsed();
+
+  let image = film.lock().unwrap().to_image_srgb();
+  image.save(path).expect("Cannot save the image to file.");
+  println!("Finished. Image saved to {}", console::style(path.display()).green().bright());
+  println!("Elapsed time: {}ms", render_elapsed.as_millis());
+}
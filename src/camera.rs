@@ -2,11 +2,18 @@ use crate::prelude::*;
 
 pub trait Camera: Sync + Send {
   // u, v is in [0, 1]
-  fn get_ray(&self, u: Float, v: Float) -> Ray;
+  // `sampler` is for the time and the lens, after the pixel jitter.
+  fn get_ray(&self, u: Float, v: Float, sampler: &mut dyn Sampler) -> Ray;
+}
+
+// A uniform moment in the shutter interval (open, close). Rays carry it for motion blur.
+fn shutter_time(shutter: (Float, Float), sampler: &mut dyn Sampler) -> Float {
+  let (open, close) = shutter;
+  if close > open { open + (close - open) * sampler.get_1d() } else { open }
 }
 
 mod orthographic;
 mod perspective;
 
 pub use orthographic::OrthographicCamera;
-pub use perspective::PerspectiveCamera;
\ No newline at end of file
+pub use perspective::{ApertureShape, PerspectiveCamera};
\ No newline at end of file
@@ -6,6 +6,7 @@ pub struct OrthographicCamera {
   horizonal: Vec3d,
   vertical: Vec3d,
   direction: Direction, // projection direction
+  shutter: (Float, Float),
 }
 
 impl OrthographicCamera {
@@ -34,15 +35,22 @@ impl OrthographicCamera {
       horizonal,
       vertical,
       direction: -w,
+      shutter: (0.0, 0.0),
     }
   }
+  /// Rays are shot at random moments between `open` and `close`, for motion blur.
+  pub fn with_shutter(mut self, open: Float, close: Float) -> Self {
+    self.shutter = (open, close);
+    self
+  }
 }
 
 impl Camera for OrthographicCamera {
-  fn get_ray(&self, u: Float, v: Float) -> Ray {
-    Ray::new(
+  fn get_ray(&self, u: Float, v: Float, sampler: &mut dyn Sampler) -> Ray {
+    Ray::new_with_time(
       self.origin + u * self.horizonal + v * self.vertical,
-      self.direction
+      self.direction,
+      super::shutter_time(self.shutter, sampler),
     )
   }
 }
\ No newline at end of file
@@ -1,13 +1,30 @@
 use crate::prelude::*;
 
+/// The shape of the lens opening, which is also the shape of out-of-focus highlights (bokeh).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ApertureShape {
+  Disk,
+  /// A regular polygon of `blades` (>= 3) sides, like the diaphragm of a real lens.
+  /// `rotation` is in degrees, counterclockwise from a vertex pointing to +u (right).
+  Polygon { blades: u32, rotation: Float },
+}
+
 pub struct PerspectiveCamera {
   origin: Point,
+  // the view port lies on the focus plane.
   lower_left_corner: Point,
   horizontal: Direction,
   vertical: Direction,
+  // unit vectors to the right and up, spanning the lens.
+  u: Direction,
+  v: Direction,
+  lens_radius: Float,
+  aperture_shape: ApertureShape,
+  shutter: (Float, Float),
 }
 
 impl PerspectiveCamera {
+  /// A pinhole camera, everything is in focus.
   /// `vfov` shall be passed by degrees, like 45.0 instead of PI / 4.
   pub fn new(
     look_from: Point,
@@ -15,6 +32,21 @@ impl PerspectiveCamera {
     vup: Vec3d,
     vfov: Float,
     aspect_ratio: Float,
+  ) -> Self {
+    Self::new_thin_lens(look_from, look_at, vup, vfov, aspect_ratio, 0.0, 1.0)
+  }
+
+  /// A thin lens camera with depth of field.
+  /// `aperture` is the diameter of the lens, and objects at `focus_distance` from `look_from`
+  /// (along the view direction) are sharp.
+  pub fn new_thin_lens(
+    look_from: Point,
+    look_at: Point,
+    vup: Vec3d,
+    vfov: Float,
+    aspect_ratio: Float,
+    aperture: Float,
+    focus_distance: Float,
   ) -> Self {
     let theta = vfov.to_radians();
     let h = (theta / 2.0).tan();
@@ -26,19 +58,111 @@ impl PerspectiveCamera {
     let v = w.cross(u);
 
     let origin = look_from;
-    let horizontal = viewport_width * u;
-    let vertical = viewport_height * v;
-    let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - w;
+    let horizontal = focus_distance * viewport_width * u;
+    let vertical = focus_distance * viewport_height * v;
+    let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - focus_distance * w;
+
+    Self {
+      origin,
+      lower_left_corner,
+      horizontal,
+      vertical,
+      u,
+      v,
+      lens_radius: aperture / 2.0,
+      aperture_shape: ApertureShape::Disk,
+      shutter: (0.0, 0.0),
+    }
+  }
+
+  /// A thin lens camera focused on `look_at`.
+  pub fn new_autofocus(
+    look_from: Point,
+    look_at: Point,
+    vup: Vec3d,
+    vfov: Float,
+    aspect_ratio: Float,
+    aperture: Float,
+  ) -> Self {
+    let focus_distance = (look_at - look_from).length();
+    Self::new_thin_lens(look_from, look_at, vup, vfov, aspect_ratio, aperture, focus_distance)
+  }
+
+  pub fn with_aperture_shape(mut self, shape: ApertureShape) -> Self {
+    if let ApertureShape::Polygon { blades, .. } = shape {
+      assert!(blades >= 3, "PerspectiveCamera: an aperture needs at least 3 blades");
+    }
+    self.aperture_shape = shape;
+    self
+  }
+
+  /// Rays are shot at random moments between `open` and `close`, for motion blur.
+  pub fn with_shutter(mut self, open: Float, close: Float) -> Self {
+    self.shutter = (open, close);
+    self
+  }
 
-    Self { origin, lower_left_corner, horizontal, vertical }
+  // A uniform point on the aperture of radius 1, as (right, up).
+  fn sample_aperture(&self, sampler: &mut dyn Sampler) -> (Float, Float) {
+    match self.aperture_shape {
+      ApertureShape::Disk => sampler::sample_uniform_disk(sampler.get_2d()),
+      ApertureShape::Polygon { blades, rotation } => {
+        // the polygon is a fan of equal triangles around the center, pick one and sample it.
+        let step = 2.0 * PI / blades as Float;
+        let blade = ((sampler.get_1d() * blades as Float) as u32).min(blades - 1);
+        let start = rotation.to_radians() + step * blade as Float;
+        let (a, b) = ((start.cos(), start.sin()), ((start + step).cos(), (start + step).sin()));
+        let (b1, b2) = sampler::sample_uniform_triangle(sampler.get_2d());
+        (b1 * a.0 + b2 * b.0, b1 * a.1 + b2 * b.1)
+      }
+    }
   }
 }
 
 impl Camera for PerspectiveCamera {
-  fn get_ray(&self, u: Float, v: Float) -> Ray {
-    Ray::new(
-      self.origin,
-      self.lower_left_corner + self.horizontal * u + self.vertical * v - self.origin,
-    )
+  fn get_ray(&self, u: Float, v: Float, sampler: &mut dyn Sampler) -> Ray {
+    let target = self.lower_left_corner + self.horizontal * u + self.vertical * v;
+    let time = super::shutter_time(self.shutter, sampler);
+    let origin = if self.lens_radius > 0.0 {
+      let (dx, dy) = self.sample_aperture(sampler);
+      self.origin + self.lens_radius * (dx * self.u + dy * self.v)
+    } else {
+      self.origin
+    };
+    Ray::new_with_time(origin, target - origin, time)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_thin_lens_focus() {
+    let look_from = Point::new(1.0, 2.0, 3.0);
+    let look_at = Point::new(0.0, 0.0, -4.0);
+    let vup = Direction::new(0.0, 1.0, 0.0);
+    let focus_distance = (look_at - look_from).length();
+    let pinhole = PerspectiveCamera::new(look_from, look_at, vup, 40.0, 1.5);
+    let shapes = [ApertureShape::Disk, ApertureShape::Polygon { blades: 5, rotation: 18.0 }];
+    for shape in shapes {
+      let camera = PerspectiveCamera::new_autofocus(look_from, look_at, vup, 40.0, 1.5, 0.5)
+        .with_aperture_shape(shape);
+      let mut sampler = sampler::IndependentSampler::new(0);
+      for (u, v) in [(0.5, 0.5), (0.1, 0.9), (0.8, 0.3)] {
+        let center = pinhole.get_ray(u, v, &mut sampler);
+        // the pinhole ray reaches the focus plane at this t.
+        let t = focus_distance / center.direction.dot((look_at - look_from).normalize());
+        let focus_point = center.at(t);
+        for index in 0..100 {
+          sampler.start_pixel_sample((0, 0), index);
+          let ray = camera.get_ray(u, v, &mut sampler);
+          assert!((ray.origin - look_from).length() <= 0.25 + 1e-5);
+          // every lens sample converges to the same point on the focus plane.
+          let to_focus = focus_point - ray.origin;
+          assert!(to_focus.normalize().cross(ray.direction.normalize()).length() < 1e-4);
+        }
+      }
+    }
   }
 }
@@ -1,5 +1,6 @@
 
 pub type Float = f32; // f32 or f64
+pub type AtomicFloatBits = std::sync::atomic::AtomicU32; // as wide as Float: AtomicU32 or AtomicU64
 
 pub const COLOR_EPSILON: Float = 1e-5;
 pub const VEC3D_EPSILON: Float = 1e-5;
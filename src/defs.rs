@@ -3,9 +3,13 @@ mod vec3d;
 mod ray;
 mod mat4d;
 mod uv;
+mod onb;
+mod quat;
 
 pub use color_rgb::ColorRgb;
 pub use vec3d::Vec3d;
 pub use ray::{Ray, Point, Direction};
 pub use mat4d::Mat4d;
-pub use uv::UV;
\ No newline at end of file
+pub use uv::UV;
+pub use onb::Onb;
+pub use quat::Quat;
\ No newline at end of file
@@ -42,6 +42,19 @@ impl ColorRgb {
     Self::new(r, g, b)
   }
 
+  /// For the 32-bit float images, whatever `Float` is.
+  #[inline]
+  #[allow(clippy::unnecessary_cast)] // only while Float is f32
+  pub fn to_rgb32f(self) -> image::Rgb<f32> {
+    image::Rgb([self.r as f32, self.g as f32, self.b as f32])
+  }
+
+  #[inline]
+  pub fn from_rgb32f(pixel: image::Rgb<f32>) -> Self {
+    let [r, g, b] = pixel.0;
+    Self::new(r as Float, g as Float, b as Float)
+  }
+
   fn check_valid(r: Float, g: Float, b: Float) -> bool {
     static WARN_ONCE: std::sync::Once = std::sync::Once::new();
 
@@ -74,6 +87,12 @@ impl ColorRgb {
     )
   }
 
+  /// Relative luminance of linear Rec.709 values.
+  #[inline]
+  pub fn luminance(self) -> Float {
+    0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+  }
+
   #[inline]
   /// 0 -> color1, 1 -> color2
   pub fn lerp(color1: Self, color2: Self, t: Float) -> Self {
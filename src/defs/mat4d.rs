@@ -102,6 +102,12 @@ impl Mat4d {
       self.data[8] * vec.x + self.data[9] * vec.y + self.data[10] * vec.z,
     )
   }
+  /// determinant of the linear (upper-left 3x3) part.
+  pub fn linear_determinant(&self) -> Float {
+    let d = &self.data;
+    d[0] * (d[5] * d[10] - d[9] * d[6]) - d[4] * (d[1] * d[10] - d[9] * d[2])
+      + d[8] * (d[1] * d[6] - d[5] * d[2])
+  }
   pub fn transpose(&self) -> Self {
     let mut data = [0.0; 16];
     for i in 0..4 {
@@ -111,6 +117,7 @@ impl Mat4d {
     }
     Self { data }
   }
+  #[allow(clippy::needless_range_loop)]
   pub fn inverse(&self) -> Option<Self> {
     let mut aug = [[0.0 as Float; 8]; 4];
     let identity = Self::IDENTITY;
@@ -165,33 +172,21 @@ impl Mat4d {
 impl std::ops::Add for Mat4d {
   type Output = Self;
   fn add(self, rhs: Self) -> Self {
-    let mut data = [0.0; 16];
-    for i in 0..16 {
-      data[i] = self.data[i] + rhs.data[i];
-    }
-    Self { data }
+    Self { data: std::array::from_fn(|i| self.data[i] + rhs.data[i]) }
   }
 }
 
 impl std::ops::Sub for Mat4d {
   type Output = Self;
   fn sub(self, rhs: Self) -> Self {
-    let mut data = [0.0; 16];
-    for i in 0..16 {
-      data[i] = self.data[i] - rhs.data[i];
-    }
-    Self { data }
+    Self { data: std::array::from_fn(|i| self.data[i] - rhs.data[i]) }
   }
 }
 
 impl std::ops::Mul<Float> for Mat4d {
   type Output = Self;
   fn mul(self, rhs: Float) -> Self {
-    let mut data = [0.0; 16];
-    for i in 0..16 {
-      data[i] = self.data[i] * rhs;
-    }
-    Self { data }
+    Self { data: self.data.map(|x| x * rhs) }
   }
 }
 
@@ -0,0 +1,33 @@
+use crate::config::Float;
+use crate::defs::ray::Direction;
+
+/// Orthonormal basis (u, v, w), where w is usually the surface normal.
+/// In the local frame, a direction (x, y, z) is x * u + y * v + z * w,
+/// so `z` is the cosine against the normal.
+#[derive(Clone, Copy, Debug)]
+pub struct Onb {
+  pub u: Direction,
+  pub v: Direction,
+  pub w: Direction,
+}
+
+impl Onb {
+  /// `w` shall be a unit vector.
+  /// Duff et al., "Building an Orthonormal Basis, Revisited".
+  pub fn from_w(w: Direction) -> Self {
+    let sign = (1.0 as Float).copysign(w.z);
+    let a = -1.0 / (sign + w.z);
+    let b = w.x * w.y * a;
+    let u = Direction::new(1.0 + sign * w.x * w.x * a, sign * b, -sign * w.x);
+    let v = Direction::new(b, sign + w.y * w.y * a, -w.y);
+    Self { u, v, w }
+  }
+  #[inline]
+  pub fn to_world(&self, local: Direction) -> Direction {
+    local.x * self.u + local.y * self.v + local.z * self.w
+  }
+  #[inline]
+  pub fn to_local(&self, world: Direction) -> Direction {
+    Direction::new(world.dot(self.u), world.dot(self.v), world.dot(self.w))
+  }
+}
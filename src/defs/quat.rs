@@ -0,0 +1,133 @@
+use crate::config::Float;
+use crate::defs::mat4d::Mat4d;
+use crate::defs::ray::Direction;
+
+/// Unit quaternion w + xi + yj + zk, for rotations that can be interpolated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quat {
+  pub w: Float,
+  pub x: Float,
+  pub y: Float,
+  pub z: Float,
+}
+
+impl Quat {
+  pub const IDENTITY: Quat = Quat { w: 1.0, x: 0.0, y: 0.0, z: 0.0 };
+
+  /// Counterclockwise rotation by `angle` (radians) around `axis`, the same as `Mat4d::rotation_axis`.
+  pub fn from_axis_angle(axis: Direction, angle: Float) -> Self {
+    let axis = axis.normalize();
+    let (s, c) = (angle / 2.0).sin_cos();
+    Self { w: c, x: axis.x * s, y: axis.y * s, z: axis.z * s }
+  }
+
+  /// The rotation of the upper 3x3 of `mat`, which shall be orthonormal with determinant 1.
+  pub fn from_rotation_matrix(mat: &Mat4d) -> Self {
+    let m = |row: usize, col: usize| mat.data[col * 4 + row];
+    let trace = m(0, 0) + m(1, 1) + m(2, 2);
+    // Shepperd's method: divide by the largest of the four candidates.
+    let q = if trace > 0.0 {
+      let s = (trace + 1.0).sqrt() * 2.0;
+      Self {
+        w: 0.25 * s,
+        x: (m(2, 1) - m(1, 2)) / s,
+        y: (m(0, 2) - m(2, 0)) / s,
+        z: (m(1, 0) - m(0, 1)) / s,
+      }
+    } else if m(0, 0) > m(1, 1) && m(0, 0) > m(2, 2) {
+      let s = (1.0 + m(0, 0) - m(1, 1) - m(2, 2)).sqrt() * 2.0;
+      Self {
+        w: (m(2, 1) - m(1, 2)) / s,
+        x: 0.25 * s,
+        y: (m(0, 1) + m(1, 0)) / s,
+        z: (m(0, 2) + m(2, 0)) / s,
+      }
+    } else if m(1, 1) > m(2, 2) {
+      let s = (1.0 + m(1, 1) - m(0, 0) - m(2, 2)).sqrt() * 2.0;
+      Self {
+        w: (m(0, 2) - m(2, 0)) / s,
+        x: (m(0, 1) + m(1, 0)) / s,
+        y: 0.25 * s,
+        z: (m(1, 2) + m(2, 1)) / s,
+      }
+    } else {
+      let s = (1.0 + m(2, 2) - m(0, 0) - m(1, 1)).sqrt() * 2.0;
+      Self {
+        w: (m(1, 0) - m(0, 1)) / s,
+        x: (m(0, 2) + m(2, 0)) / s,
+        y: (m(1, 2) + m(2, 1)) / s,
+        z: 0.25 * s,
+      }
+    };
+    q.normalize()
+  }
+
+  pub fn to_mat4d(&self) -> Mat4d {
+    let Self { w, x, y, z } = *self;
+    let mut mat = Mat4d::IDENTITY;
+    mat.data[0] = 1.0 - 2.0 * (y * y + z * z);
+    mat.data[1] = 2.0 * (x * y + w * z);
+    mat.data[2] = 2.0 * (x * z - w * y);
+    mat.data[4] = 2.0 * (x * y - w * z);
+    mat.data[5] = 1.0 - 2.0 * (x * x + z * z);
+    mat.data[6] = 2.0 * (y * z + w * x);
+    mat.data[8] = 2.0 * (x * z + w * y);
+    mat.data[9] = 2.0 * (y * z - w * x);
+    mat.data[10] = 1.0 - 2.0 * (x * x + y * y);
+    mat
+  }
+
+  #[inline]
+  pub fn dot(&self, rhs: Self) -> Float {
+    self.w * rhs.w + self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+  }
+  pub fn normalize(&self) -> Self {
+    let len = self.dot(*self).sqrt();
+    Self { w: self.w / len, x: self.x / len, y: self.y / len, z: self.z / len }
+  }
+  /// The inverse rotation.
+  #[inline]
+  pub fn conjugate(&self) -> Self {
+    Self { w: self.w, x: -self.x, y: -self.y, z: -self.z }
+  }
+
+  #[inline]
+  pub fn rotate(&self, v: Direction) -> Direction {
+    // v + 2w(q x v) + 2q x (q x v), with q the vector part.
+    let q = Direction::new(self.x, self.y, self.z);
+    let t = 2.0 * q.cross(v);
+    v + self.w * t + q.cross(t)
+  }
+
+  /// The angle (radians, in [0, PI]) of the rotation from `self` to `rhs`.
+  pub fn angle_to(&self, rhs: Self) -> Float {
+    2.0 * self.dot(rhs).abs().min(1.0).acos()
+  }
+
+  /// Spherical linear interpolation along the shorter arc, `t` in [0, 1].
+  pub fn slerp(a: Self, b: Self, t: Float) -> Self {
+    let mut cos = a.dot(b);
+    // q and -q are the same rotation, take the closer one.
+    let b = if cos < 0.0 {
+      cos = -cos;
+      Self { w: -b.w, x: -b.x, y: -b.y, z: -b.z }
+    } else {
+      b
+    };
+    let (wa, wb) = if cos > 0.9995 {
+      // nearly the same, lerp is precise enough and avoids dividing by sin ~ 0.
+      (1.0 - t, t)
+    } else {
+      let theta = cos.acos();
+      let sin = theta.sin();
+      (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+    };
+    Self {
+      w: wa * a.w + wb * b.w,
+      x: wa * a.x + wb * b.x,
+      y: wa * a.y + wb * b.y,
+      z: wa * a.z + wb * b.z,
+    }
+    .normalize()
+  }
+}
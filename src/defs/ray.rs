@@ -6,16 +6,24 @@ pub type Point = Vec3d;
 
 // r(t) = ori + dir * t
 // dir is not necessarily normalized.
+// `time` is the moment in the shutter interval the ray is shot at, for motion blur,
+// not to be confused with the ray parameter t.
+#[derive(Clone, Copy, Debug)]
 pub struct Ray {
   pub origin: Point,
   pub direction: Direction,
+  pub time: Float,
 }
 
 impl Ray {
   pub const DISTURBANCE_RATE: Float = RAY_EPSILON;
   #[inline]
   pub fn new(origin: Point, direction: Direction) -> Self {
-    Self { origin, direction }
+    Self { origin, direction, time: 0.0 }
+  }
+  #[inline]
+  pub fn new_with_time(origin: Point, direction: Direction, time: Float) -> Self {
+    Self { origin, direction, time }
   }
   #[inline]
   pub fn at(&self, t: Float) -> Point {
@@ -36,7 +44,7 @@ impl Ray {
     let i = self.direction;
     let direction = i - 2.0 as Float * n.dot(i) * n;
 
-    Self::new(hit_point, direction)
+    Self::new_with_time(hit_point, direction, self.time)
   }
   // refract at point with parameter `hit_t` against the surface with norm `n`.
   // Passing `hit_t` instead of a point, for forcing the section point to be exactly on this ray.
@@ -64,7 +72,7 @@ impl Ray {
       None
     } else {
       let r_para = n * disc.sqrt();
-      Some(Self::new(hit_point, r_perp + r_para))
+      Some(Self::new_with_time(hit_point, r_perp + r_para, self.time))
     }
   }
 }
@@ -1,5 +1,4 @@
 use crate::config::{Float, RAY_EPSILON, VEC3D_EPSILON};
-use rand::Rng;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Vec3d {
@@ -58,16 +57,6 @@ impl Vec3d {
   pub fn near_zero(&self) -> bool {
     self.length_squared() < Self::NEAR_ZERO_SQ
   }
-
-  #[inline]
-  pub fn random_unit() -> Self {
-    let mut rng = rand::rng();
-    let theta: Float = rng.random_range(0.0..std::f64::consts::TAU) as Float;
-    let z: Float = rng.random_range(-1.0..1.0);
-    let r = (1.0 - z * z).max(0.0).sqrt();
-    let (sin_t, cos_t) = theta.sin_cos();
-    Self::new(r * cos_t, r * sin_t, z)
-  }
 }
 
 impl std::ops::Add for Vec3d {
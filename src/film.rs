@@ -9,11 +9,48 @@ pub trait Film: Send + Sync {
 
   fn set_pixel(&mut self, x: u32, y: u32, color: ColorRgb);
   fn get_pixel(&self, x: u32, y: u32) -> ColorRgb;
-  
+  /// Write the pixels (and layers) of `tile` through `&self`, so that many threads may write
+  /// their tiles at once without locking the film. Tiles written at the same time never overlap.
+  fn write_tile(&self, tile: &FilmTile);
+
   fn to_image_linear(&self) -> image::RgbaImage;
   fn to_image_srgb(&self) -> image::RgbaImage;
+  /// The AOV layers held besides the color, none by default.
+  fn layers(&self) -> &[Aov] {
+    &[]
+  }
+  /// Ignored if the film does not hold `aov`.
+  fn set_layer_pixel(&mut self, _aov: Aov, _x: u32, _y: u32, _value: ColorRgb) {}
+  /// None if the film does not hold `aov`.
+  fn get_layer_pixel(&self, _aov: Aov, _x: u32, _y: u32) -> Option<ColorRgb> {
+    None
+  }
+
+  /// Linear and unclamped, for the HDR formats.
+  fn to_image_hdr(&self) -> image::Rgb32FImage {
+    image::Rgb32FImage::from_fn(self.width(), self.height(), |x, y| self.get_pixel(x, y).to_rgb32f())
+  }
+  /// Like `to_image_hdr`, for one AOV layer.
+  fn to_layer_image_hdr(&self, aov: Aov) -> Option<image::Rgb32FImage> {
+    if !self.layers().contains(&aov) {
+      return None;
+    }
+    Some(image::Rgb32FImage::from_fn(self.width(), self.height(), |x, y| {
+      self.get_layer_pixel(aov, x, y).unwrap_or_default().to_rgb32f()
+    }))
+  }
 }
 
+mod accumulation;
+mod aov;
+mod output;
+mod pixel_filter;
 mod simple;
+mod tile;
 
+pub use accumulation::AccumulationBuffer;
+pub use aov::{Aov, AovAccumulator, samples_heatmap};
+pub use output::{ExrPrecision, OutputFormat};
+pub use pixel_filter::PixelFilter;
 pub use simple::SimpleFilm;
+pub use tile::{FilmTile, Tile, TileOrder};
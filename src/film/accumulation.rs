@@ -0,0 +1,144 @@
+use crate::prelude::*;
+
+/// Weighted sums of samples, for renderers that add samples to a pixel over time.
+/// The value of a pixel is `sum / weight`, where samples are splatted through a `PixelFilter`.
+/// The luminance of the samples taken in each pixel is also summed (squared too, unweighted),
+/// to estimate how noisy the pixel still is.
+///
+/// A buffer may be a window of the film, from (`x0`, `y0`): samples falling outside of it are
+/// dropped, and `merge` adds windows into a whole buffer.
+pub struct AccumulationBuffer {
+  x0: i32,
+  y0: i32,
+  width: u32,
+  height: u32,
+  sum: Vec<ColorRgb>,
+  weight: Vec<Float>,
+  // samples taken in the pixel, and their luminance summed and squared.
+  count: Vec<u32>,
+  sum_lum: Vec<Float>,
+  sum_sq: Vec<Float>,
+}
+
+impl AccumulationBuffer {
+  // pixels darker than this are judged by their absolute error instead.
+  const MIN_RELATIVE_LUMINANCE: Float = 0.01;
+
+  pub fn new(width: u32, height: u32) -> Self {
+    Self::new_window(0, 0, width, height)
+  }
+
+  /// `width` x `height` pixels of the film, from (`x0`, `y0`), which may be off the film.
+  pub fn new_window(x0: i32, y0: i32, width: u32, height: u32) -> Self {
+    let len = (width * height) as usize;
+    Self {
+      x0,
+      y0,
+      width,
+      height,
+      sum: vec![ColorRgb::BLACK; len],
+      weight: vec![0.0; len],
+      count: vec![0; len],
+      sum_lum: vec![0.0; len],
+      sum_sq: vec![0.0; len],
+    }
+  }
+
+  pub fn width(&self) -> u32 {
+    self.width
+  }
+  pub fn height(&self) -> u32 {
+    self.height
+  }
+
+  // index of the film pixel (x, y), None if it is outside.
+  #[inline]
+  fn index(&self, x: i32, y: i32) -> Option<usize> {
+    let (x, y) = (x - self.x0, y - self.y0);
+    if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+      return None;
+    }
+    Some((y as u32 * self.width + x as u32) as usize)
+  }
+
+  /// Add a sample at film position (`x`, `y`), in pixels: pixel (i, j) spans [i, i + 1) x [j, j + 1).
+  pub fn add_sample(&mut self, x: Float, y: Float, color: ColorRgb, filter: &PixelFilter) {
+    let luminance = color.luminance();
+    if let Some(idx) = self.index(x.floor() as i32, y.floor() as i32) {
+      self.count[idx] += 1;
+      self.sum_lum[idx] += luminance;
+      self.sum_sq[idx] += luminance * luminance;
+    }
+    // the pixels whose centers are in (p - radius, p + radius].
+    let radius = filter.radius();
+    let (x_min, x_max) = ((x - 0.5 - radius).floor() as i32 + 1, (x - 0.5 + radius).floor() as i32);
+    let (y_min, y_max) = ((y - 0.5 - radius).floor() as i32 + 1, (y - 0.5 + radius).floor() as i32);
+    for py in y_min.max(self.y0)..=y_max.min(self.y0 + self.height as i32 - 1) {
+      for px in x_min.max(self.x0)..=x_max.min(self.x0 + self.width as i32 - 1) {
+        let weight = filter.eval(px as Float + 0.5 - x, py as Float + 0.5 - y);
+        if weight != 0.0 {
+          let idx = self.index(px, py).unwrap();
+          self.sum[idx] += color * weight;
+          self.weight[idx] += weight;
+        }
+      }
+    }
+  }
+
+  /// Add the sums of `other` where the windows overlap.
+  pub fn merge(&mut self, other: &AccumulationBuffer) {
+    for y in 0..other.height as i32 {
+      for x in 0..other.width as i32 {
+        let Some(idx) = self.index(other.x0 + x, other.y0 + y) else {
+          continue;
+        };
+        let from = (y as u32 * other.width + x as u32) as usize;
+        self.sum[idx] += other.sum[from];
+        self.weight[idx] += other.weight[from];
+        self.count[idx] += other.count[from];
+        self.sum_lum[idx] += other.sum_lum[from];
+        self.sum_sq[idx] += other.sum_sq[from];
+      }
+    }
+  }
+
+  /// Black where nothing is added yet. (x, y) is relative to the window.
+  #[inline]
+  pub fn get(&self, x: u32, y: u32) -> ColorRgb {
+    let idx = (y * self.width + x) as usize;
+    if self.weight[idx] > 0.0 { self.sum[idx] / self.weight[idx] } else { ColorRgb::BLACK }
+  }
+  /// Total filter weight of the pixel.
+  #[inline]
+  pub fn weight(&self, x: u32, y: u32) -> Float {
+    self.weight[(y * self.width + x) as usize]
+  }
+  /// Samples taken in the pixel.
+  #[inline]
+  pub fn samples(&self, x: u32, y: u32) -> u32 {
+    self.count[(y * self.width + x) as usize]
+  }
+
+  /// Standard error of the mean luminance of the samples taken in the pixel, relative to the
+  /// mean. Infinite with fewer than 2 samples.
+  pub fn relative_error(&self, x: u32, y: u32) -> Float {
+    let idx = (y * self.width + x) as usize;
+    let n = self.count[idx] as Float;
+    if n < 2.0 {
+      return Float::INFINITY;
+    }
+    let mean = self.sum_lum[idx] / n;
+    // unbiased sample variance.
+    let variance = ((self.sum_sq[idx] - n * mean * mean) / (n - 1.0)).max(0.0);
+    (variance / n).sqrt() / mean.abs().max(Self::MIN_RELATIVE_LUMINANCE)
+  }
+
+  /// Write the current values to the color of `film`, which shall be as large.
+  pub fn resolve(&self, film: &mut dyn Film) {
+    for y in 0..self.height {
+      for x in 0..self.width {
+        film.set_pixel(x, y, self.get(x, y));
+      }
+    }
+  }
+}
@@ -0,0 +1,252 @@
+use crate::prelude::*;
+
+/// Arbitrary output variables: what the camera rays first hit, stored beside the color.
+/// Every layer is kept as `ColorRgb`; scalars are repeated in all of r, g, b.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Aov {
+  /// Reflectance of the material, or the background color where nothing is hit.
+  Albedo,
+  /// World space shading normal, 0 where nothing is hit.
+  Normal,
+  /// Distance from the camera, averaged over the samples that hit. Infinite where nothing is hit.
+  Depth,
+  /// World space point, averaged over the samples that hit.
+  Position,
+  /// Texture coordinates (u, v, 0), averaged over the samples that hit.
+  Uv,
+  /// `World::material_id` of the first sample, 0 for the background and unknown materials.
+  MaterialId,
+  /// `HitRecord::object_id` of the first sample, 0 for the background.
+  ObjectId,
+  /// Camera samples taken for the pixel.
+  SampleCount,
+}
+
+impl Aov {
+  pub const ALL: [Aov; 8] = [
+    Aov::Albedo,
+    Aov::Normal,
+    Aov::Depth,
+    Aov::Position,
+    Aov::Uv,
+    Aov::MaterialId,
+    Aov::ObjectId,
+    Aov::SampleCount,
+  ];
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Aov::Albedo => "albedo",
+      Aov::Normal => "normal",
+      Aov::Depth => "depth",
+      Aov::Position => "position",
+      Aov::Uv => "uv",
+      Aov::MaterialId => "material_id",
+      Aov::ObjectId => "object_id",
+      Aov::SampleCount => "samples",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Self> {
+    Self::ALL.into_iter().find(|aov| aov.name() == name)
+  }
+
+  /// Names of the meaningful channels, as in `<name>.<channel>` of a multi-layer EXR.
+  pub fn channels(&self) -> &'static [&'static str] {
+    match self {
+      Aov::Albedo => &["R", "G", "B"],
+      Aov::Normal | Aov::Position => &["X", "Y", "Z"],
+      Aov::Uv => &["U", "V"],
+      Aov::Depth => &["Z"],
+      Aov::MaterialId | Aov::ObjectId => &["id"],
+      Aov::SampleCount => &["count"],
+    }
+  }
+
+  /// Whether the camera rays have to be traced for this layer.
+  pub fn needs_hit(&self) -> bool {
+    *self != Aov::SampleCount
+  }
+
+  /// Whether the values are integers, which are stored as such in EXR files.
+  pub fn is_integer(&self) -> bool {
+    matches!(self, Aov::MaterialId | Aov::ObjectId | Aov::SampleCount)
+  }
+}
+
+impl std::fmt::Display for Aov {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.name())
+  }
+}
+
+/// The `Aov::SampleCount` layer of `film` in false colors, from blue (fewest samples) to red
+/// (most), for tuning adaptive sampling. None if the film has no such layer.
+pub fn samples_heatmap(film: &dyn Film) -> Option<image::RgbImage> {
+  let counts = film.to_layer_image_hdr(Aov::SampleCount)?;
+  let count = |p: &image::Rgb<f32>| ColorRgb::from_rgb32f(*p).r;
+  let (min, max) = counts
+    .pixels()
+    .fold((Float::MAX, 0.0 as Float), |(min, max), p| (min.min(count(p)), max.max(count(p))));
+  const STOPS: [ColorRgb; 5] = [
+    ColorRgb { r: 0.05, g: 0.05, b: 0.5 },
+    ColorRgb { r: 0.0, g: 0.6, b: 1.0 },
+    ColorRgb { r: 0.1, g: 0.9, b: 0.2 },
+    ColorRgb { r: 1.0, g: 0.9, b: 0.0 },
+    ColorRgb { r: 0.9, g: 0.1, b: 0.0 },
+  ];
+  Some(image::RgbImage::from_fn(counts.width(), counts.height(), |x, y| {
+    let t = if max > min { (count(counts.get_pixel(x, y)) - min) / (max - min) } else { 0.0 };
+    let pos = t * (STOPS.len() - 1) as Float;
+    let idx = (pos as usize).min(STOPS.len() - 2);
+    let c = ColorRgb::lerp(STOPS[idx], STOPS[idx + 1], pos - idx as Float);
+    let to_u8 = |v: Float| (v.clamp(0.0, 1.0) * 255.999) as u8;
+    image::Rgb([to_u8(c.r), to_u8(c.g), to_u8(c.b)])
+  }))
+}
+
+/// Sums the AOVs of the camera samples of one pixel.
+pub struct AovAccumulator<'a> {
+  layers: &'a [Aov],
+  sums: Vec<ColorRgb>,
+  samples: u32,
+  hits: u32,
+  // ids are not averaged, the first sample decides.
+  material_id: Option<u32>,
+  object_id: Option<u32>,
+}
+
+impl<'a> AovAccumulator<'a> {
+  pub fn new(layers: &'a [Aov]) -> Self {
+    Self {
+      layers,
+      sums: vec![ColorRgb::BLACK; layers.len()],
+      samples: 0,
+      hits: 0,
+      material_id: None,
+      object_id: None,
+    }
+  }
+
+  /// Add a camera sample, `record` is where `ray` first hits.
+  pub fn add(&mut self, ray: &Ray, record: Option<&HitRecord>, world: &World) {
+    let first = self.samples == 0;
+    self.samples += 1;
+    let Some(record) = record else {
+      for (aov, sum) in self.layers.iter().zip(&mut self.sums) {
+        if *aov == Aov::Albedo {
+          *sum += world.background(ray);
+        }
+      }
+      if first {
+        (self.material_id, self.object_id) = (Some(0), Some(0));
+      }
+      return;
+    };
+    self.hits += 1;
+    if first {
+      self.material_id = Some(world.material_id(&record.material));
+      self.object_id = Some(record.object_id);
+    }
+    let vec = |v: Vec3d| ColorRgb::new(v.x, v.y, v.z);
+    for (aov, sum) in self.layers.iter().zip(&mut self.sums) {
+      *sum += match aov {
+        Aov::Albedo => record.material.albedo(record),
+        Aov::Normal => vec(record.unit_normal),
+        Aov::Depth => {
+          let depth = record.hit_t * ray.direction.length();
+          ColorRgb::new(depth, depth, depth)
+        }
+        Aov::Position => vec(record.point),
+        Aov::Uv => ColorRgb::new(record.mat_uv.u, record.mat_uv.v, 0.0),
+        Aov::MaterialId | Aov::ObjectId | Aov::SampleCount => ColorRgb::BLACK,
+      };
+    }
+  }
+
+  /// (layer, value) of every layer, from the samples added so far.
+  pub fn values(&self) -> impl Iterator<Item = (Aov, ColorRgb)> + '_ {
+    let Self { layers, sums, samples, hits, material_id, object_id } = self;
+    let (samples, hits) = (*samples, *hits);
+    layers.iter().zip(sums).map(move |(aov, sum)| {
+      let sum = *sum;
+      let gray = |v: Float| ColorRgb::new(v, v, v);
+      let value = match aov {
+        Aov::Albedo | Aov::Normal => sum / samples.max(1) as Float,
+        Aov::Depth if hits == 0 => gray(Float::INFINITY),
+        Aov::Depth | Aov::Position | Aov::Uv => sum / hits.max(1) as Float,
+        Aov::MaterialId => gray(material_id.unwrap_or(0) as Float),
+        Aov::ObjectId => gray(object_id.unwrap_or(0) as Float),
+        Aov::SampleCount => gray(samples as Float),
+      };
+      (*aov, value)
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_aov_layers() {
+    let background = ColorRgb::new(0.1, 0.2, 0.3);
+    let mut world = World::new(move |_ray| background);
+    let red: Arc<dyn Material> =
+      material::Lambertian::new_arc(texture::SolidColorTexture::new_arc(ColorRgb::new(0.8, 0.1, 0.1)));
+    world.register_material(&red);
+    world.add_object(geometry::UnitSphere::new_arc(red));
+    world.bvh_finalize();
+
+    let layers = [Aov::Albedo, Aov::Normal, Aov::Depth, Aov::MaterialId, Aov::ObjectId, Aov::SampleCount];
+    let film = Arc::new(Mutex::new(film::SimpleFilm::new(8, 8).with_layers(&layers)));
+    let camera = OrthographicCamera::new(
+      Point::new(0.0, 0.0, 5.0),
+      Point::new(0.0, 0.0, 0.0),
+      Direction::new(0.0, 1.0, 0.0),
+      4.0,
+      1.0,
+    );
+    renderer::SimpleRenderer::new(4, 2).render(RenderConfig {
+      film: film.clone(),
+      camera: Arc::new(camera),
+      filters: Arc::new(FilterList::new()),
+      world: Arc::new(world),
+      seed: 0,
+    });
+    let film = film.lock().unwrap();
+    assert_eq!(film.layers(), &layers);
+    let layer = |aov, x, y| film.get_layer_pixel(aov, x, y).unwrap();
+
+    // the sphere is in the middle, the corners see the background.
+    assert_eq!(layer(Aov::Albedo, 4, 4), ColorRgb::new(0.8, 0.1, 0.1));
+    assert!(layer(Aov::Normal, 4, 4).b > 0.3);
+    let depth = layer(Aov::Depth, 4, 4).r;
+    assert!((4.0..=5.0).contains(&depth), "{}", depth);
+    assert_eq!(layer(Aov::MaterialId, 4, 4).r, 1.0);
+    assert_eq!(layer(Aov::ObjectId, 4, 4).r, 1.0);
+    assert_eq!(layer(Aov::Albedo, 0, 0), background);
+    assert_eq!(layer(Aov::Normal, 0, 0), ColorRgb::BLACK);
+    assert_eq!(layer(Aov::Depth, 0, 0).r, Float::INFINITY);
+    assert_eq!(layer(Aov::ObjectId, 0, 0).r, 0.0);
+    assert_eq!(layer(Aov::SampleCount, 0, 0).r, 4.0);
+    assert!(film.get_layer_pixel(Aov::Uv, 4, 4).is_none());
+
+    // a multi-layer EXR has the color and every layer as channels.
+    let path = std::env::temp_dir().join(format!("raytracer-ramel-aov-{}.exr", std::process::id()));
+    film::OutputFormat::Exr(film::ExrPrecision::Half).save(&*film, &path).unwrap();
+    let image = exr::prelude::read_all_flat_layers_from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let channels = &image.layer_data[0].channel_data.list;
+    let names: Vec<String> = channels.iter().map(|c| c.name.to_string()).collect();
+    assert_eq!(
+      names,
+      [
+        "B", "G", "R", "albedo.B", "albedo.G", "albedo.R", "depth.Z", "material_id.id", "normal.X",
+        "normal.Y", "normal.Z", "object_id.id", "samples.count"
+      ]
+    );
+    let object_id = channels.iter().find(|c| c.name.to_string() == "object_id.id").unwrap();
+    assert!(matches!(object_id.sample_data, exr::prelude::FlatSamples::U32(_)));
+  }
+}
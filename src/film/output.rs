@@ -0,0 +1,215 @@
+use crate::prelude::*;
+use std::io::Write;
+use std::path::Path;
+
+/// Bits per channel of an OpenEXR file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ExrPrecision {
+  Half,
+  #[default]
+  Float,
+}
+
+/// How a film is saved, chosen by the file extension.
+/// The HDR formats keep the linear, unclamped radiance; the others are 8-bit sRGB.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+  /// Anything `image` can write, like png or jpg.
+  Ldr(image::ImageFormat),
+  /// `.exr`
+  Exr(ExrPrecision),
+  /// `.hdr`, Radiance RGBE. Negative values are lost.
+  Hdr,
+  /// `.pfm`, Portable Float Map.
+  Pfm,
+}
+
+impl OutputFormat {
+  /// None if the extension is missing or unknown.
+  pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+    let path = path.as_ref();
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+      "exr" => Some(Self::Exr(ExrPrecision::default())),
+      "hdr" => Some(Self::Hdr),
+      "pfm" => Some(Self::Pfm),
+      _ => image::ImageFormat::from_path(path).ok().map(Self::Ldr),
+    }
+  }
+
+  pub fn is_hdr(&self) -> bool {
+    !matches!(self, Self::Ldr(_))
+  }
+
+  /// The AOV layers of `film` go into the same file as channels `<layer>.<channel>` if it is
+  /// an EXR file, and are not saved otherwise; see `save_layer`.
+  pub fn save<P: AsRef<Path>>(&self, film: &dyn Film, path: P) -> image::ImageResult<()> {
+    let path = path.as_ref();
+    match *self {
+      Self::Ldr(format) => film.to_image_srgb().save_with_format(path, format),
+      Self::Exr(precision) => save_exr(film, path, precision),
+      Self::Hdr => save_radiance(&film.to_image_hdr(), path),
+      Self::Pfm => save_pfm(&film.to_image_hdr(), path).map_err(image::ImageError::IoError),
+    }
+  }
+
+  /// Save one AOV layer of `film` as an image of its own.
+  /// The HDR formats keep the values, EXR also keeps the channel names. In 8-bit formats, the
+  /// albedo is sRGB encoded, normals are mapped from [-1, 1], and the other layers are divided
+  /// by their largest finite value.
+  pub fn save_layer<P: AsRef<Path>>(&self, film: &dyn Film, aov: Aov, path: P) -> image::ImageResult<()> {
+    let path = path.as_ref();
+    let Some(image) = film.to_layer_image_hdr(aov) else {
+      return Err(image::ImageError::Parameter(image::error::ParameterError::from_kind(
+        image::error::ParameterErrorKind::Generic(format!("the film has no {} layer", aov)),
+      )));
+    };
+    match *self {
+      Self::Ldr(format) => {
+        let colors = image.pixels().map(|p| ColorRgb::from_rgb32f(*p));
+        let max = colors.flat_map(|c| [c.r, c.g, c.b]).filter(|v| v.is_finite()).fold(0.0, Float::max);
+        let scale = if max > 0.0 { 1.0 / max } else { 1.0 };
+        let image = image::RgbImage::from_fn(image.width(), image.height(), |x, y| {
+          let c = ColorRgb::from_rgb32f(*image.get_pixel(x, y));
+          let c = match aov {
+            Aov::Albedo => c.to_gamma(),
+            Aov::Normal => c * 0.5 + ColorRgb::new(0.5, 0.5, 0.5),
+            _ => c * scale,
+          };
+          let to_u8 = |v: Float| (v.clamp(0.0, 1.0) * 255.999) as u8;
+          image::Rgb([to_u8(c.r), to_u8(c.g), to_u8(c.b)])
+        });
+        image.save_with_format(path, format)
+      }
+      Self::Exr(precision) => {
+        let (width, height) = (film.width() as usize, film.height() as usize);
+        write_exr(path, width, height, layer_channels(&image, aov, "", precision))
+      }
+      Self::Hdr => save_radiance(&image, path),
+      Self::Pfm => save_pfm(&image, path).map_err(image::ImageError::IoError),
+    }
+  }
+}
+
+// The color as R, G, B, then every layer as `<layer>.<channel>`, all in one part,
+// the way most compositors expect multi-layer files.
+fn save_exr(film: &dyn Film, path: &Path, precision: ExrPrecision) -> image::ImageResult<()> {
+  let (width, height) = (film.width() as usize, film.height() as usize);
+  let color = film.to_image_hdr();
+  let mut channels = float_channels(&color, &["R", "G", "B"], precision);
+  for aov in film.layers() {
+    if let Some(image) = film.to_layer_image_hdr(*aov) {
+      channels.extend(layer_channels(&image, *aov, &format!("{}.", aov.name()), precision));
+    }
+  }
+  write_exr(path, width, height, channels)
+}
+
+type ExrChannel = exr::prelude::AnyChannel<exr::prelude::FlatSamples>;
+
+// The first `names.len()` channels of `image`.
+fn float_channels(image: &image::Rgb32FImage, names: &[&str], precision: ExrPrecision) -> Vec<ExrChannel> {
+  use exr::prelude::{AnyChannel, FlatSamples, f16};
+  names
+    .iter()
+    .enumerate()
+    .map(|(c, name)| {
+      let values = image.pixels().map(|p| p.0[c]);
+      let samples = match precision {
+        ExrPrecision::Half => FlatSamples::F16(values.map(f16::from_f32).collect()),
+        ExrPrecision::Float => FlatSamples::F32(values.collect()),
+      };
+      AnyChannel::new(*name, samples)
+    })
+    .collect()
+}
+
+fn layer_channels(image: &image::Rgb32FImage, aov: Aov, prefix: &str, precision: ExrPrecision) -> Vec<ExrChannel> {
+  let names: Vec<String> = aov.channels().iter().map(|name| format!("{}{}", prefix, name)).collect();
+  if aov.is_integer() {
+    let values: Vec<u32> = image.pixels().map(|p| p.0[0] as u32).collect();
+    vec![exr::prelude::AnyChannel::new(names[0].as_str(), exr::prelude::FlatSamples::U32(values))]
+  } else {
+    let names: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+    float_channels(image, &names, precision)
+  }
+}
+
+fn write_exr(path: &Path, width: usize, height: usize, channels: Vec<ExrChannel>) -> image::ImageResult<()> {
+  use exr::prelude::*;
+  let channels = AnyChannels::sort(SmallVec::from_vec(channels));
+  let layer = Layer::new((width, height), LayerAttributes::default(), Encoding::FAST_LOSSLESS, channels);
+  Image::from_layer(layer).write().to_file(path).map_err(|e| {
+    image::ImageError::Encoding(image::error::EncodingError::new(
+      image::error::ImageFormatHint::Exact(image::ImageFormat::OpenExr),
+      e,
+    ))
+  })
+}
+
+fn save_radiance(image: &image::Rgb32FImage, path: &Path) -> image::ImageResult<()> {
+  // RGBE has neither sign nor NaN.
+  let pixels: Vec<image::Rgb<f32>> =
+    image.pixels().map(|p| image::Rgb(p.0.map(|c| if c > 0.0 { c } else { 0.0 }))).collect();
+  let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+  image::codecs::hdr::HdrEncoder::new(file).encode(&pixels, image.width() as usize, image.height() as usize)
+}
+
+// Little endian (negative scale), rows from the bottom up.
+fn save_pfm(image: &image::Rgb32FImage, path: &Path) -> std::io::Result<()> {
+  let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+  write!(file, "PF\n{} {}\n-1.0\n", image.width(), image.height())?;
+  for row in image.rows().rev() {
+    for pixel in row {
+      for c in pixel.0 {
+        file.write_all(&c.to_le_bytes())?;
+      }
+    }
+  }
+  file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_hdr_output() {
+    let mut film = film::SimpleFilm::new(3, 2);
+    film.set_pixel(0, 0, ColorRgb::new(4.5, 0.25, 0.0));
+    film.set_pixel(2, 1, ColorRgb::new(1000.0, 0.5, 2.0));
+    assert_eq!(OutputFormat::from_path("a/b.EXR"), Some(OutputFormat::Exr(ExrPrecision::Float)));
+    assert_eq!(OutputFormat::from_path("b.png"), Some(OutputFormat::Ldr(image::ImageFormat::Png)));
+    assert_eq!(OutputFormat::from_path("b.unknown"), None);
+
+    let dir = std::env::temp_dir().join(format!("raytracer-ramel-hdr-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    // read back through `image`, values above 1 must survive.
+    for (name, tolerance) in [("float.exr", 0.0), ("half.exr", 0.5), ("rgbe.hdr", 8.0)] {
+      let path = dir.join(name);
+      let format = match name {
+        "half.exr" => OutputFormat::Exr(ExrPrecision::Half),
+        _ => OutputFormat::from_path(&path).unwrap(),
+      };
+      format.save(&film, &path).unwrap();
+      let image = image::open(&path).unwrap().into_rgb32f();
+      assert_eq!(image.dimensions(), (3, 2));
+      let [r, g, _] = image.get_pixel(0, 0).0;
+      assert!((r - 4.5).abs() <= tolerance / 100.0 + 1e-6 && (g - 0.25).abs() < 1e-2, "{}", name);
+      assert!((image.get_pixel(2, 1).0[0] - 1000.0).abs() <= tolerance, "{}", name);
+    }
+
+    let path = dir.join("float.pfm");
+    OutputFormat::Pfm.save(&film, &path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    let header = b"PF\n3 2\n-1.0\n";
+    assert_eq!(&bytes[..header.len()], header);
+    // the first pixel in the file is the bottom left one.
+    let floats: Vec<f32> =
+      bytes[header.len()..].chunks(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect();
+    assert_eq!(floats.len(), 3 * 2 * 3);
+    assert_eq!(floats[2 * 3], 1000.0);
+    assert_eq!(&floats[3 * 3..3 * 3 + 3], &[4.5, 0.25, 0.0]);
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
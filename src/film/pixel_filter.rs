@@ -0,0 +1,150 @@
+use crate::prelude::*;
+
+/// How the samples around a pixel are weighted into it (reconstruction filter).
+/// Every sample is splatted into all the pixels whose centers are within `radius` of it (in
+/// pixels, on each axis), weighted by `eval`, and every pixel is divided by its total weight.
+/// The filters are separable: the weight is the product of those of dx and dy.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PixelFilter {
+  /// Radius 0.5 keeps every sample in its own pixel, a plain average.
+  Box { radius: Float },
+  Tent { radius: Float },
+  /// Shifted down so it reaches 0 at the radius.
+  Gaussian { radius: Float, sigma: Float },
+  /// Mitchell-Netravali cubic, B = C = 1/3 is the usual choice.
+  /// The negative lobes sharpen, and may ring around strong edges.
+  Mitchell { radius: Float, b: Float, c: Float },
+  /// sinc(x) * sinc(x / radius), sharp with some ringing.
+  Lanczos { radius: Float },
+}
+
+impl Default for PixelFilter {
+  fn default() -> Self {
+    PixelFilter::Box { radius: 0.5 }
+  }
+}
+
+impl PixelFilter {
+  pub const NAMES: [&'static str; 5] = ["box", "tent", "gaussian", "mitchell", "lanczos"];
+
+  /// The filter called `name`, with the usual parameters.
+  pub fn from_name(name: &str) -> Option<Self> {
+    Some(match name {
+      "box" => PixelFilter::Box { radius: 0.5 },
+      "tent" => PixelFilter::Tent { radius: 1.0 },
+      "gaussian" => PixelFilter::Gaussian { radius: 1.5, sigma: 0.5 },
+      "mitchell" => PixelFilter::Mitchell { radius: 2.0, b: 1.0 / 3.0, c: 1.0 / 3.0 },
+      "lanczos" => PixelFilter::Lanczos { radius: 3.0 },
+      _ => return None,
+    })
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      PixelFilter::Box { .. } => "box",
+      PixelFilter::Tent { .. } => "tent",
+      PixelFilter::Gaussian { .. } => "gaussian",
+      PixelFilter::Mitchell { .. } => "mitchell",
+      PixelFilter::Lanczos { .. } => "lanczos",
+    }
+  }
+
+  pub fn radius(&self) -> Float {
+    match *self {
+      PixelFilter::Box { radius }
+      | PixelFilter::Tent { radius }
+      | PixelFilter::Gaussian { radius, .. }
+      | PixelFilter::Mitchell { radius, .. }
+      | PixelFilter::Lanczos { radius } => radius,
+    }
+  }
+
+  /// Weight of a sample at offset (dx, dy) from the center of a pixel.
+  #[inline]
+  pub fn eval(&self, dx: Float, dy: Float) -> Float {
+    self.eval_1d(dx) * self.eval_1d(dy)
+  }
+
+  fn eval_1d(&self, x: Float) -> Float {
+    let x = x.abs();
+    match *self {
+      PixelFilter::Box { radius } => {
+        if x <= radius { 1.0 } else { 0.0 }
+      }
+      PixelFilter::Tent { radius } => (radius - x).max(0.0),
+      PixelFilter::Gaussian { radius, sigma } => {
+        let gaussian = |x: Float| (-x * x / (2.0 * sigma * sigma)).exp();
+        if x < radius { (gaussian(x) - gaussian(radius)).max(0.0) } else { 0.0 }
+      }
+      PixelFilter::Mitchell { radius, b, c } => {
+        if x >= radius {
+          return 0.0;
+        }
+        // the cubic spans [0, 2].
+        let x = 2.0 * x / radius;
+        let (x2, x3) = (x * x, x * x * x);
+        let value = if x < 1.0 {
+          (12.0 - 9.0 * b - 6.0 * c) * x3 + (-18.0 + 12.0 * b + 6.0 * c) * x2 + (6.0 - 2.0 * b)
+        } else {
+          (-b - 6.0 * c) * x3 + (6.0 * b + 30.0 * c) * x2 + (-12.0 * b - 48.0 * c) * x + (8.0 * b + 24.0 * c)
+        };
+        value / 6.0
+      }
+      PixelFilter::Lanczos { radius } => {
+        if x >= radius { 0.0 } else { sinc(x) * sinc(x / radius) }
+      }
+    }
+  }
+}
+
+#[inline]
+fn sinc(x: Float) -> Float {
+  if x < 1e-5 { 1.0 } else { (PI * x).sin() / (PI * x) }
+}
+
+impl std::fmt::Display for PixelFilter {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} (radius {})", self.name(), self.radius())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_pixel_filters() {
+    for name in PixelFilter::NAMES {
+      let filter = PixelFilter::from_name(name).unwrap();
+      assert_eq!(filter.name(), name);
+      assert!(filter.eval(0.0, 0.0) > 0.0, "{}", name);
+      assert_eq!(filter.eval(filter.radius() + 0.01, 0.0), 0.0, "{}", name);
+
+      // a flat image stays flat, up to the edges, whatever the filter.
+      let mut buffer = film::AccumulationBuffer::new(6, 5);
+      let mut sampler = sampler::StratifiedSampler::new(4, 4, 0);
+      for y in 0..5 {
+        for x in 0..6 {
+          for index in 0..16 {
+            sampler.start_pixel_sample((x, y), index);
+            let (dx, dy) = sampler.get_2d();
+            buffer.add_sample(x as Float + dx, y as Float + dy, ColorRgb::new(0.25, 0.5, 2.0), &filter);
+          }
+        }
+      }
+      for (x, y) in [(0, 0), (3, 2), (5, 4)] {
+        let c = buffer.get(x, y);
+        assert!((c.r - 0.25).abs() < 1e-4 && (c.b - 2.0).abs() < 1e-4, "{}: {:?}", name, c);
+      }
+    }
+
+    // a sample spreads to the neighbours by the tent.
+    let tent = PixelFilter::Tent { radius: 1.0 };
+    let mut buffer = film::AccumulationBuffer::new(3, 3);
+    buffer.add_sample(1.5, 1.5, ColorRgb::WHITE, &tent);
+    buffer.add_sample(2.5, 1.5, ColorRgb::BLACK, &tent);
+    assert_eq!(buffer.weight(1, 1), 1.0);
+    assert_eq!(buffer.weight(0, 1), 0.0);
+    assert_eq!(buffer.get(2, 1), ColorRgb::BLACK);
+  }
+}
@@ -1,9 +1,41 @@
 use crate::prelude::*;
+use std::sync::atomic::Ordering;
 
+/// Pixels are atomics, so that tiles are written into the film from many threads through
+/// `write_tile`, without a lock.
 pub struct SimpleFilm {
   width: u32,
   height: u32,
-  buffer: Vec<ColorRgb>,
+  buffer: Vec<AtomicColor>,
+  layers: Vec<Aov>,
+  // one buffer for each of `layers`.
+  layer_buffers: Vec<Vec<AtomicColor>>,
+}
+
+// The bits of r, g and b. Relaxed is enough: the tiles never overlap, and the render joins its
+// threads before the film is read.
+#[derive(Default)]
+struct AtomicColor([AtomicFloatBits; 3]);
+
+impl AtomicColor {
+  #[inline]
+  fn load(&self) -> ColorRgb {
+    let [r, g, b] = &self.0;
+    let load = |c: &AtomicFloatBits| Float::from_bits(c.load(Ordering::Relaxed));
+    ColorRgb::new(load(r), load(g), load(b))
+  }
+  #[inline]
+  fn store(&self, color: ColorRgb) {
+    let [r, g, b] = &self.0;
+    r.store(color.r.to_bits(), Ordering::Relaxed);
+    g.store(color.g.to_bits(), Ordering::Relaxed);
+    b.store(color.b.to_bits(), Ordering::Relaxed);
+  }
+}
+
+// All black.
+fn black_buffer(len: u32) -> Vec<AtomicColor> {
+  (0..len).map(|_| AtomicColor::default()).collect()
 }
 
 impl SimpleFilm {
@@ -12,8 +44,25 @@ impl SimpleFilm {
     Self {
       width,
       height,
-      buffer: vec![ColorRgb::BLACK; (width * height) as usize],
+      buffer: black_buffer(width * height),
+      layers: Vec::new(),
+      layer_buffers: Vec::new(),
+    }
+  }
+
+  /// Also hold the `aovs` layers, which the renderer fills.
+  pub fn with_layers(mut self, aovs: &[Aov]) -> Self {
+    for aov in aovs {
+      if !self.layers.contains(aov) {
+        self.layers.push(*aov);
+        self.layer_buffers.push(black_buffer(self.width * self.height));
+      }
     }
+    self
+  }
+
+  fn layer_index(&self, aov: Aov) -> Option<usize> {
+    self.layers.iter().position(|layer| *layer == aov)
   }
 }
 
@@ -28,23 +77,46 @@ impl Film for SimpleFilm {
   }
   #[inline]
   fn set_pixel(&mut self, x: u32, y: u32, color: ColorRgb) {
-    self.buffer[(y * self.width + x) as usize] = color;
+    self.buffer[(y * self.width + x) as usize].store(color);
   }
   #[inline]
   fn get_pixel(&self, x: u32, y: u32) -> ColorRgb {
-    self.buffer[(y * self.width + x) as usize]
+    self.buffer[(y * self.width + x) as usize].load()
+  }
+  fn write_tile(&self, tile: &film::FilmTile) {
+    for (x, y) in tile.tile().pixels() {
+      let idx = (y * self.width + x) as usize;
+      self.buffer[idx].store(tile.get_pixel(x, y));
+      for (aov, value) in tile.layer_pixels(x, y) {
+        if let Some(layer) = self.layer_index(aov) {
+          self.layer_buffers[layer][idx].store(value);
+        }
+      }
+    }
+  }
+  fn layers(&self) -> &[Aov] {
+    &self.layers
+  }
+  fn set_layer_pixel(&mut self, aov: Aov, x: u32, y: u32, value: ColorRgb) {
+    if let Some(idx) = self.layer_index(aov) {
+      self.layer_buffers[idx][(y * self.width + x) as usize].store(value);
+    }
+  }
+  fn get_layer_pixel(&self, aov: Aov, x: u32, y: u32) -> Option<ColorRgb> {
+    let idx = self.layer_index(aov)?;
+    Some(self.layer_buffers[idx][(y * self.width + x) as usize].load())
   }
   fn to_image_linear(&self) -> image::RgbaImage {
     let mut image = image::RgbaImage::new(self.width, self.height);
     for (x, y, pixel) in image.enumerate_pixels_mut() {
-      *pixel = self.buffer[(y * self.width + x) as usize].into_rgba();
+      *pixel = self.buffer[(y * self.width + x) as usize].load().into_rgba();
     }
     image
   }
   fn to_image_srgb(&self) -> image::RgbaImage {
     let mut image = image::RgbaImage::new(self.width, self.height);
     for (x, y, pixel) in image.enumerate_pixels_mut() {
-      *pixel = self.buffer[(y * self.width + x) as usize].to_gamma().into_rgba();
+      *pixel = self.buffer[(y * self.width + x) as usize].load().to_gamma().into_rgba();
     }
     image
   }
@@ -0,0 +1,198 @@
+use crate::prelude::*;
+
+/// A rectangle of the film, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tile {
+  pub x0: u32,
+  pub y0: u32,
+  pub width: u32,
+  pub height: u32,
+}
+
+impl Tile {
+  #[inline]
+  pub fn contains(&self, x: u32, y: u32) -> bool {
+    x >= self.x0 && y >= self.y0 && x < self.x0 + self.width && y < self.y0 + self.height
+  }
+
+  /// (x, y) of the pixels, row by row.
+  pub fn pixels(&self) -> impl Iterator<Item = (u32, u32)> + use<> {
+    let Tile { x0, y0, width, height } = *self;
+    (y0..y0 + height).flat_map(move |y| (x0..x0 + width).map(move |x| (x, y)))
+  }
+}
+
+/// The order tiles are handed out to the threads in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TileOrder {
+  /// Row by row, from the top.
+  Scanline,
+  /// From the center outwards, where the subject usually is.
+  #[default]
+  Spiral,
+  /// Along a Hilbert curve, so that the tiles in flight stay close to each other.
+  Hilbert,
+}
+
+impl TileOrder {
+  pub const ALL: [TileOrder; 3] = [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert];
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      TileOrder::Scanline => "scanline",
+      TileOrder::Spiral => "spiral",
+      TileOrder::Hilbert => "hilbert",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Self> {
+    Self::ALL.into_iter().find(|order| order.name() == name)
+  }
+
+  /// The `size` x `size` tiles covering a `width` x `height` film, in this order.
+  /// The tiles of the last column and row are cut to the film.
+  pub fn tiles(&self, width: u32, height: u32, size: u32) -> Vec<Tile> {
+    let (nx, ny) = (width.div_ceil(size), height.div_ceil(size));
+    let tile = |(tx, ty): (u32, u32)| {
+      let (x0, y0) = (tx * size, ty * size);
+      Tile { x0, y0, width: size.min(width - x0), height: size.min(height - y0) }
+    };
+    let count = (nx * ny) as usize;
+    let grid: Vec<(u32, u32)> = match self {
+      TileOrder::Scanline => (0..ny).flat_map(|ty| (0..nx).map(move |tx| (tx, ty))).collect(),
+      TileOrder::Spiral => {
+        // walk right 1, down 1, left 2, up 2, right 3... from the center, keeping what is on the film.
+        let mut grid = Vec::with_capacity(count);
+        let (mut tx, mut ty) = ((nx as i64 - 1) / 2, (ny as i64 - 1) / 2);
+        let (mut dx, mut dy) = (1, 0);
+        let mut run = 1;
+        while grid.len() < count {
+          for _ in 0..2 {
+            for _ in 0..run {
+              if (0..nx as i64).contains(&tx) && (0..ny as i64).contains(&ty) {
+                grid.push((tx as u32, ty as u32));
+              }
+              (tx, ty) = (tx + dx, ty + dy);
+            }
+            (dx, dy) = (-dy, dx);
+          }
+          run += 1;
+        }
+        grid
+      }
+      TileOrder::Hilbert => {
+        let side = nx.max(ny).next_power_of_two();
+        let mut grid: Vec<_> = (0..ny).flat_map(|ty| (0..nx).map(move |tx| (tx, ty))).collect();
+        grid.sort_by_key(|&(tx, ty)| hilbert_index(side, tx, ty));
+        grid
+      }
+    };
+    grid.into_iter().map(tile).collect()
+  }
+}
+
+impl std::fmt::Display for TileOrder {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.name())
+  }
+}
+
+// Distance of (x, y) along the Hilbert curve filling a `side` x `side` grid, a power of 2.
+fn hilbert_index(side: u32, mut x: u32, mut y: u32) -> u64 {
+  let mut index = 0;
+  let mut s = side / 2;
+  while s > 0 {
+    let (rx, ry) = ((x & s > 0) as u32, (y & s > 0) as u32);
+    index += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+    // rotate the quadrant, so the curve inside it starts where the previous one ended.
+    if ry == 0 {
+      if rx == 1 {
+        (x, y) = (side - 1 - x, side - 1 - y);
+      }
+      (x, y) = (y, x);
+    }
+    s /= 2;
+  }
+  index
+}
+
+/// The final pixels of a tile (and of the AOV layers), which `Film::write_tile` writes at once.
+/// Coordinates are those of the film.
+pub struct FilmTile {
+  tile: Tile,
+  colors: Vec<ColorRgb>,
+  layers: Vec<Aov>,
+  // one buffer for each of `layers`.
+  layer_values: Vec<Vec<ColorRgb>>,
+}
+
+impl FilmTile {
+  pub fn new(tile: Tile, layers: &[Aov]) -> Self {
+    let len = (tile.width * tile.height) as usize;
+    Self {
+      tile,
+      colors: vec![ColorRgb::BLACK; len],
+      layers: layers.to_vec(),
+      layer_values: vec![vec![ColorRgb::BLACK; len]; layers.len()],
+    }
+  }
+
+  pub fn tile(&self) -> Tile {
+    self.tile
+  }
+
+  #[inline]
+  fn index(&self, x: u32, y: u32) -> usize {
+    debug_assert!(self.tile.contains(x, y));
+    ((y - self.tile.y0) * self.tile.width + x - self.tile.x0) as usize
+  }
+
+  pub fn set_pixel(&mut self, x: u32, y: u32, color: ColorRgb) {
+    let idx = self.index(x, y);
+    self.colors[idx] = color;
+  }
+  pub fn get_pixel(&self, x: u32, y: u32) -> ColorRgb {
+    self.colors[self.index(x, y)]
+  }
+  /// Ignored if the tile does not hold `aov`.
+  pub fn set_layer_pixel(&mut self, aov: Aov, x: u32, y: u32, value: ColorRgb) {
+    let idx = self.index(x, y);
+    if let Some(layer) = self.layers.iter().position(|layer| *layer == aov) {
+      self.layer_values[layer][idx] = value;
+    }
+  }
+  /// (layer, value) of every layer at (x, y).
+  pub fn layer_pixels(&self, x: u32, y: u32) -> impl Iterator<Item = (Aov, ColorRgb)> + '_ {
+    let idx = self.index(x, y);
+    self.layers.iter().zip(&self.layer_values).map(move |(aov, values)| (*aov, values[idx]))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_tile_orders() {
+    for order in TileOrder::ALL {
+      // every pixel in exactly one tile.
+      let tiles = order.tiles(100, 70, 32);
+      assert_eq!(tiles.len(), 12, "{}", order);
+      let mut covered = vec![0; 100 * 70];
+      for (x, y) in tiles.iter().flat_map(|tile| tile.pixels()) {
+        covered[(y * 100 + x) as usize] += 1;
+      }
+      assert!(covered.iter().all(|&n| n == 1), "{}", order);
+    }
+
+    let spiral = TileOrder::Spiral.tiles(96, 96, 32);
+    assert_eq!((spiral[0].x0, spiral[0].y0), (32, 32));
+    assert_eq!((spiral[1].x0, spiral[1].y0), (64, 32));
+    // every tile of the curve is next to the previous one.
+    let hilbert = TileOrder::Hilbert.tiles(128, 128, 32);
+    for pair in hilbert.windows(2) {
+      let (dx, dy) = (pair[0].x0.abs_diff(pair[1].x0), pair[0].y0.abs_diff(pair[1].y0));
+      assert_eq!(dx + dy, 32, "{:?}", pair);
+    }
+  }
+}
@@ -1,6 +1,6 @@
 use crate::prelude::*;
 
-pub trait Filter {
+pub trait Filter: Send + Sync {
   fn process(&self, film: &mut dyn Film);
 }
 
@@ -28,6 +28,12 @@ impl FilterList {
   }
 }
 
+impl Default for FilterList {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
 mod gamma;
 mod exposure;
 mod aces;
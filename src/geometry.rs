@@ -9,6 +9,10 @@ pub struct HitRecord {
   pub hit_t: Float,
   pub material: Arc<dyn Material>,
   pub mat_uv: UV,
+  // time of the ray, so that rays spawned from here see the scene at the same moment.
+  pub time: Float,
+  // `World::object_id` of the top level object hit, 0 if not hit through a world.
+  pub object_id: u32,
 }
 
 impl HitRecord {
@@ -33,9 +37,23 @@ impl HitRecord {
       unit_normal,
       hit_t,
       material,
-      mat_uv
+      mat_uv,
+      time: ray.time,
+      object_id: 0,
     }
   }
+
+  /// Spawn a ray leaving the hit point towards `direction`.
+  /// The origin is pushed to the side `direction` points to, to avoid hitting the surface again.
+  #[inline]
+  pub fn spawn_ray(&self, direction: Direction) -> Ray {
+    let offset = if direction.is_facing(self.unit_normal) {
+      Ray::DISTURBANCE_RATE
+    } else {
+      -Ray::DISTURBANCE_RATE
+    };
+    Ray::new_with_time(self.point + offset * self.unit_normal, direction, self.time)
+  }
 }
 
 #[derive(Copy, Clone)]
@@ -59,6 +77,17 @@ impl Aabb {
     };
     Self { min, max }
   }
+  pub fn centroid(&self) -> Point {
+    (self.min + self.max) * 0.5
+  }
+  /// 0 for an empty box.
+  pub fn surface_area(&self) -> Float {
+    let d = self.max - self.min;
+    if d.x < 0.0 || d.y < 0.0 || d.z < 0.0 {
+      return 0.0;
+    }
+    2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+  }
   pub fn longest_axis(&self) -> usize {
     let dx = self.max.x - self.min.x;
     let dy = self.max.y - self.min.y;
@@ -71,17 +100,25 @@ impl Aabb {
       2
     }
   }
-  pub fn might_hit(&self, ray: &Ray, mut t_min: Float, mut t_max: Float) -> bool {
+  pub fn might_hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> bool {
+    let inv_dir = Direction::new(1.0 / ray.direction.x, 1.0 / ray.direction.y, 1.0 / ray.direction.z);
+    self.might_hit_inv(&ray.origin, &inv_dir, t_min, t_max)
+  }
+  /// `might_hit` with the inverse of the ray direction computed once by the caller,
+  /// for testing one ray against many boxes.
+  #[inline]
+  pub fn might_hit_inv(&self, origin: &Point, inv_dir: &Direction, mut t_min: Float, mut t_max: Float) -> bool {
     for i in 0..3 {
-      let inv_d = 1.0 / ray.direction[i];
-      let mut t0 = (self.min[i] - ray.origin[i]) * inv_d;
-      let mut t1 = (self.max[i] - ray.origin[i]) * inv_d;
+      let inv_d = inv_dir[i];
+      let mut t0 = (self.min[i] - origin[i]) * inv_d;
+      let mut t1 = (self.max[i] - origin[i]) * inv_d;
       if inv_d < 0.0 {
         std::mem::swap(&mut t0, &mut t1);
       }
       t_min = t_min.max(t0);
       t_max = t_max.min(t1);
-      if t_max <= t_min {
+      // not `<=`, so that flat boxes (of axis-aligned quads) can still be hit.
+      if t_max < t_min {
         return false;
       }
     }
@@ -97,9 +134,57 @@ impl Default for Aabb {
   }
 }
 
+/// A point sampled on the surface of a hittable object, used for explicit light sampling.
+pub struct SurfaceSample {
+  pub point: Point,
+  pub unit_normal: Direction,
+  pub material: Arc<dyn Material>,
+  pub mat_uv: UV,
+  // pdf with respect to the area measure.
+  pub pdf_area: Float,
+}
+
 pub trait Hittable: Send + Sync {
   fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord>;
   fn bounding_box(&self) -> Aabb;
+
+  /// Sample a point on the surface.
+  /// Objects that do not support sampling return `None`, and cannot be used as lights.
+  fn sample_surface(&self, _sampler: &mut dyn Sampler) -> Option<SurfaceSample> {
+    None
+  }
+  /// pdf (area measure) of `sample_surface` returning the surface point `point` with normal `unit_normal`.
+  fn pdf_area(&self, _point: &Point, _unit_normal: &Direction) -> Float {
+    0.0
+  }
+
+  /// Sample a point on the surface as seen from `origin`.
+  /// Returns the sample and the pdf with respect to the solid angle at `origin`.
+  fn sample_towards(&self, origin: &Point, sampler: &mut dyn Sampler) -> Option<(SurfaceSample, Float)> {
+    let sample = self.sample_surface(sampler)?;
+    let to_sample = sample.point - *origin;
+    let dist_sq = to_sample.length_squared();
+    let cos = sample.unit_normal.dot(to_sample).abs() / dist_sq.sqrt();
+    if cos < FLOAT_EPSILON || sample.pdf_area <= 0.0 {
+      return None;
+    }
+    let pdf = sample.pdf_area * dist_sq / cos;
+    Some((sample, pdf))
+  }
+  /// pdf (solid angle at `origin`) of `sample_towards` choosing `direction`.
+  fn pdf_value(&self, origin: &Point, direction: &Direction) -> Float {
+    let ray = Ray::new(*origin, direction.normalize());
+    match self.hit(&ray, RAY_EPSILON, Float::MAX) {
+      Some(rec) => {
+        let cos = rec.unit_normal.dot(ray.direction).abs();
+        if cos < FLOAT_EPSILON {
+          return 0.0;
+        }
+        self.pdf_area(&rec.point, &rec.unit_normal) * rec.hit_t * rec.hit_t / cos
+      }
+      None => 0.0,
+    }
+  }
 }
 
 mod aggregate;
@@ -108,14 +193,20 @@ mod cone;
 mod cube;
 mod cylinder;
 mod instance;
+mod linear_bvh;
+mod mesh_accel;
+mod motion_instance;
 mod quad;
 mod sphere;
 mod triangle;
 
 pub use aggregate::Aggregate;
-pub use bvh::BvhAggregate;
+pub use bvh::{BvhAggregate, BvhOptions, BvhSplit, BvhStats};
 pub use cube::UnitCube;
 pub use instance::Instance;
+pub use linear_bvh::LinearBvh;
+pub use mesh_accel::MeshAccel;
+pub use motion_instance::{Keyframe, MotionInstance};
 pub use quad::UnitQuad;
 pub use sphere::UnitSphere;
 pub use triangle::{TriangleMesh, Triangle};
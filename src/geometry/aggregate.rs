@@ -1,12 +1,19 @@
 use crate::prelude::*;
+use geometry::{BvhStats, LinearBvh};
 pub struct Aggregate {
   objects: Vec<Arc<dyn Hittable>>,
-  bvh_acc: Option<Arc<dyn Hittable>>,
+  bvh_acc: Option<LinearBvh>,
+  // how `bvh_acc` was built.
+  bvh_options: BvhOptions,
 }
 
 impl Aggregate {
   pub fn new(objects: Vec<Arc<dyn Hittable>>) -> Aggregate {
-    Self { objects, bvh_acc: None }
+    Self {
+      objects,
+      bvh_acc: None,
+      bvh_options: BvhOptions::default(),
+    }
   }
   pub fn add_object(&mut self, object: Arc<dyn Hittable>) {
     self.objects.push(object);
@@ -17,11 +24,25 @@ impl Aggregate {
     self.bvh_acc = None;
   }
 
+  pub fn len(&self) -> usize {
+    self.objects.len()
+  }
+  pub fn is_empty(&self) -> bool {
+    self.objects.is_empty()
+  }
+
   /// Pack existing objects with BVH strategy.
-  pub fn bvh_accelerate(&mut self) {
-    if self.bvh_acc.is_none() {
-      self.bvh_acc = Some(BvhAggregate::build(self.objects.clone()));
+  /// Nothing is rebuilt if the objects and the options are the same as the last time.
+  pub fn bvh_accelerate(&mut self, options: BvhOptions) {
+    if self.objects.is_empty() || (self.bvh_acc.is_some() && self.bvh_options == options) {
+      return;
     }
+    self.bvh_acc = Some(LinearBvh::build(self.objects.clone(), &options));
+    self.bvh_options = options;
+  }
+  /// None if the objects are not packed.
+  pub fn bvh_stats(&self) -> Option<&BvhStats> {
+    self.bvh_acc.as_ref().map(|bvh| bvh.stats())
   }
 }
 
@@ -1,11 +1,275 @@
 use crate::prelude::*;
 
+// Relative costs of the surface area heuristic, the same as pbrt.
+const TRAVERSAL_COST: Float = 0.125;
+const INTERSECT_COST: Float = 1.0;
+
+/// How the objects are split into two children.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BvhSplit {
+  /// At the median of `bounding_box().min` on the longest axis. Fast to build.
+  Median,
+  /// Binned surface area heuristic, with `bins` candidate planes per axis.
+  /// Slower to build, but much faster to traverse for uneven scenes.
+  Sah { bins: usize },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BvhOptions {
+  pub split: BvhSplit,
+  /// Nodes with at most this many objects may become leaves, which test their objects one by one.
+  pub max_leaf_size: usize,
+}
+
+impl BvhOptions {
+  pub fn median() -> Self {
+    Self { split: BvhSplit::Median, ..Default::default() }
+  }
+  pub fn sah() -> Self {
+    Self::default()
+  }
+}
+
+impl Default for BvhOptions {
+  fn default() -> Self {
+    Self { split: BvhSplit::Sah { bins: 12 }, max_leaf_size: 4 }
+  }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BvhStats {
+  pub interior_nodes: usize,
+  pub leaves: usize,
+  /// The root has depth 0.
+  pub max_depth: usize,
+  /// Expected cost of a ray hitting the root box, in units of one object intersection.
+  pub sah_cost: Float,
+}
+
+impl BvhStats {
+  pub fn nodes(&self) -> usize {
+    self.interior_nodes + self.leaves
+  }
+}
+
+impl std::fmt::Display for BvhStats {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{} nodes ({} leaves), depth {}, SAH cost {:.2}",
+      self.nodes(),
+      self.leaves,
+      self.max_depth,
+      self.sah_cost
+    )
+  }
+}
+
+// Below this depth, nodes are split at the median whatever the options are,
+// so that no tree is deeper than 64 (`LinearBvh` traverses with a fixed stack).
+const MAX_SAH_DEPTH: usize = 32;
+
+/// A tree of `Arc<dyn Hittable>` nodes. `LinearBvh` is faster, this is kept for comparison.
 pub struct BvhAggregate {
   left: Arc<dyn Hittable>,
   right: Arc<dyn Hittable>,
   bbox: Aabb,
 }
 
+// A leaf with more than one object.
+struct BvhLeaf {
+  objects: Vec<Arc<dyn Hittable>>,
+  bbox: Aabb,
+}
+
+// an object with its bounding box, which may be expensive to compute (`Instance`).
+type Item = (Arc<dyn Hittable>, Aabb);
+
+// The plane between bin `split - 1` and bin `split` on `axis`.
+struct SahSplit {
+  cost: Float,
+  axis: usize,
+  lo: Float,
+  extent: Float,
+  bins: usize,
+  split: usize,
+}
+
+impl SahSplit {
+  fn bin_of(&self, bbox: &Aabb) -> usize {
+    let offset = (bbox.centroid()[self.axis] - self.lo) / self.extent;
+    ((offset * self.bins as Float) as usize).min(self.bins - 1)
+  }
+}
+
+/// The tree shared by the BVH layouts, before it is packed.
+pub(crate) enum BuildNode {
+  /// `objects[first..first + count]` of the `BvhBuild`.
+  Leaf { bbox: Aabb, first: usize, count: usize },
+  /// The left child has the smaller coordinates on `axis`.
+  Interior { bbox: Aabb, axis: usize, children: Box<[BuildNode; 2]> },
+}
+
+impl BuildNode {
+  pub(crate) fn bbox(&self) -> Aabb {
+    match self {
+      BuildNode::Leaf { bbox, .. } | BuildNode::Interior { bbox, .. } => *bbox,
+    }
+  }
+}
+
+pub(crate) struct BvhBuild {
+  pub root: BuildNode,
+  /// Ordered so that the objects of every leaf are contiguous.
+  pub objects: Vec<Arc<dyn Hittable>>,
+  pub stats: BvhStats,
+}
+
+impl BvhBuild {
+  pub(crate) fn new(objects: Vec<Arc<dyn Hittable>>, options: &BvhOptions) -> Self {
+    if objects.is_empty() {
+      panic!("BvhAggregate requires at least one object per frame");
+    }
+    let items = objects
+      .into_iter()
+      .map(|obj| {
+        let bbox = obj.bounding_box();
+        (obj, bbox)
+      })
+      .collect();
+    let mut build = BvhBuilder { options, ordered: Vec::new(), stats: BvhStats::default() };
+    let (root, cost) = build.node(items, 0);
+    build.stats.sah_cost = cost;
+    Self { root, objects: build.ordered, stats: build.stats }
+  }
+}
+
+struct BvhBuilder<'a> {
+  options: &'a BvhOptions,
+  ordered: Vec<Arc<dyn Hittable>>,
+  stats: BvhStats,
+}
+
+impl BvhBuilder<'_> {
+  // returns (node, SAH cost of the node)
+  fn node(&mut self, mut items: Vec<Item>, depth: usize) -> (BuildNode, Float) {
+    self.stats.max_depth = self.stats.max_depth.max(depth);
+    let bbox = items.iter().fold(Aabb::default(), |acc, item| Aabb::union(acc, item.1));
+    let len = items.len();
+    let leaf_cost = INTERSECT_COST * len as Float;
+    let max_leaf_size = self.options.max_leaf_size.max(1);
+
+    let split = match self.options.split {
+      BvhSplit::Sah { bins } if depth < MAX_SAH_DEPTH => Some(bins),
+      _ => None,
+    };
+    let (axis, right_items) = match split {
+      None => {
+        if len <= max_leaf_size {
+          return self.leaf(items, bbox);
+        }
+        let axis = bbox.longest_axis();
+        let mid = len / 2;
+        items.select_nth_unstable_by(mid, |a, b| a.1.min[axis].total_cmp(&b.1.min[axis]));
+        (axis, items.split_off(mid))
+      }
+      Some(bins) => match Self::sah_split(&items, &bbox, bins.max(2)) {
+        Some(split) if len <= max_leaf_size && leaf_cost <= split.cost => {
+          return self.leaf(items, bbox);
+        }
+        Some(split) => {
+          // the same binning as the one the split is chosen from, so neither side is empty.
+          let (left, right) = items.into_iter().partition(|item| split.bin_of(&item.1) < split.split);
+          items = left;
+          (split.axis, right)
+        }
+        // all the centroids are at the same place, nothing to tell them apart.
+        None if len <= max_leaf_size => return self.leaf(items, bbox),
+        None => (bbox.longest_axis(), items.split_off(len / 2)),
+      },
+    };
+
+    self.stats.interior_nodes += 1;
+    let (left, left_cost) = self.node(items, depth + 1);
+    let (right, right_cost) = self.node(right_items, depth + 1);
+    let area = bbox.surface_area();
+    let cost = if area > 0.0 {
+      TRAVERSAL_COST
+        + (left.bbox().surface_area() * left_cost + right.bbox().surface_area() * right_cost) / area
+    } else {
+      TRAVERSAL_COST + 0.5 * (left_cost + right_cost)
+    };
+    let node = BuildNode::Interior { bbox, axis, children: Box::new([left, right]) };
+    (node, cost)
+  }
+
+  fn leaf(&mut self, items: Vec<Item>, bbox: Aabb) -> (BuildNode, Float) {
+    self.stats.leaves += 1;
+    let (first, count) = (self.ordered.len(), items.len());
+    self.ordered.extend(items.into_iter().map(|item| item.0));
+    (BuildNode::Leaf { bbox, first, count }, INTERSECT_COST * count as Float)
+  }
+
+  // Bin the centroids on every axis, and find the cheapest plane between two bins.
+  // Returns None if the centroids cannot be separated.
+  fn sah_split(items: &[Item], bbox: &Aabb, bins: usize) -> Option<SahSplit> {
+    let centroid_bounds = items.iter().fold(Aabb::default(), |acc, item| {
+      let c = item.1.centroid();
+      Aabb::union(acc, Aabb { min: c, max: c })
+    });
+    let area = bbox.surface_area();
+    let mut best: Option<SahSplit> = None;
+
+    for axis in 0..3 {
+      let lo = centroid_bounds.min[axis];
+      let extent = centroid_bounds.max[axis] - lo;
+      if extent <= FLOAT_EPSILON {
+        continue;
+      }
+      let mut candidate = SahSplit { cost: Float::INFINITY, axis, lo, extent, bins, split: 0 };
+      let mut counts = vec![0usize; bins];
+      let mut boxes = vec![Aabb::default(); bins];
+      for item in items {
+        let idx = candidate.bin_of(&item.1);
+        counts[idx] += 1;
+        boxes[idx] = Aabb::union(boxes[idx], item.1);
+      }
+
+      // right_area[i] and right_count[i] cover bins i..
+      let mut right_area = vec![0.0; bins];
+      let mut right_count = vec![0usize; bins];
+      let (mut acc_box, mut acc_count) = (Aabb::default(), 0);
+      for i in (1..bins).rev() {
+        acc_box = Aabb::union(acc_box, boxes[i]);
+        acc_count += counts[i];
+        right_area[i] = acc_box.surface_area();
+        right_count[i] = acc_count;
+      }
+
+      let (mut acc_box, mut acc_count) = (Aabb::default(), 0);
+      for i in 1..bins {
+        acc_box = Aabb::union(acc_box, boxes[i - 1]);
+        acc_count += counts[i - 1];
+        if acc_count == 0 || right_count[i] == 0 {
+          continue;
+        }
+        let weighted = acc_box.surface_area() * acc_count as Float
+          + right_area[i] * right_count[i] as Float;
+        let cost = TRAVERSAL_COST
+          + INTERSECT_COST * if area > 0.0 { weighted / area } else { items.len() as Float };
+        if cost < candidate.cost {
+          candidate.cost = cost;
+          candidate.split = i;
+        }
+      }
+      if candidate.split > 0 && best.as_ref().is_none_or(|best| candidate.cost < best.cost) {
+        best = Some(candidate);
+      }
+    }
+    best
+  }
+}
+
 impl BvhAggregate {
   pub fn new(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self {
     Self {
@@ -14,31 +278,22 @@ impl BvhAggregate {
       right,
     }
   }
-  pub fn build(mut objects: Vec<Arc<dyn Hittable>>) -> Arc<dyn Hittable> {
-    let axis = {
-      let mut surround = Aabb::default();
-      for obj in &objects {
-        surround = Aabb::union(surround, obj.bounding_box());
-      }
-      surround.longest_axis()
-    };
-    let len = objects.len();
-    match len {
-      0 => panic!("BvhAggregate requires at least one object per frame"),
-      1 => objects.pop().unwrap(),
-      _ => {
-        let mid = len / 2;
-        objects.select_nth_unstable_by(mid, |a: &Arc<dyn Hittable>, b: &Arc<dyn Hittable>| {
-          let a = a.bounding_box();
-          let b = b.bounding_box();
-          a.min[axis].partial_cmp(&b.min[axis]).unwrap()
-        });
-        let right_objects = objects.split_off(mid);
-        let left_objects = objects;
-        let left = Self::build(left_objects);
-        let right = Self::build(right_objects);
-        Arc::new(Self::new(left, right))
+  pub fn build(objects: Vec<Arc<dyn Hittable>>, options: &BvhOptions) -> (Arc<dyn Hittable>, BvhStats) {
+    let build = BvhBuild::new(objects, options);
+    (Self::from_node(&build.root, &build.objects), build.stats)
+  }
+
+  fn from_node(node: &BuildNode, objects: &[Arc<dyn Hittable>]) -> Arc<dyn Hittable> {
+    match node {
+      BuildNode::Leaf { first, count: 1, .. } => objects[*first].clone(),
+      &BuildNode::Leaf { bbox, first, count } => {
+        Arc::new(BvhLeaf { objects: objects[first..first + count].to_vec(), bbox })
       }
+      BuildNode::Interior { bbox, children, .. } => Arc::new(Self {
+        left: Self::from_node(&children[0], objects),
+        right: Self::from_node(&children[1], objects),
+        bbox: *bbox,
+      }),
     }
   }
 }
@@ -59,3 +314,70 @@ impl Hittable for BvhAggregate {
     self.bbox
   }
 }
+
+impl Hittable for BvhLeaf {
+  fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    if !self.bbox.might_hit(ray, t_min, t_max) {
+      return None;
+    }
+    let mut closest_so_far = t_max;
+    let mut closest_hit_record = None;
+    for object in &self.objects {
+      if let Some(hit_record) = object.hit(ray, t_min, closest_so_far) {
+        closest_so_far = hit_record.hit_t;
+        closest_hit_record = Some(hit_record);
+      }
+    }
+    closest_hit_record
+  }
+  fn bounding_box(&self) -> Aabb {
+    self.bbox
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::{Rng, SeedableRng};
+
+  #[test]
+  fn test_bvh_matches_brute_force() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+    let material = material::Lambertian::new_arc(texture::SolidColorTexture::new_arc(ColorRgb::WHITE));
+    let sphere: Arc<dyn Hittable> = geometry::UnitSphere::new_arc(material.clone());
+    // clustered like the small spheres of examples/spheres.rs, plus a few big ones.
+    let mut objects: Vec<Arc<dyn Hittable>> = Vec::new();
+    for i in 0..300 {
+      let radius = if i % 50 == 0 { 2.0 } else { rng.random_range(0.01..0.1) };
+      let center = Direction::new(
+        rng.random_range(-3.0..3.0),
+        rng.random_range(-0.5..0.5),
+        rng.random_range(-20.0..-5.0),
+      );
+      let mat = Mat4d::from_translation(center) * Mat4d::from_scaling(radius, radius, radius);
+      objects.push(geometry::Instance::new_arc(sphere.clone(), mat));
+    }
+    // a flat bounding box.
+    let quad = Arc::new(geometry::UnitQuad::new(material));
+    let mat = Mat4d::from_translation(Direction::new(1.0, 0.0, -8.0)) * Mat4d::from_scaling(2.0, 2.0, 1.0);
+    objects.push(geometry::Instance::new_arc(quad, mat));
+
+    let brute_force = Aggregate::new(objects.clone());
+    let (median, median_stats) = BvhAggregate::build(objects.clone(), &BvhOptions::median());
+    let (sah, sah_stats) = BvhAggregate::build(objects.clone(), &BvhOptions::sah());
+    let linear = geometry::LinearBvh::build(objects.clone(), &BvhOptions::sah());
+    assert!(sah_stats.sah_cost < median_stats.sah_cost, "{} vs {}", sah_stats, median_stats);
+    assert_eq!(sah_stats.nodes(), 2 * sah_stats.leaves - 1);
+
+    for _ in 0..2000 {
+      let origin = Point::new(rng.random_range(-4.0..4.0), rng.random_range(-2.0..2.0), 0.0);
+      let target = Point::new(rng.random_range(-4.0..4.0), rng.random_range(-1.0..1.0), -20.0);
+      let ray = Ray::new(origin, (target - origin).normalize());
+      let expected = brute_force.hit(&ray, RAY_EPSILON, Float::MAX).map(|rec| rec.hit_t);
+      for bvh in [median.as_ref(), sah.as_ref(), &linear as &dyn Hittable] {
+        let t = bvh.hit(&ray, RAY_EPSILON, Float::MAX).map(|rec| rec.hit_t);
+        assert_eq!(t, expected);
+      }
+    }
+  }
+}
@@ -48,7 +48,7 @@ impl Hittable for UnitCube {
           _ => unreachable!()
         };
         const BOUND: Float = 0.5 + VEC3D_EPSILON;
-        if u < -BOUND || u > BOUND || v < -BOUND || v > BOUND {
+        if !(-BOUND..=BOUND).contains(&u) || !(-BOUND..=BOUND).contains(&v) {
           continue;
         }
         (u_raw, v_raw) = (u, v);
@@ -64,6 +64,8 @@ impl Hittable for UnitCube {
       unit_normal: normal,
       material: self.mat[order].clone(),
       mat_uv: UV { u: u_raw + 0.5, v: v_raw + 0.5 },
+      time: ray.time,
+      object_id: 0,
     })
   }
   fn bounding_box(&self) -> Aabb {
@@ -5,6 +5,8 @@ pub struct Instance {
   trans_mat: Mat4d,
   inv_trans: Mat4d,
   bbox: Aabb,
+  // |det| of the linear part, for converting area pdfs.
+  abs_det: Float,
 }
 
 impl Instance {
@@ -33,18 +35,39 @@ impl Instance {
       object,
       trans_mat,
       inv_trans: trans_mat.inverse().unwrap(),
+      abs_det: trans_mat.linear_determinant().abs(),
     }
   }
   pub fn new_arc(object: Arc<dyn Hittable>, trans_mat: Mat4d) -> Arc<Self> {
     Arc::new(Self::new(object, trans_mat))
   }
+
+  fn to_world_sample(&self, local: SurfaceSample) -> SurfaceSample {
+    let area_scale = self.area_scale(&local.unit_normal);
+    SurfaceSample {
+      point: self.trans_mat.transform_point(local.point),
+      unit_normal: self
+        .inv_trans
+        .transpose_transform_vector(local.unit_normal)
+        .normalize(),
+      material: local.material,
+      mat_uv: local.mat_uv,
+      pdf_area: local.pdf_area / area_scale,
+    }
+  }
+
+  // A local surface patch with unit normal `local_normal` has its area scaled by this factor.
+  // dA' = |det M| * |M^-T n| dA
+  fn area_scale(&self, local_normal: &Direction) -> Float {
+    self.abs_det * self.inv_trans.transpose_transform_vector(*local_normal).length()
+  }
 }
 
 impl Hittable for Instance {
   fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
     let origin = self.inv_trans.transform_point(ray.origin);
     let direction = self.inv_trans.transform_vector(ray.direction);
-    let local_ray = Ray::new(origin, direction);
+    let local_ray = Ray::new_with_time(origin, direction, ray.time);
     let mut rec = self.object.hit(&local_ray, t_min, t_max)?;
     rec.point = self.trans_mat.transform_point(rec.point);
     rec.unit_normal = self
@@ -56,4 +79,50 @@ impl Hittable for Instance {
   fn bounding_box(&self) -> Aabb {
     self.bbox
   }
+  fn sample_surface(&self, sampler: &mut dyn Sampler) -> Option<SurfaceSample> {
+    let local = self.object.sample_surface(sampler)?;
+    Some(self.to_world_sample(local))
+  }
+  fn pdf_area(&self, point: &Point, unit_normal: &Direction) -> Float {
+    let local_point = self.inv_trans.transform_point(*point);
+    let local_normal = self.trans_mat.transpose_transform_vector(*unit_normal).normalize();
+    self.object.pdf_area(&local_point, &local_normal) / self.area_scale(&local_normal)
+  }
+  // sampled by the object as seen from the local origin, like a sphere by its cone.
+  fn sample_towards(&self, origin: &Point, sampler: &mut dyn Sampler) -> Option<(SurfaceSample, Float)> {
+    let local_origin = self.inv_trans.transform_point(*origin);
+    let (local, local_pdf) = self.object.sample_towards(&local_origin, sampler)?;
+    let local_per_area = solid_angle_per_area(&local_origin, &local.point, &local.unit_normal);
+    let area_scale = self.area_scale(&local.unit_normal);
+    let sample = self.to_world_sample(local);
+    let per_area = solid_angle_per_area(origin, &sample.point, &sample.unit_normal);
+    if per_area < FLOAT_EPSILON {
+      return None;
+    }
+    Some((sample, local_pdf * local_per_area / area_scale / per_area))
+  }
+  fn pdf_value(&self, origin: &Point, direction: &Direction) -> Float {
+    let Some(rec) = self.hit(&Ray::new(*origin, direction.normalize()), RAY_EPSILON, Float::MAX) else {
+      return 0.0;
+    };
+    let per_area = solid_angle_per_area(origin, &rec.point, &rec.unit_normal);
+    if per_area < FLOAT_EPSILON {
+      return 0.0;
+    }
+    let local_origin = self.inv_trans.transform_point(*origin);
+    let local_point = self.inv_trans.transform_point(rec.point);
+    let local_normal = self.trans_mat.transpose_transform_vector(rec.unit_normal).normalize();
+    let local_pdf = self.object.pdf_value(&local_origin, &(local_point - local_origin));
+    let local_per_area = solid_angle_per_area(&local_origin, &local_point, &local_normal);
+    local_pdf * local_per_area / self.area_scale(&local_normal) / per_area
+  }
+}
+
+// dω / dA at `origin`, for a surface patch at `point` with normal `unit_normal`: cos / dist^2.
+// Solid angle pdfs are carried between object and world space through the area, which the
+// transform only scales.
+fn solid_angle_per_area(origin: &Point, point: &Point, unit_normal: &Direction) -> Float {
+  let to_point = *point - *origin;
+  let dist_sq = to_point.length_squared();
+  unit_normal.dot(to_point).abs() / (dist_sq * dist_sq.sqrt())
 }
@@ -0,0 +1,102 @@
+use crate::prelude::*;
+use geometry::BvhStats;
+use geometry::bvh::{BuildNode, BvhBuild};
+
+// deep enough for any tree `BvhBuild` makes.
+const STACK_SIZE: usize = 64;
+
+/// A BVH packed into one array of nodes in depth-first order, so the first child of a node is
+/// the next one in the array. Rays visit the nearer child first and skip the farther one
+/// once something closer has been hit.
+pub struct LinearBvh {
+  nodes: Vec<LinearNode>,
+  objects: Vec<Arc<dyn Hittable>>,
+  stats: BvhStats,
+}
+
+#[derive(Clone, Copy)]
+struct LinearNode {
+  bbox: Aabb,
+  // leaf: index of the first object; interior: index of the second child.
+  offset: u32,
+  // 0 for interior nodes.
+  count: u16,
+  // interior nodes only, the axis the children are split on.
+  axis: u8,
+}
+
+impl LinearBvh {
+  pub fn build(objects: Vec<Arc<dyn Hittable>>, options: &BvhOptions) -> Self {
+    let build = BvhBuild::new(objects, options);
+    let mut nodes = Vec::with_capacity(build.stats.nodes());
+    Self::flatten(&build.root, &mut nodes);
+    Self { nodes, objects: build.objects, stats: build.stats }
+  }
+  pub fn stats(&self) -> &BvhStats {
+    &self.stats
+  }
+
+  // returns the index of `node`.
+  fn flatten(node: &BuildNode, nodes: &mut Vec<LinearNode>) -> usize {
+    let idx = nodes.len();
+    match *node {
+      BuildNode::Leaf { bbox, first, count } => {
+        // a leaf never holds more than `max_leaf_size` objects, or the objects at one place.
+        let count = u16::try_from(count).expect("too many objects in one BVH leaf");
+        nodes.push(LinearNode { bbox, offset: first as u32, count, axis: 0 });
+      }
+      BuildNode::Interior { bbox, axis, ref children } => {
+        nodes.push(LinearNode { bbox, offset: 0, count: 0, axis: axis as u8 });
+        Self::flatten(&children[0], nodes);
+        nodes[idx].offset = Self::flatten(&children[1], nodes) as u32;
+      }
+    }
+    idx
+  }
+}
+
+impl Hittable for LinearBvh {
+  fn hit(&self, ray: &Ray, t_min: Float, mut t_max: Float) -> Option<HitRecord> {
+    let inv_dir = Direction::new(1.0 / ray.direction.x, 1.0 / ray.direction.y, 1.0 / ray.direction.z);
+    let dir_is_neg = [inv_dir.x < 0.0, inv_dir.y < 0.0, inv_dir.z < 0.0];
+    let mut closest_hit_record = None;
+
+    let mut stack = [0u32; STACK_SIZE];
+    let mut stack_len = 0;
+    let mut current = 0;
+    loop {
+      let node = &self.nodes[current];
+      if node.bbox.might_hit_inv(&ray.origin, &inv_dir, t_min, t_max) {
+        if node.count > 0 {
+          let first = node.offset as usize;
+          for object in &self.objects[first..first + node.count as usize] {
+            if let Some(hit_record) = object.hit(ray, t_min, t_max) {
+              t_max = hit_record.hit_t;
+              closest_hit_record = Some(hit_record);
+            }
+          }
+        } else {
+          // the left child has the smaller coordinates, so it is the nearer one for a positive direction.
+          let (near, far) = if dir_is_neg[node.axis as usize] {
+            (node.offset as usize, current + 1)
+          } else {
+            (current + 1, node.offset as usize)
+          };
+          stack[stack_len] = far as u32;
+          stack_len += 1;
+          current = near;
+          continue;
+        }
+      }
+      if stack_len == 0 {
+        break;
+      }
+      stack_len -= 1;
+      current = stack[stack_len] as usize;
+    }
+    closest_hit_record
+  }
+  fn bounding_box(&self) -> Aabb {
+    self.nodes[0].bbox
+  }
+}
@@ -0,0 +1,134 @@
+use crate::prelude::*;
+use geometry::{BvhStats, LinearBvh};
+
+/// The triangles of one or more meshes packed in their own BVH, built once.
+/// Being a `Hittable` itself, it can be shared by many `Instance`s,
+/// so that the BVH of the world only holds the instances.
+/// Sampled uniformly by area, so a whole emissive mesh can be one light.
+pub struct MeshAccel {
+  bvh: LinearBvh,
+  triangles: Vec<Arc<Triangle>>,
+  // cumulative areas of `triangles`, for sampling.
+  area_cdf: Vec<Float>,
+  area: Float,
+}
+
+impl MeshAccel {
+  pub fn new(mesh: Arc<TriangleMesh>) -> Self {
+    Self::from_meshes(vec![mesh])
+  }
+  pub fn new_arc(mesh: Arc<TriangleMesh>) -> Arc<Self> {
+    Arc::new(Self::new(mesh))
+  }
+  /// All the meshes of one model (one for each material in an `.obj`) in one BVH.
+  pub fn from_meshes(meshes: Vec<Arc<TriangleMesh>>) -> Self {
+    Self::from_meshes_with(meshes, BvhOptions::default())
+  }
+  /// Panics if the meshes have no triangle.
+  pub fn from_meshes_with(meshes: Vec<Arc<TriangleMesh>>, options: BvhOptions) -> Self {
+    let triangles: Vec<Arc<Triangle>> =
+      meshes.into_iter().flat_map(|mesh| mesh.triangles()).map(Arc::new).collect();
+    let bvh = LinearBvh::build(
+      triangles.iter().map(|tri| tri.clone() as Arc<dyn Hittable>).collect(),
+      &options,
+    );
+    let area_cdf: Vec<Float> = triangles
+      .iter()
+      .scan(0.0, |acc, tri| {
+        *acc += tri.area();
+        Some(*acc)
+      })
+      .collect();
+    let area = area_cdf.last().copied().unwrap_or(0.0);
+    Self { bvh, triangles, area_cdf, area }
+  }
+
+  pub fn triangle_count(&self) -> usize {
+    self.triangles.len()
+  }
+  pub fn area(&self) -> Float {
+    self.area
+  }
+  pub fn bvh_stats(&self) -> &BvhStats {
+    self.bvh.stats()
+  }
+}
+
+impl Hittable for MeshAccel {
+  fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+    self.bvh.hit(ray, t_min, t_max)
+  }
+  fn bounding_box(&self) -> Aabb {
+    self.bvh.bounding_box()
+  }
+  // pick a triangle by its area, then a point uniformly on it.
+  fn sample_surface(&self, sampler: &mut dyn Sampler) -> Option<SurfaceSample> {
+    if self.area < FLOAT_EPSILON {
+      return None;
+    }
+    let target = sampler.get_1d() * self.area;
+    let idx = self.area_cdf.partition_point(|&acc| acc <= target).min(self.triangles.len() - 1);
+    let mut sample = self.triangles[idx].sample_surface(sampler)?;
+    sample.pdf_area = 1.0 / self.area;
+    Some(sample)
+  }
+  fn pdf_area(&self, _point: &Point, _unit_normal: &Direction) -> Float {
+    if self.area < FLOAT_EPSILON { 0.0 } else { 1.0 / self.area }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::{Rng, SeedableRng};
+
+  #[test]
+  fn test_instanced_mesh() {
+    let material = material::Lambertian::new_arc(texture::SolidColorTexture::new_arc(ColorRgb::WHITE));
+    // a square pyramid, 6 triangles.
+    let vertices = vec![
+      Point::new(-1.0, 0.0, -1.0),
+      Point::new(1.0, 0.0, -1.0),
+      Point::new(1.0, 0.0, 1.0),
+      Point::new(-1.0, 0.0, 1.0),
+      Point::new(0.0, 1.0, 0.0),
+    ];
+    let indices = vec![0, 2, 1, 0, 3, 2, 0, 1, 4, 1, 2, 4, 2, 3, 4, 3, 0, 4];
+    let mesh = Arc::new(TriangleMesh::new(vertices, vec![], vec![], indices, material));
+    let accel = MeshAccel::new_arc(mesh.clone());
+    assert_eq!(accel.triangle_count(), 6);
+    assert!((accel.area() - (4.0 + 4.0 * Float::sqrt(2.0))).abs() < 1e-4);
+    let accel: Arc<dyn Hittable> = accel;
+
+    // a grid of instances sharing one MeshAccel, against transformed triangles one by one.
+    let mut instanced = World::default();
+    let mut flat = Aggregate::default();
+    for i in 0..10 {
+      for j in 0..10 {
+        let mat = Mat4d::from_translation(Direction::new(3.0 * i as Float, 0.0, -3.0 * j as Float))
+          * Mat4d::from_rotation_y(0.3 * (i * 10 + j) as Float)
+          * Mat4d::from_scaling(1.0, 0.5 + 0.1 * j as Float, 1.0);
+        instanced.add_object(geometry::Instance::new_arc(accel.clone(), mat));
+        for tri in mesh.clone().triangles() {
+          flat.add_object(geometry::Instance::new_arc(Arc::new(tri), mat));
+        }
+      }
+    }
+    instanced.bvh_finalize();
+    assert_eq!(instanced.objects().len(), 100);
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+    for _ in 0..2000 {
+      let origin = Point::new(rng.random_range(-5.0..35.0), 10.0, rng.random_range(-35.0..5.0));
+      let target = Point::new(rng.random_range(-1.0..28.0), 0.0, rng.random_range(-28.0..1.0));
+      let ray = Ray::new(origin, (target - origin).normalize());
+      let expected = flat.hit(&ray, RAY_EPSILON, Float::MAX);
+      let got = instanced.hit(&ray, RAY_EPSILON, Float::MAX);
+      assert_eq!(got.is_some(), expected.is_some());
+      if let (Some(got), Some(expected)) = (got, expected) {
+        assert!((got.hit_t - expected.hit_t).abs() < 1e-3);
+        assert!((got.unit_normal - expected.unit_normal).length() < 1e-3);
+      }
+    }
+  }
+}
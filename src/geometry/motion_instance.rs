@@ -0,0 +1,243 @@
+use crate::prelude::*;
+
+/// One pose of a `MotionInstance` at `time`: scale, then rotate, then translate.
+/// Poses are interpolated component by component, so a rotation stays a rotation in between,
+/// which a lerp of the matrices would not.
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe {
+  pub time: Float,
+  pub translation: Direction,
+  pub rotation: Quat,
+  pub scale: Vec3d,
+}
+
+impl Keyframe {
+  pub fn new(time: Float, translation: Direction, rotation: Quat, scale: Vec3d) -> Self {
+    Self { time, translation, rotation, scale }
+  }
+
+  /// Decompose an affine `mat` into translation, rotation and scale.
+  /// Returns None if `mat` is singular or shears, which a keyframe cannot represent.
+  pub fn from_matrix(time: Float, mat: &Mat4d) -> Option<Self> {
+    let column = |col: usize| Direction::new(mat.data[col * 4], mat.data[col * 4 + 1], mat.data[col * 4 + 2]);
+    let mut columns = [column(0), column(1), column(2)];
+    let mut scale = Vec3d::new(columns[0].length(), columns[1].length(), columns[2].length());
+    if scale.x < FLOAT_EPSILON || scale.y < FLOAT_EPSILON || scale.z < FLOAT_EPSILON {
+      return None;
+    }
+    // a mirroring flips one axis, so that the rest is a proper rotation.
+    if mat.linear_determinant() < 0.0 {
+      scale.x = -scale.x;
+    }
+    for (axis, col) in columns.iter_mut().enumerate() {
+      *col = *col / scale[axis];
+    }
+    let orthogonal = |a: Direction, b: Direction| a.dot(b).abs() < 1e-4;
+    if !orthogonal(columns[0], columns[1])
+      || !orthogonal(columns[1], columns[2])
+      || !orthogonal(columns[0], columns[2])
+    {
+      return None;
+    }
+    let mut rotation = Mat4d::IDENTITY;
+    for (col, c) in columns.iter().enumerate() {
+      for row in 0..3 {
+        rotation.data[col * 4 + row] = c[row];
+      }
+    }
+    let translation = Direction::new(mat.data[12], mat.data[13], mat.data[14]);
+    Some(Self::new(time, translation, Quat::from_rotation_matrix(&rotation), scale))
+  }
+
+  pub fn to_mat4d(&self) -> Mat4d {
+    Mat4d::from_translation(self.translation)
+      * self.rotation.to_mat4d()
+      * Mat4d::from_scaling(self.scale.x, self.scale.y, self.scale.z)
+  }
+
+  // `t` in [0, 1] from `a` to `b`.
+  fn lerp(a: &Self, b: &Self, t: Float) -> Self {
+    let lerp = |x: Vec3d, y: Vec3d| (1.0 - t) * x + t * y;
+    Self {
+      time: (1.0 - t) * a.time + t * b.time,
+      translation: lerp(a.translation, b.translation),
+      rotation: Quat::slerp(a.rotation, b.rotation, t),
+      scale: lerp(a.scale, b.scale),
+    }
+  }
+
+  #[inline]
+  fn transform_point(&self, p: Point) -> Point {
+    self.rotation.rotate(scaled(p, self.scale)) + self.translation
+  }
+  #[inline]
+  fn inverse_transform_point(&self, p: Point) -> Point {
+    inverse_scaled(self.rotation.conjugate().rotate(p - self.translation), self.scale)
+  }
+  #[inline]
+  fn inverse_transform_vector(&self, v: Direction) -> Direction {
+    inverse_scaled(self.rotation.conjugate().rotate(v), self.scale)
+  }
+  // normals go by the inverse transpose: R * S^-1.
+  #[inline]
+  fn transform_normal(&self, n: Direction) -> Direction {
+    self.rotation.rotate(inverse_scaled(n, self.scale))
+  }
+}
+
+#[inline]
+fn scaled(v: Vec3d, s: Vec3d) -> Vec3d {
+  Vec3d::new(v.x * s.x, v.y * s.y, v.z * s.z)
+}
+#[inline]
+fn inverse_scaled(v: Vec3d, s: Vec3d) -> Vec3d {
+  Vec3d::new(v.x / s.x, v.y / s.y, v.z / s.z)
+}
+
+/// An object moving through keyframed poses, seen by each ray at the ray's `time`.
+/// Before the first keyframe and after the last one, the object stays still.
+/// Moving objects cannot be sampled, so they cannot be lights.
+pub struct MotionInstance {
+  object: Arc<dyn Hittable>,
+  keyframes: Vec<Keyframe>,
+  bbox: Aabb,
+}
+
+impl MotionInstance {
+  // max rotation between two poses sampled for the bounding box.
+  const BBOX_STEP_ANGLE: Float = PI / 36.0;
+
+  /// Panics if `keyframes` is empty. They are sorted by time here.
+  pub fn new(object: Arc<dyn Hittable>, mut keyframes: Vec<Keyframe>) -> Self {
+    assert!(!keyframes.is_empty(), "MotionInstance requires at least one keyframe");
+    keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+    let bbox = Self::motion_bbox(&object.bounding_box(), &keyframes);
+    Self { object, keyframes, bbox }
+  }
+  pub fn new_arc(object: Arc<dyn Hittable>, keyframes: Vec<Keyframe>) -> Arc<Self> {
+    Arc::new(Self::new(object, keyframes))
+  }
+
+  pub fn keyframes(&self) -> &[Keyframe] {
+    &self.keyframes
+  }
+
+  /// The pose at `time`.
+  pub fn pose_at(&self, time: Float) -> Keyframe {
+    let idx = self.keyframes.partition_point(|key| key.time <= time);
+    if idx == 0 {
+      return self.keyframes[0];
+    }
+    if idx == self.keyframes.len() {
+      return self.keyframes[idx - 1];
+    }
+    let (a, b) = (&self.keyframes[idx - 1], &self.keyframes[idx]);
+    Keyframe::lerp(a, b, (time - a.time) / (b.time - a.time))
+  }
+
+  // The box of the object at poses close enough to each other that the rotation in between
+  // cannot bulge out of the padding.
+  fn motion_bbox(local: &Aabb, keyframes: &[Keyframe]) -> Aabb {
+    let corners: Vec<Point> = (0..8)
+      .map(|i| {
+        Point::new(
+          if i & 1 == 0 { local.min.x } else { local.max.x },
+          if i & 2 == 0 { local.min.y } else { local.max.y },
+          if i & 4 == 0 { local.min.z } else { local.max.z },
+        )
+      })
+      .collect();
+    let mut bbox = Aabb::default();
+    let mut add_pose = |pose: &Keyframe| {
+      for corner in &corners {
+        let p = pose.transform_point(*corner);
+        bbox = Aabb::union(bbox, Aabb { min: p, max: p });
+      }
+    };
+    add_pose(&keyframes[0]);
+
+    let mut pad: Float = 0.0;
+    for pair in keyframes.windows(2) {
+      let (a, b) = (&pair[0], &pair[1]);
+      let angle = a.rotation.angle_to(b.rotation);
+      let steps = (angle / Self::BBOX_STEP_ANGLE).ceil().max(1.0) as usize;
+      for step in 1..=steps {
+        add_pose(&Keyframe::lerp(a, b, step as Float / steps as Float));
+      }
+      // a point at distance r from the rotation center leaves the chord by r(1 - cos(step / 2)).
+      let max_scale = |s: Vec3d| s.x.abs().max(s.y.abs()).max(s.z.abs());
+      let radius = corners.iter().map(|c| c.length()).fold(0.0, Float::max)
+        * max_scale(a.scale).max(max_scale(b.scale));
+      pad = pad.max(radius * (1.0 - (0.5 * angle / steps as Float).cos()));
+    }
+    let pad = Direction::new(pad, pad, pad);
+    Aabb { min: bbox.min - pad, max: bbox.max + pad }
+  }
+}
+
+impl Hittable for MotionInstance {
+  fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+    let pose = self.pose_at(ray.time);
+    let local_ray = Ray::new_with_time(
+      pose.inverse_transform_point(ray.origin),
+      pose.inverse_transform_vector(ray.direction),
+      ray.time,
+    );
+    let mut rec = self.object.hit(&local_ray, t_min, t_max)?;
+    rec.point = pose.transform_point(rec.point);
+    rec.unit_normal = pose.transform_normal(rec.unit_normal).normalize();
+    Some(rec)
+  }
+  fn bounding_box(&self) -> Aabb {
+    self.bbox
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_keyframes() {
+    let mat = Mat4d::from_translation(Direction::new(1.0, -2.0, 3.0))
+      * Mat4d::rotation_axis(Direction::new(1.0, 2.0, 0.5), 2.0)
+      * Mat4d::from_scaling(0.5, 2.0, -1.5);
+    let key = Keyframe::from_matrix(0.0, &mat).unwrap();
+    let back = key.to_mat4d();
+    for i in 0..16 {
+      assert!((back.data[i] - mat.data[i]).abs() < 1e-4, "{:?} vs {:?}", back, mat);
+    }
+    let shear = Mat4d { data: [1.0, 0.0, 0.0, 0.0, 0.5, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0] };
+    assert!(Keyframe::from_matrix(0.0, &shear).is_none());
+
+    // a unit cube spinning half a turn while moving; the middle pose is rotated by a quarter turn,
+    // and every pose stays inside the bounding box.
+    let material = material::Lambertian::new_arc(texture::SolidColorTexture::new_arc(ColorRgb::WHITE));
+    let cube = Arc::new(geometry::UnitCube::from_one(material));
+    let start = Keyframe::new(0.0, Direction::new(-2.0, 0.0, 0.0), Quat::IDENTITY, Vec3d::new(1.0, 1.0, 1.0));
+    let end = Keyframe::new(
+      1.0,
+      Direction::new(2.0, 0.0, 0.0),
+      Quat::from_axis_angle(Direction::new(0.0, 0.0, 1.0), PI * 0.999),
+      Vec3d::new(1.0, 1.0, 1.0),
+    );
+    let moving = MotionInstance::new(cube, vec![end, start]);
+    let middle = moving.pose_at(0.5);
+    assert!(middle.translation.length() < 1e-5);
+    assert!((middle.rotation.rotate(Direction::new(1.0, 0.0, 0.0)) - Direction::new(0.0, 1.0, 0.0)).length() < 1e-2);
+
+    let bbox = moving.bounding_box();
+    for i in 0..=100 {
+      let time = i as Float / 100.0;
+      // rays along -z through the corner region of the cube at `time`.
+      let pose = moving.pose_at(time);
+      let corner = pose.transform_point(Point::new(0.49, 0.49, 0.0));
+      let ray = Ray::new_with_time(corner + Direction::new(0.0, 0.0, 5.0), Direction::new(0.0, 0.0, -1.0), time);
+      let rec = moving.hit(&ray, RAY_EPSILON, Float::MAX).expect("the cube moves with the ray");
+      for axis in 0..3 {
+        assert!(rec.point[axis] >= bbox.min[axis] && rec.point[axis] <= bbox.max[axis]);
+      }
+      assert!((rec.unit_normal - Direction::new(0.0, 0.0, 1.0)).length() < 1e-4);
+    }
+  }
+}
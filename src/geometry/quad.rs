@@ -41,4 +41,18 @@ impl Hittable for UnitQuad {
       min: Point::new(-0.5, -0.5, 0.0),
     }
   }
+  // uniform on the quad, whose area is 1.
+  fn sample_surface(&self, sampler: &mut dyn Sampler) -> Option<SurfaceSample> {
+    let (u, v) = sampler.get_2d();
+    Some(SurfaceSample {
+      point: Point::new(u - 0.5, v - 0.5, 0.0),
+      unit_normal: Direction::new(0.0, 0.0, 1.0),
+      material: self.mat.clone(),
+      mat_uv: UV { u, v },
+      pdf_area: 1.0,
+    })
+  }
+  fn pdf_area(&self, _point: &Point, _unit_normal: &Direction) -> Float {
+    1.0
+  }
 }
@@ -13,6 +13,22 @@ impl UnitSphere {
   pub fn new_arc(mat: Arc<dyn Material>) -> Arc<Self> {
     Arc::new(Self::new(mat))
   }
+
+  const INV_AREA: Float = 1.0 / (4.0 * PI);
+
+  // the cosine of the half angle of the cone the sphere covers from `dist_sq` away from its
+  // center, and the solid angle of the cone, 2 pi (1 - cos), kept precise when it is small.
+  fn cone(dist_sq: Float) -> (Float, Float) {
+    let sin_sq = 1.0 / dist_sq;
+    let cos_max = (1.0 - sin_sq).max(0.0).sqrt();
+    (cos_max, 2.0 * PI * sin_sq / (1.0 + cos_max))
+  }
+
+  fn uv_at(point: &Point) -> UV {
+    let theta = (-point.y).acos();
+    let phi = (-point.z).atan2(point.x) + PI;
+    UV { u: phi / (2.0 * PI), v: theta / PI }
+  }
 }
 
 impl Hittable for UnitSphere {
@@ -37,21 +53,82 @@ impl Hittable for UnitSphere {
     let point = ray.at(root);
     let normal = point.normalize(); // precision not enough...
 
-    let theta = (-point.y).acos();
-    let phi = (-point.z).atan2(point.x) + PI;
-
-    let u = phi / (2.0 * PI);
-    let v = theta / PI;
-
     Some(HitRecord::from_ray(
       ray,
       normal,
       root,
       self.mat.clone(),
-      UV { u, v }
+      Self::uv_at(&point)
     ))
   }
   fn bounding_box(&self) -> Aabb {
     Aabb { max: Point::new(1.0, 1.0, 1.0), min: Point::new(-1.0, -1.0, -1.0) }
   }
+  // uniform on the whole sphere.
+  fn sample_surface(&self, sampler: &mut dyn Sampler) -> Option<SurfaceSample> {
+    let point = sampler::sample_uniform_sphere(sampler.get_2d());
+    Some(SurfaceSample {
+      point,
+      unit_normal: point,
+      material: self.mat.clone(),
+      mat_uv: Self::uv_at(&point),
+      pdf_area: Self::INV_AREA,
+    })
+  }
+  fn pdf_area(&self, _point: &Point, _unit_normal: &Direction) -> Float {
+    Self::INV_AREA
+  }
+  // uniform in the cone the sphere covers as seen from `origin`, on the side facing it.
+  // From inside, the whole sphere is seen: uniform by area.
+  fn sample_towards(&self, origin: &Point, sampler: &mut dyn Sampler) -> Option<(SurfaceSample, Float)> {
+    let dist_sq = origin.length_squared();
+    if dist_sq <= 1.0 {
+      let sample = self.sample_surface(sampler)?;
+      let to_sample = sample.point - *origin;
+      let cos = sample.unit_normal.dot(to_sample).abs() / to_sample.length();
+      if cos < FLOAT_EPSILON {
+        return None;
+      }
+      let pdf = sample.pdf_area * to_sample.length_squared() / cos;
+      return Some((sample, pdf));
+    }
+    let (cos_max, solid_angle) = Self::cone(dist_sq);
+    let (u1, u2) = sampler.get_2d();
+    let cos_theta = 1.0 - u1 * (1.0 - cos_max);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u2;
+    let frame = Onb::from_w(-*origin / dist_sq.sqrt());
+    let direction = frame.to_world(Direction::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta));
+    // the near intersection; rays along the silhouette may miss by rounding, they touch it.
+    let half_b = origin.dot(direction);
+    let t = -half_b - (half_b * half_b - (dist_sq - 1.0)).max(0.0).sqrt();
+    let point = (*origin + t * direction).normalize();
+    let cos = point.dot(direction).abs();
+    Some((
+      SurfaceSample {
+        point,
+        unit_normal: point,
+        material: self.mat.clone(),
+        mat_uv: Self::uv_at(&point),
+        pdf_area: cos / (t * t * solid_angle),
+      },
+      1.0 / solid_angle,
+    ))
+  }
+  fn pdf_value(&self, origin: &Point, direction: &Direction) -> Float {
+    let dist_sq = origin.length_squared();
+    if dist_sq <= 1.0 {
+      let ray = Ray::new(*origin, direction.normalize());
+      return match self.hit(&ray, RAY_EPSILON, Float::MAX) {
+        Some(rec) => {
+          let cos = rec.unit_normal.dot(ray.direction).abs();
+          if cos < FLOAT_EPSILON { 0.0 } else { Self::INV_AREA * rec.hit_t * rec.hit_t / cos }
+        }
+        None => 0.0,
+      };
+    }
+    let (cos_max, solid_angle) = Self::cone(dist_sq);
+    let cos = -origin.dot(*direction) / (dist_sq * direction.length_squared()).sqrt();
+    if cos < cos_max { 0.0 } else { 1.0 / solid_angle }
+  }
 }
@@ -15,6 +15,7 @@ pub struct Triangle {
   // caches:
   bbox: Aabb,
   face_unit_normal: Direction,
+  area: Float,
 }
 
 impl Triangle {
@@ -50,7 +51,9 @@ impl Triangle {
       face_normal.normalize()
     };
 
-    Self { mesh, idx, bbox, face_unit_normal }
+    let area = 0.5 * face_normal.length();
+
+    Self { mesh, idx, bbox, face_unit_normal, area }
   }
 
   pub fn v0(&self) -> Point {
@@ -66,6 +69,9 @@ impl Triangle {
   pub fn material(&self) -> Arc<dyn Material> {
     self.mesh.material.clone()
   }
+  pub fn area(&self) -> Float {
+    self.area
+  }
 
   pub fn unit_normal_at(&self, b1: Float, b2: Float) -> Direction {
     if self.mesh.normals.is_empty() {
@@ -116,7 +122,7 @@ impl Triangle {
       return None;
     }
     let b1 = s1.dot(s) * inv;
-    if b1 < 0.0 || b1 > 1.0 {
+    if !(0.0..=1.0).contains(&b1) {
       return None;
     }
     let b2 = s2.dot(ray.direction) * inv;
@@ -129,44 +135,116 @@ impl Triangle {
 }
 
 impl TriangleMesh {
+  /// `indices` lists 3 vertices per triangle.
+  /// `normals` and `tex_coords` are either empty or one per vertex.
+  pub fn new(
+    vertices: Vec<Point>,
+    normals: Vec<Direction>,
+    tex_coords: Vec<UV>,
+    indices: Vec<u32>,
+    material: Arc<dyn Material>,
+  ) -> Self {
+    assert!(indices.len().is_multiple_of(3), "TriangleMesh: indices must come in triples");
+    assert!(normals.is_empty() || normals.len() == vertices.len());
+    assert!(tex_coords.is_empty() || tex_coords.len() == vertices.len());
+    Self { vertices, normals, tex_coords, indices, material }
+  }
+  pub fn triangle_count(&self) -> usize {
+    self.indices.len() / 3
+  }
+  pub fn material(&self) -> &Arc<dyn Material> {
+    &self.material
+  }
   fn default_material() -> Arc<dyn Material> {
     material::Lambertian::new_arc(texture::SolidColorTexture::new_arc(ColorRgb::YELLOW))
     // Lambertian::new_arc(UVCheckerTexture::new_arc(10.0, 10.0, SolidColorTexture::new_arc(ColorRgb::WHITE), SolidColorTexture::new_arc(ColorRgb::MAGENTA)))
   }
   /// Use default material
   pub fn load_obj_ignore_material(path: &str) -> Vec<Arc<TriangleMesh>> {
-    let (models, _materials) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS)
-      .expect(&format!("error loading .obj: {}", path));
-    // let _materials = _materials.expect(&format!("error loading .obj materials: {}", path));
-    let mut res: Vec<Arc<TriangleMesh>> = vec![];
-    for m in models {
-      let mesh = m.mesh;
-      let vertices: Vec<Point> = mesh
-        .positions
-        .chunks_exact(3)
-        .map(|p| Point::new(p[0], p[1], p[2]))
-        .collect();
-      let normals: Vec<Direction> = mesh
-        .normals
-        .chunks_exact(3)
-        .map(|n| Direction::new(n[0], n[1], n[2]))
-        .collect();
-      let tex_coords: Vec<UV> = mesh
-        .texcoords
-        .chunks_exact(2)
-        .map(|c| UV::new(c[0], c[1]))
-        .collect();
-      let material = Self::default_material();
-      let indices = mesh.indices.clone();
-      res.push(Arc::new(TriangleMesh {
-        vertices,
-        normals,
-        tex_coords,
-        material,
-        indices,
-      }))
+    Self::load_obj_with_material(path, Self::default_material())
+      .unwrap_or_else(|e| panic!("error loading .obj: {}: {}", path, e))
+  }
+
+  /// Use `material` for all the meshes, ignoring the `.mtl` files.
+  pub fn load_obj_with_material(
+    path: &str,
+    material: Arc<dyn Material>,
+  ) -> Result<Vec<Arc<TriangleMesh>>, tobj::LoadError> {
+    let (models, _materials) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS)?;
+    Ok(
+      models
+        .into_iter()
+        .map(|m| Arc::new(Self::from_tobj_mesh(m.mesh, material.clone())))
+        .collect(),
+    )
+  }
+
+  /// Load meshes with the materials declared in the `.mtl` files.
+  /// Texture paths are resolved relative to the `.mtl` file that declares them.
+  /// Meshes without a (loadable) material fall back to the default material.
+  pub fn load_obj(path: &str) -> Result<Vec<Arc<TriangleMesh>>, tobj::LoadError> {
+    let base_dir = std::path::Path::new(path).parent().unwrap_or(std::path::Path::new(""));
+    let file = std::fs::File::open(path).map_err(|_| tobj::LoadError::OpenFileFailed)?;
+    // the directory of the .mtl of every material, in the order tobj appends them.
+    let mtl_dirs = std::cell::RefCell::new(vec![]);
+    let (models, materials) =
+      tobj::load_obj_buf(&mut std::io::BufReader::new(file), &tobj::GPU_LOAD_OPTIONS, |mtl_path| {
+        let mtl_path = base_dir.join(mtl_path);
+        let result = tobj::load_mtl(&mtl_path);
+        if let Ok((ref mats, _)) = result {
+          let mtl_dir = mtl_path.parent().unwrap_or(base_dir).to_path_buf();
+          mtl_dirs.borrow_mut().extend(std::iter::repeat_n(mtl_dir, mats.len()));
+        }
+        result
+      })?;
+    let materials = materials.unwrap_or_else(|e| {
+      eprintln!("Warning: error loading .mtl of {}: {}. Using default material.", path, e);
+      vec![]
+    });
+    let materials: Vec<Arc<dyn Material>> = materials
+      .iter()
+      .zip(mtl_dirs.into_inner())
+      .map(|(t_mat, mtl_dir)| material::convert_material(t_mat, &mtl_dir))
+      .collect();
+    let default_material = Self::default_material();
+    Ok(
+      models
+        .into_iter()
+        .map(|m| {
+          let material = m
+            .mesh
+            .material_id
+            .and_then(|id| materials.get(id).cloned())
+            .unwrap_or_else(|| default_material.clone());
+          Arc::new(Self::from_tobj_mesh(m.mesh, material))
+        })
+        .collect(),
+    )
+  }
+
+  fn from_tobj_mesh(mesh: tobj::Mesh, material: Arc<dyn Material>) -> Self {
+    let vertices: Vec<Point> = mesh
+      .positions
+      .chunks_exact(3)
+      .map(|p| Point::new(p[0], p[1], p[2]))
+      .collect();
+    let normals: Vec<Direction> = mesh
+      .normals
+      .chunks_exact(3)
+      .map(|n| Direction::new(n[0], n[1], n[2]))
+      .collect();
+    let tex_coords: Vec<UV> = mesh
+      .texcoords
+      .chunks_exact(2)
+      .map(|c| UV::new(c[0], c[1]))
+      .collect();
+    TriangleMesh {
+      vertices,
+      normals,
+      tex_coords,
+      material,
+      indices: mesh.indices,
     }
-    res
   }
 
   pub fn triangles(self: Arc<Self>) -> Vec<Triangle> {
@@ -198,6 +276,24 @@ impl Hittable for Triangle {
   fn bounding_box(&self) -> Aabb {
     self.bbox
   }
+  // uniform on the triangle.
+  fn sample_surface(&self, sampler: &mut dyn Sampler) -> Option<SurfaceSample> {
+    if self.area < FLOAT_EPSILON {
+      return None;
+    }
+    let (b1, b2) = sampler::sample_uniform_triangle(sampler.get_2d());
+    let point = (1.0 - b1 - b2) * self.v0() + b1 * self.v1() + b2 * self.v2();
+    Some(SurfaceSample {
+      point,
+      unit_normal: self.face_unit_normal,
+      material: self.material(),
+      mat_uv: self.uv_at(b1, b2),
+      pdf_area: 1.0 / self.area,
+    })
+  }
+  fn pdf_area(&self, _point: &Point, _unit_normal: &Direction) -> Float {
+    if self.area < FLOAT_EPSILON { 0.0 } else { 1.0 / self.area }
+  }
 }
 #[cfg(test)]
 mod tests {
@@ -230,6 +326,7 @@ mod tests {
       film: film_arc.clone(),
       filters: Arc::new(filters),
       world: Arc::new(world),
+      seed: 0,
     });
 
     let output_path = std::path::Path::new("output").join(filename);
@@ -341,7 +438,7 @@ mod tests {
       0.8,
     )));
     let unit_sphere_light = geometry::UnitSphere::new_arc(mat_light.clone());
-    world.add_object(geometry::Instance::new_arc(
+    world.add_light(geometry::Instance::new_arc(
       unit_sphere_light.clone(),
       Mat4d::from_translation(Direction::new(3.0, 4.5, -1.0)) * Mat4d::from_scaling(4.0, 4.0, 4.0),
     ));
@@ -375,6 +472,7 @@ mod tests {
       film: film_arc.clone(),
       filters: Arc::new(FilterList::new()),
       world: Arc::new(world),
+      seed: 0,
     });
 
     let output_path = std::path::Path::new(working_dir).join("output/stanford-bunny.png");
@@ -414,7 +512,7 @@ mod tests {
         let tri = Triangle::new(mesh.clone(), idx);
 
         // 直接调用 intersect
-        if let Some((t, b1, b2)) = tri.intersect(&test_ray, 0.0001, 1000.0) {
+        if let Some((t, _b1, _b2)) = tri.intersect(&test_ray, 0.0001, 1000.0) {
           hit_count += 1;
           min_t = min_t.min(t);
         }
@@ -431,16 +529,16 @@ mod tests {
     } else {
       // 如果没击中，随机抽查一个三角形的顶点，看看它们到底在哪
       println!("错误：未击中任何三角形！");
-      if let Some(m) = meshes.first() {
-        if m.indices.len() >= 3 {
-          let v0 = m.vertices[m.indices[0] as usize];
-          let v1 = m.vertices[m.indices[1] as usize];
-          let v2 = m.vertices[m.indices[2] as usize];
-          println!(
-            "抽查第一个三角形顶点:\n  v0: {:?}\n  v1: {:?}\n  v2: {:?}",
-            v0, v1, v2
-          );
-        }
+      if let Some(m) = meshes.first()
+        && m.indices.len() >= 3
+      {
+        let v0 = m.vertices[m.indices[0] as usize];
+        let v1 = m.vertices[m.indices[1] as usize];
+        let v2 = m.vertices[m.indices[2] as usize];
+        println!(
+          "抽查第一个三角形顶点:\n  v0: {:?}\n  v1: {:?}\n  v2: {:?}",
+          v0, v1, v2
+        );
       }
     }
     println!("--- 测试结束 ---");
@@ -451,6 +549,69 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_load_obj_with_materials() {
+    let dir = std::env::temp_dir().join(format!("raytracer-ramel-load-obj-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("materials/textures")).unwrap();
+    // the texture sits next to the .mtl, not next to the .obj.
+    image::RgbImage::from_pixel(2, 2, image::Rgb([255, 0, 0]))
+      .save(dir.join("materials/textures/red.png"))
+      .unwrap();
+    std::fs::write(
+      dir.join("materials/quads.mtl"),
+      "newmtl glass\nKd 0.8 0.8 0.8\nTf 0.9 1 0.9\nNi 1.5\nillum 4\n\n\
+       newmtl lamp\nKd 0 0 0\nKe 4 4 4\n\n\
+       newmtl wood\nKd 0.6 0.4 0.2\nmap_Kd -bm 1.0 textures/red.png\n\n\
+       newmtl chrome\nKd 0.1 0.1 0.1\nKs 0.9 0.8 0.7\nNs 1000\n",
+    )
+    .unwrap();
+    std::fs::write(
+      dir.join("quads.obj"),
+      "mtllib materials/quads.mtl\n\
+       v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+       o bare\nf 1 2 4\n\
+       o glass\nusemtl glass\nf 1 2 3\n\
+       o lamp\nusemtl lamp\nf 1 3 4\n\
+       o wood\nusemtl wood\nf 2 3 4\n\
+       o chrome\nusemtl chrome\nf 1 2 3\n",
+    )
+    .unwrap();
+
+    let meshes = TriangleMesh::load_obj(dir.join("quads.obj").to_str().unwrap()).unwrap();
+    assert_eq!(meshes.len(), 5);
+    let [bare, glass, lamp, wood, chrome] = [0, 1, 2, 3, 4].map(|i| meshes[i].material.clone());
+
+    // only the lamp emits light.
+    let uv = UV::new(0.5, 0.5);
+    let p = Point::ZERO;
+    assert!((lamp.emitted(uv, p).r - 4.0).abs() < COLOR_EPSILON);
+    for mat in [&bare, &glass, &wood, &chrome] {
+      assert!(mat.emitted(uv, p).r < COLOR_EPSILON);
+    }
+
+    let ray = Ray::new(Point::new(0.5, 0.5, 1.0), Direction::new(0.0, 0.0, -1.0));
+    let normal = Direction::new(0.0, 0.0, 1.0);
+    let albedo = |mat: &Arc<dyn Material>| mat.albedo(&HitRecord::from_ray(&ray, normal, 1.0, mat.clone(), uv));
+    let close = |a: ColorRgb, b: ColorRgb| (a.r - b.r).abs().max((a.g - b.g).abs()).max((a.b - b.b).abs()) < 1e-3;
+    // no `usemtl`: the default material.
+    assert!(!bare.is_delta() && close(albedo(&bare), ColorRgb::YELLOW));
+    // illum 4 with Ni: glass tinted by Tf.
+    assert!(glass.is_delta() && close(albedo(&glass), ColorRgb::new(0.9, 1.0, 0.9)));
+    // map_Kd found relative to the .mtl, its options skipped.
+    assert!(!wood.is_delta() && close(albedo(&wood), ColorRgb::new(1.0, 0.0, 0.0)));
+    // Ks over Kd: metal of color Ks, nearly a mirror for Ns 1000.
+    assert!(chrome.is_delta() && close(albedo(&chrome), ColorRgb::new(0.9, 0.8, 0.7)));
+    let record = HitRecord::from_ray(&ray, normal, 1.0, chrome.clone(), uv);
+    let mut sampler = sampler::IndependentSampler::new(0);
+    for _ in 0..100 {
+      let sample = chrome.sample(&record, normal, &mut sampler).unwrap();
+      assert!(sample.wi.z > 0.99, "{:?}", sample.wi);
+    }
+
+    assert!(TriangleMesh::load_obj(dir.join("no-such-file.obj").to_str().unwrap()).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
   #[test]
   fn test_perspective_tetrahedron() {
     let mut world = World::new(|_ray| ColorRgb::new(0.2, 0.2, 0.2));
@@ -497,6 +658,7 @@ mod tests {
       film: film.clone(),
       filters: Arc::new(FilterList::new()),
       world: Arc::new(world),
+      seed: 0,
     });
 
     let working_dir = env!("CARGO_MANIFEST_DIR");
@@ -542,7 +704,7 @@ mod tests {
       10.0, 10.0, 10.0,
     )));
     let sphere_light = geometry::UnitSphere::new_arc(mat_light);
-    world.add_object(geometry::Instance::new_arc(
+    world.add_light(geometry::Instance::new_arc(
       sphere_light,
       Mat4d::from_translation(Direction::new(3.0, 4.5, 2.0)) * Mat4d::from_scaling(2.0, 2.0, 2.0),
     ));
@@ -570,6 +732,7 @@ mod tests {
       film: film.clone(),
       filters: Arc::new(filter_list),
       world: Arc::new(world),
+      seed: 0,
     });
 
     let output_path = std::path::Path::new(working_dir).join("output/persp-bunny.png");
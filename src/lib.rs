@@ -3,9 +3,13 @@ pub mod defs;
 pub mod geometry;
 pub mod camera;
 pub mod renderer;
+pub mod sampler;
 pub mod film;
+pub mod light;
 pub mod config;
 pub mod world;
 pub mod material;
+pub mod medium;
 pub mod filter;
-pub mod texture;
\ No newline at end of file
+pub mod texture;
+pub mod scene;
\ No newline at end of file
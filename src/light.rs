@@ -0,0 +1,122 @@
+use crate::prelude::*;
+
+/// A light sampled from a point, see `World::sample_light`.
+pub struct LightSample {
+  /// Where the light was sampled. None for the lights infinitely far away.
+  pub point: Option<Point>,
+  /// Unit direction from the point that sampled the light, towards it.
+  pub direction: Direction,
+  /// Emitted towards the point that sampled the light. For a delta light, what arrives there
+  /// from the whole light, like intensity / distance^2 for a point light.
+  pub radiance: ColorRgb,
+  /// With respect to the solid angle, including the probability of picking the light.
+  /// Only the probability of picking it for a delta light.
+  pub pdf: Float,
+  /// The light is a single point or direction, which no BSDF sample can hit.
+  pub is_delta: bool,
+}
+
+/// A light which is not an object of the world, only sampled explicitly: point, spot and
+/// directional lights.
+pub trait Light: Send + Sync {
+  /// Sample the light as seen from `origin`. None if it gives nothing there.
+  fn sample(&self, origin: &Point, sampler: &mut dyn Sampler) -> Option<LightSample>;
+  /// Lights with a size, infinitely far away, are seen by the rays escaping the world: the
+  /// radiance from unit `direction`. Black by default.
+  fn radiance(&self, _direction: &Direction) -> ColorRgb {
+    ColorRgb::BLACK
+  }
+  /// pdf (solid angle) of `sample` choosing unit `direction`, for the rays escaping the world.
+  /// 0 by default, as for the delta lights.
+  fn pdf(&self, _direction: &Direction) -> Float {
+    0.0
+  }
+  /// Where the light is and how much it gives, to pick it among many by `LightBvh`.
+  /// None for the lights infinitely far away, picked apart from the others.
+  fn bounds(&self) -> Option<LightBounds> {
+    None
+  }
+}
+
+mod bvh;
+mod directional;
+mod environment;
+mod point;
+mod sky;
+mod spot;
+mod sun;
+
+pub use bvh::{LightBounds, LightBvh};
+pub use directional::DirectionalLight;
+pub use environment::EnvironmentLight;
+pub use point::PointLight;
+pub use sky::PreethamSky;
+pub use spot::SpotLight;
+pub use sun::SunLight;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_light_sources() {
+    // radiance off a gray floor, seen straight down, with one light source under a uniform sky.
+    let shade_under = |sky: Float, light: Arc<dyn Light>, at: Point, samples: u32| {
+      let mut world = World::new(move |_ray| ColorRgb::WHITE * sky);
+      let gray = material::Lambertian::new_arc(texture::SolidColorTexture::new_arc(ColorRgb::new(0.5, 0.5, 0.5)));
+      world.add_object(geometry::Instance::new_arc(
+        Arc::new(geometry::UnitQuad::new(gray)),
+        Mat4d::from_rotation_x(-PI / 2.0) * Mat4d::from_scaling(100.0, 100.0, 1.0),
+      ));
+      world.add_light_source(light);
+      world.bvh_finalize();
+      let mut sampler = sampler::IndependentSampler::new(0);
+      let ray = Ray::new(at + Direction::new(0.0, 1.0, 0.0), Direction::new(0.0, -1.0, 0.0));
+      let sum = (0..samples).fold(0.0, |sum, index| {
+        sampler.start_pixel_sample((0, 0), index);
+        sum + renderer::SimpleRenderer::ray_color(&ray, &world, 2, &mut sampler).g
+      });
+      sum / samples as Float
+    };
+    let shade = |light: Arc<dyn Light>, at: Point, samples: u32| shade_under(0.0, light, at, samples);
+    let origin = Point::new(0.0, 0.0, 0.0);
+    let brdf = 0.5 / PI;
+
+    // intensity * cos / distance^2, exactly: nothing but the light is random.
+    let point = PointLight::new_arc(Point::new(1.0, 1.0, 0.0), ColorRgb::new(4.0, 4.0, 4.0));
+    let expected = brdf * 4.0 * (0.5 as Float).sqrt() / 2.0;
+    assert!((shade(point, origin, 1) - expected).abs() < 1e-5);
+
+    let spot = |at: Point| {
+      let spot = SpotLight::new(
+        Point::new(0.0, 2.0, 0.0),
+        Direction::new(0.0, -1.0, 0.0),
+        ColorRgb::new(4.0, 4.0, 4.0),
+        (30.0 as Float).to_radians(),
+        (20.0 as Float).to_radians(),
+      );
+      shade(Arc::new(spot), at, 1)
+    };
+    assert!((spot(origin) - brdf).abs() < 1e-5);
+    let fading = spot(Point::new(2.0 * (25.0 as Float).to_radians().tan(), 0.0, 0.0));
+    assert!(fading > 0.0 && fading < brdf * 0.5, "{}", fading);
+    assert_eq!(spot(Point::new(2.0, 0.0, 0.0)), 0.0);
+
+    // 45 degrees off the normal, the disk of a soft sun gives about as much as a delta light.
+    let direction = Direction::new(0.0, -1.0, -1.0);
+    let sharp = shade(Arc::new(DirectionalLight::new(direction, ColorRgb::new(2.0, 2.0, 2.0), 0.0)), origin, 1);
+    assert!((sharp - brdf * 2.0 * (0.5 as Float).sqrt()).abs() < 1e-5);
+    let soft = DirectionalLight::new(direction, ColorRgb::new(2.0, 2.0, 2.0), (10.0 as Float).to_radians());
+    assert!(soft.radiance(&-direction.normalize()).g > 50.0);
+    let soft = shade(Arc::new(soft), origin, 256);
+    assert!((soft - sharp).abs() < 0.02 * sharp, "{} {}", soft, sharp);
+
+    // the sky is never sampled as a light, so it keeps its whole weight where the BSDF rays
+    // see the sun too: 0.5 more, exactly, with the same samples.
+    let sun = || -> Arc<dyn Light> {
+      Arc::new(DirectionalLight::new(direction, ColorRgb::WHITE, (10.0 as Float).to_radians()))
+    };
+    let (clear, with_sky) = (shade_under(0.0, sun(), origin, 2048), shade_under(1.0, sun(), origin, 2048));
+    assert!((with_sky - clear - 0.5).abs() < 1e-4, "{} {}", with_sky, clear);
+  }
+}
@@ -0,0 +1,356 @@
+use crate::prelude::*;
+use crate::sampler::ONE_MINUS_EPSILON;
+
+// Below this depth, nodes are split in two halves, so that the way to a leaf fits in a u64.
+const MAX_SAOH_DEPTH: u32 = 32;
+// Candidate split planes of the surface area orientation heuristic.
+const BUCKETS: usize = 12;
+
+/// Where lights are, how much they give and towards where: enough to bound what they may give
+/// to a point without sampling them. See `LightBvh`.
+#[derive(Clone, Copy)]
+pub struct LightBounds {
+  bounds: Aabb,
+  // luminance times the solid angle and the area it leaves, or 4 pi times the intensity.
+  power: Float,
+  // the normals of the surfaces (or the directions of emission) are within theta_o of the
+  // unit `axis`, and the light leaves them at most theta_e apart.
+  axis: Direction,
+  cos_theta_o: Float,
+  cos_theta_e: Float,
+  // both sides of the surfaces shine.
+  two_sided: bool,
+}
+
+impl LightBounds {
+  pub fn new(
+    bounds: Aabb,
+    power: Float,
+    axis: Direction,
+    cos_theta_o: Float,
+    cos_theta_e: Float,
+    two_sided: bool,
+  ) -> Self {
+    Self { bounds, power, axis: axis.normalize(), cos_theta_o, cos_theta_e, two_sided }
+  }
+
+  /// A light at `point`, the same in every direction.
+  pub fn point(point: Point, power: Float) -> Self {
+    Self::new(Aabb { min: point, max: point }, power, Direction::new(0.0, 0.0, 1.0), -1.0, 0.0, false)
+  }
+
+  /// Of an emissive object, from points sampled on its surface: None if it cannot be sampled.
+  /// Emissive materials shine on both sides of the surfaces.
+  pub fn of_object(object: &dyn Hittable) -> Option<Self> {
+    const SAMPLES: u32 = 64;
+    let mut sampler = sampler::IndependentSampler::new(0);
+    let mut area = 0.0;
+    let mut radiance: Float = 0.0;
+    let mut normals = Vec::with_capacity(SAMPLES as usize);
+    for index in 0..SAMPLES {
+      sampler.start_pixel_sample((0, 0), index);
+      let sample = object.sample_surface(&mut sampler)?;
+      if sample.pdf_area > 0.0 {
+        area += 1.0 / sample.pdf_area / SAMPLES as Float;
+      }
+      // the brightest point seen, a textured light may be dark in most places.
+      radiance = radiance.max(sample.material.emitted(sample.mat_uv, sample.point).luminance());
+      normals.push(sample.unit_normal);
+    }
+    // a flat surface keeps one normal, anything else is taken to shine anywhere.
+    let axis = normals[0];
+    let flat = normals.iter().all(|normal| normal.dot(axis) > 1.0 - 1e-4);
+    let cos_theta_o = if flat { 1.0 } else { -1.0 };
+    Some(Self::new(object.bounding_box(), 2.0 * PI * radiance * area, axis, cos_theta_o, 0.0, true))
+  }
+
+  pub fn power(&self) -> Float {
+    self.power
+  }
+
+  /// Bounds of both.
+  pub fn union(a: &Self, b: &Self) -> Self {
+    if a.power <= 0.0 {
+      return *b;
+    }
+    if b.power <= 0.0 {
+      return *a;
+    }
+    let (axis, cos_theta_o) = cone_union((a.axis, a.cos_theta_o), (b.axis, b.cos_theta_o));
+    Self {
+      bounds: Aabb::union(a.bounds, b.bounds),
+      power: a.power + b.power,
+      axis,
+      cos_theta_o,
+      cos_theta_e: a.cos_theta_e.min(b.cos_theta_e),
+      two_sided: a.two_sided || b.two_sided,
+    }
+  }
+
+  /// At least what the lights may give to `point`, up to a constant: the power over the
+  /// distance squared, times the cosine of the smallest angle any of them may shine at it.
+  /// 0 if none of them faces it.
+  pub fn importance(&self, point: &Point) -> Float {
+    let center = self.bounds.centroid();
+    let to_point = *point - center;
+    let dist_sq = to_point.length_squared();
+    let radius_sq = (self.bounds.max - self.bounds.min).length_squared() / 4.0;
+    let mut cos_w = if dist_sq > 0.0 { self.axis.dot(to_point) / dist_sq.sqrt() } else { 1.0 };
+    if self.two_sided {
+      cos_w = cos_w.abs();
+    }
+    // the bounds seen from the point are within theta_b of the center, all around if it is in them.
+    let cos_b = if dist_sq > radius_sq { (1.0 - radius_sq / dist_sq).sqrt() } else { -1.0 };
+    let cos_x = cos_minus_clamped(cos_w, self.cos_theta_o);
+    let cos_p = cos_minus_clamped(cos_x, cos_b);
+    if cos_p <= self.cos_theta_e {
+      return 0.0;
+    }
+    // not closer than the size of the bounds, the lights may be anywhere in them.
+    self.power * cos_p / dist_sq.max(radius_sq.sqrt())
+  }
+
+  // how much of the directions the lights cover, with their spread of theta_e, for the split
+  // heuristic.
+  fn orientation_measure(&self) -> Float {
+    let theta_o = self.cos_theta_o.clamp(-1.0, 1.0).acos();
+    let theta_e = self.cos_theta_e.clamp(-1.0, 1.0).acos();
+    let theta_w = (theta_o + theta_e).min(PI);
+    let sin_o = theta_o.sin();
+    2.0 * PI * (1.0 - self.cos_theta_o)
+      + PI / 2.0
+        * (2.0 * theta_w * sin_o - (theta_o - 2.0 * theta_w).cos() - 2.0 * theta_o * sin_o + self.cos_theta_o)
+  }
+}
+
+// cos(max(0, a - b)) of the angles a and b in [0, pi], from their cosines.
+fn cos_minus_clamped(cos_a: Float, cos_b: Float) -> Float {
+  if cos_a >= cos_b {
+    return 1.0;
+  }
+  let sin = |cos: Float| (1.0 - cos * cos).max(0.0).sqrt();
+  cos_a * cos_b + sin(cos_a) * sin(cos_b)
+}
+
+// The smallest cone around both cones of (unit axis, cos of the half angle).
+fn cone_union(a: (Direction, Float), b: (Direction, Float)) -> (Direction, Float) {
+  let theta_a = a.1.clamp(-1.0, 1.0).acos();
+  let theta_b = b.1.clamp(-1.0, 1.0).acos();
+  let theta_d = a.0.dot(b.0).clamp(-1.0, 1.0).acos();
+  if (theta_d + theta_b).min(PI) <= theta_a {
+    return a;
+  }
+  if (theta_d + theta_a).min(PI) <= theta_b {
+    return b;
+  }
+  let theta_o = (theta_a + theta_d + theta_b) / 2.0;
+  let turn = a.0.cross(b.0);
+  if theta_o >= PI || turn.length_squared() == 0.0 {
+    return (a.0, -1.0);
+  }
+  // turn the axis of `a` towards `b`, until the cone reaches the far side of `b`.
+  let axis = Quat::from_axis_angle(turn, theta_o - theta_a).rotate(a.0);
+  (axis.normalize(), theta_o.cos())
+}
+
+// A leaf holds one light, the first child of an interior node follows it.
+enum NodeContent {
+  Light(usize),
+  Children { second: usize },
+}
+
+struct LightNode {
+  bounds: LightBounds,
+  content: NodeContent,
+}
+
+/// A tree over the bounds of many lights, to pick one for a point by how much each may give
+/// to it: at every node, either child by its `LightBounds::importance`.
+/// The tree is split by the surface area orientation heuristic of Conty Estevez and Kulla,
+/// "Importance Sampling of Many Lights with Adaptive Tree Splitting".
+pub struct LightBvh {
+  nodes: Vec<LightNode>,
+  // the way from the root to the leaf of each light, from the lowest bit: 1 for the second
+  // child. None for the lights not in the tree.
+  trails: Vec<Option<u64>>,
+}
+
+impl LightBvh {
+  /// Lights are numbered by their place in `lights`. Those of no bounds or no power are left
+  /// out, and never picked.
+  pub fn build(lights: &[Option<LightBounds>]) -> Self {
+    let mut items: Vec<(usize, LightBounds)> = lights
+      .iter()
+      .enumerate()
+      .filter_map(|(light, bounds)| bounds.filter(|b| b.power > 0.0).map(|b| (light, b)))
+      .collect();
+    let mut bvh = Self { nodes: Vec::with_capacity(2 * items.len()), trails: vec![None; lights.len()] };
+    if !items.is_empty() {
+      bvh.build_node(&mut items, 0, 0);
+    }
+    bvh
+  }
+
+  fn build_node(&mut self, items: &mut [(usize, LightBounds)], trail: u64, depth: u32) -> LightBounds {
+    if let [(light, bounds)] = *items {
+      self.nodes.push(LightNode { bounds, content: NodeContent::Light(light) });
+      self.trails[light] = Some(trail);
+      return bounds;
+    }
+    let mid = split(items, depth);
+    let index = self.nodes.len();
+    // filled in once the children are built.
+    self.nodes.push(LightNode { bounds: items[0].1, content: NodeContent::Children { second: 0 } });
+    let first = self.build_node(&mut items[..mid], trail, depth + 1);
+    let second = self.nodes.len();
+    let second_bounds = self.build_node(&mut items[mid..], trail | 1 << depth, depth + 1);
+    self.nodes[index] =
+      LightNode { bounds: LightBounds::union(&first, &second_bounds), content: NodeContent::Children { second } };
+    self.nodes[index].bounds
+  }
+
+  /// No light in the tree.
+  pub fn is_empty(&self) -> bool {
+    self.nodes.is_empty()
+  }
+
+  /// Pick a light for `point` with `u` in [0, 1): its number and the probability of picking it.
+  /// None if no light faces the point.
+  pub fn sample(&self, point: &Point, u: Float) -> Option<(usize, Float)> {
+    if self.is_empty() || self.nodes[0].bounds.importance(point) <= 0.0 {
+      return None;
+    }
+    let (mut index, mut pmf, mut u) = (0, 1.0, u);
+    loop {
+      match self.nodes[index].content {
+        NodeContent::Light(light) => return Some((light, pmf)),
+        NodeContent::Children { second } => {
+          let p = self.first_probability(point, index, second)?;
+          if u < p {
+            index += 1;
+            pmf *= p;
+            u = (u / p).min(ONE_MINUS_EPSILON);
+          } else {
+            index = second;
+            pmf *= 1.0 - p;
+            u = ((u - p) / (1.0 - p)).min(ONE_MINUS_EPSILON);
+          }
+        }
+      }
+    }
+  }
+
+  /// Probability of `sample` picking `light` for `point`.
+  pub fn pmf(&self, point: &Point, light: usize) -> Float {
+    let Some(trail) = self.trails.get(light).copied().flatten() else {
+      return 0.0;
+    };
+    if self.nodes[0].bounds.importance(point) <= 0.0 {
+      return 0.0;
+    }
+    let (mut index, mut pmf, mut depth) = (0, 1.0, 0);
+    while let NodeContent::Children { second } = self.nodes[index].content {
+      let Some(p) = self.first_probability(point, index, second) else {
+        return 0.0;
+      };
+      if trail >> depth & 1 == 0 {
+        index += 1;
+        pmf *= p;
+      } else {
+        index = second;
+        pmf *= 1.0 - p;
+      }
+      depth += 1;
+    }
+    pmf
+  }
+
+  // probability of going to the first child of the node at `index`. None if neither faces `point`.
+  fn first_probability(&self, point: &Point, index: usize, second: usize) -> Option<Float> {
+    let first = self.nodes[index + 1].bounds.importance(point);
+    let second = self.nodes[second].bounds.importance(point);
+    if first + second <= 0.0 { None } else { Some(first / (first + second)) }
+  }
+}
+
+// Sort `items` on the longest axis of their centers, and tell where to cut them in two.
+fn split(items: &mut [(usize, LightBounds)], depth: u32) -> usize {
+  let centers = items.iter().fold(Aabb::default(), |bounds, (_, light)| {
+    let center = light.bounds.centroid();
+    Aabb::union(bounds, Aabb { min: center, max: center })
+  });
+  let axis = centers.longest_axis();
+  let (min, extent) = (centers.min[axis], centers.max[axis] - centers.min[axis]);
+  items.sort_by(|a, b| a.1.bounds.centroid()[axis].total_cmp(&b.1.bounds.centroid()[axis]));
+  let half = items.len() / 2;
+  if depth >= MAX_SAOH_DEPTH || extent <= 0.0 {
+    return half;
+  }
+
+  // the lights in more power, wider spread and larger boxes are more often picked, so are
+  // better kept apart.
+  let cost = |items: &[(usize, LightBounds)]| {
+    let bounds = items.iter().skip(1).fold(items[0].1, |bounds, (_, light)| LightBounds::union(&bounds, light));
+    bounds.power * bounds.orientation_measure() * bounds.bounds.surface_area()
+  };
+  let bucket = |light: &LightBounds| {
+    (((light.bounds.centroid()[axis] - min) / extent * BUCKETS as Float) as usize).min(BUCKETS - 1)
+  };
+  (1..BUCKETS)
+    .map(|plane| items.partition_point(|(_, light)| bucket(light) < plane))
+    .filter(|&mid| mid > 0 && mid < items.len())
+    .map(|mid| (mid, cost(&items[..mid]) + cost(&items[mid..])))
+    .min_by(|a, b| a.1.total_cmp(&b.1))
+    .map_or(half, |(mid, _)| mid)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_light_bvh() {
+    // a row of small lights facing down over the floor, and one facing up.
+    let mut lights: Vec<Option<LightBounds>> = (0..100)
+      .map(|i| {
+        let center = Point::new(i as Float, 1.0, 0.0);
+        let half = Direction::new(0.1, 0.0, 0.1);
+        let bounds = Aabb { min: center - half, max: center + half };
+        Some(LightBounds::new(bounds, 1.0, Direction::new(0.0, -1.0, 0.0), 1.0, 0.0, false))
+      })
+      .collect();
+    lights.push(Some(LightBounds::new(
+      Aabb { min: Point::new(50.0, 2.0, 0.0), max: Point::new(50.0, 2.0, 0.0) },
+      100.0,
+      Direction::new(0.0, 1.0, 0.0),
+      1.0,
+      0.0,
+      false,
+    )));
+    lights.push(None);
+    let bvh = LightBvh::build(&lights);
+
+    let point = Point::new(20.0, 0.0, 0.0);
+    let total: Float = (0..lights.len()).map(|light| bvh.pmf(&point, light)).sum();
+    assert!((total - 1.0).abs() < 1e-4, "{}", total);
+    assert_eq!(bvh.pmf(&point, 100), 0.0);
+    assert_eq!(bvh.pmf(&point, 101), 0.0);
+    // the lights right above are picked far more than the others.
+    assert!(bvh.pmf(&point, 20) > 20.0 * bvh.pmf(&point, 80));
+
+    let mut near = 0;
+    for i in 0..1000 {
+      let u = (i as Float + 0.5) / 1000.0;
+      let (light, pmf) = bvh.sample(&point, u).unwrap();
+      assert_eq!(pmf, bvh.pmf(&point, light));
+      near += (light.abs_diff(20) <= 5) as u32;
+    }
+    assert!(near > 500, "{}", near);
+
+    // above them all, the light facing up is picked the most: the bounds of the others only
+    // tell they may reach there from the sides of the wide row.
+    let above = Point::new(50.0, 5.0, 0.0);
+    assert!(bvh.pmf(&above, 100) > 0.7, "{}", bvh.pmf(&above, 100));
+    assert!(bvh.pmf(&above, 50) < 0.01);
+  }
+}
@@ -0,0 +1,46 @@
+use crate::prelude::*;
+
+/// Parallel light from infinitely far away, like the sun. With an angular diameter, it comes
+/// from a disk of the sky instead, which softens the shadows and can be seen.
+pub struct DirectionalLight {
+  // unit direction towards the light.
+  towards: Direction,
+  irradiance: ColorRgb,
+  // the disk, if the light has a size.
+  disk: Option<light::SunLight>,
+}
+
+impl DirectionalLight {
+  /// Light travelling along `direction`, giving `irradiance` to a surface facing it.
+  /// `angular_diameter` in radians, 0 for a delta light.
+  pub fn new(direction: Direction, irradiance: ColorRgb, angular_diameter: Float) -> Self {
+    let towards = -direction.normalize();
+    let disk = (angular_diameter > 0.0)
+      .then(|| light::SunLight::from_irradiance(towards, angular_diameter / 2.0, irradiance));
+    Self { towards, irradiance, disk }
+  }
+}
+
+impl Light for DirectionalLight {
+  fn sample(&self, _origin: &Point, sampler: &mut dyn Sampler) -> Option<LightSample> {
+    Some(match self.disk {
+      Some(disk) => {
+        let (direction, radiance, pdf) = disk.sample(sampler);
+        LightSample { point: None, direction, radiance, pdf, is_delta: false }
+      }
+      None => LightSample {
+        point: None,
+        direction: self.towards,
+        radiance: self.irradiance,
+        pdf: 1.0,
+        is_delta: true,
+      },
+    })
+  }
+  fn radiance(&self, direction: &Direction) -> ColorRgb {
+    self.disk.map_or(ColorRgb::BLACK, |disk| disk.radiance(direction))
+  }
+  fn pdf(&self, direction: &Direction) -> Float {
+    self.disk.map_or(0.0, |disk| disk.pdf(direction))
+  }
+}
@@ -0,0 +1,208 @@
+use crate::prelude::*;
+
+/// Light coming from every direction, infinitely far away, read from an equirectangular
+/// (latitude-longitude) image.
+/// In the frame of the image, +y is up: the top row is straight up, the middle column looks
+/// along -z and the image goes round to the right (+x at 3/4 of the width).
+/// Directions are sampled by the luminance of the pixels, weighted by the solid angle they
+/// cover, so a small bright sun gets its share of the samples.
+/// A sun disk, too small for the pixels, may be added on top with `with_sun`.
+pub struct EnvironmentLight {
+  width: u32,
+  height: u32,
+  pixels: Vec<ColorRgb>,
+  scale: Float,
+  // from the frame of the image to the world, a rotation.
+  rotation: Mat4d,
+  distribution: sampler::Distribution2D,
+  sun: Option<light::SunLight>,
+}
+
+impl EnvironmentLight {
+  /// Pixels are linear radiance.
+  pub fn new(image: &image::Rgb32FImage) -> Self {
+    let pixels = image.pixels().map(|p| ColorRgb::from_rgb32f(*p)).collect();
+    Self::from_pixels(image.width(), image.height(), pixels)
+  }
+
+  /// Read an .hdr or .exr image. Other formats are taken as linear too.
+  pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<Self, image::ImageError> {
+    Ok(Self::new(&image::open(path)?.into_rgb32f()))
+  }
+
+  /// A `width` x `height` image of the radiance `f` gives for each direction (in the frame of
+  /// the image), at the center of the pixels.
+  pub fn from_fn<F>(width: u32, height: u32, f: F) -> Self
+  where
+    F: Fn(Direction) -> ColorRgb,
+  {
+    let pixels = (0..width * height)
+      .map(|i| {
+        let u = ((i % width) as Float + 0.5) / width as Float;
+        let v = ((i / width) as Float + 0.5) / height as Float;
+        f(direction_of(u, v))
+      })
+      .collect();
+    Self::from_pixels(width, height, pixels)
+  }
+
+  fn from_pixels(width: u32, height: u32, pixels: Vec<ColorRgb>) -> Self {
+    // rows near the poles cover less solid angle.
+    let func: Vec<Float> = pixels
+      .iter()
+      .enumerate()
+      .map(|(i, c)| {
+        let theta = PI * ((i as u32 / width) as Float + 0.5) / height as Float;
+        c.luminance().max(0.0) * theta.sin()
+      })
+      .collect();
+    let distribution = sampler::Distribution2D::new(&func, width as usize, height as usize);
+    Self { width, height, pixels, scale: 1.0, rotation: Mat4d::IDENTITY, distribution, sun: None }
+  }
+
+  /// `rotation` turns the image in the world. It shall be a pure rotation.
+  pub fn with_rotation(mut self, rotation: Mat4d) -> Self {
+    self.rotation = rotation;
+    self
+  }
+  /// Multiplies the radiance.
+  pub fn with_scale(mut self, scale: Float) -> Self {
+    self.scale = scale;
+    self
+  }
+
+  /// Add `sun` to the image, in its frame: it turns with it, and is scaled too.
+  pub fn with_sun(mut self, sun: light::SunLight) -> Self {
+    self.sun = Some(sun);
+    self
+  }
+  pub fn sun(&self) -> Option<&light::SunLight> {
+    self.sun.as_ref()
+  }
+
+  /// Radiance arriving from `direction` (world space, towards the environment).
+  pub fn radiance(&self, direction: &Direction) -> ColorRgb {
+    let local = self.rotation.transpose_transform_vector(direction.normalize());
+    let sun = self.sun.map_or(ColorRgb::BLACK, |sun| sun.radiance(&local));
+    (self.pixel(local) + sun) * self.scale
+  }
+
+  // the pixel seen along unit `local`.
+  fn pixel(&self, local: Direction) -> ColorRgb {
+    let (u, v) = uv_of(local);
+    let x = ((u * self.width as Float) as u32).min(self.width - 1);
+    let y = ((v * self.height as Float) as u32).min(self.height - 1);
+    self.pixels[(y * self.width + x) as usize]
+  }
+
+  // probability of sampling the sun rather than the image, by their share of the power.
+  fn sun_probability(&self) -> Float {
+    let Some(sun) = self.sun else {
+      return 0.0;
+    };
+    // (u, v) to the sphere stretches the area by 2 pi * pi * sin(theta), which the
+    // distribution is already weighted by.
+    let image = self.distribution.integral() * 2.0 * PI * PI;
+    if image + sun.power() > 0.0 { sun.power() / (image + sun.power()) } else { 0.0 }
+  }
+
+  /// A world space direction towards the environment, the radiance from there and the pdf
+  /// with respect to the solid angle. None if the environment is black.
+  pub fn sample(&self, sampler: &mut dyn Sampler) -> Option<(Direction, ColorRgb, Float)> {
+    let sun_probability = self.sun_probability();
+    if let Some(sun) = self.sun
+      && sun_probability > 0.0
+      && sampler.get_1d() < sun_probability
+    {
+      let (local, radiance, pdf) = sun.sample(sampler);
+      let direction = self.rotation.transform_vector(local);
+      let pdf = sun_probability * pdf + (1.0 - sun_probability) * self.image_pdf(local);
+      return Some((direction, (self.pixel(local) + radiance) * self.scale, pdf));
+    }
+    if self.distribution.integral() <= 0.0 {
+      return None;
+    }
+    let ((u, v), pdf_uv) = self.distribution.sample(sampler.get_2d());
+    let sin_theta = (PI * v).sin();
+    if pdf_uv <= 0.0 || sin_theta <= 0.0 {
+      return None;
+    }
+    let direction = self.rotation.transform_vector(direction_of(u, v));
+    Some((direction, self.radiance(&direction), self.pdf(&direction)))
+  }
+
+  /// pdf (solid angle) of `sample` choosing `direction`.
+  pub fn pdf(&self, direction: &Direction) -> Float {
+    let local = self.rotation.transpose_transform_vector(direction.normalize());
+    let sun_probability = self.sun_probability();
+    let sun = self.sun.map_or(0.0, |sun| sun.pdf(&local));
+    sun_probability * sun + (1.0 - sun_probability) * self.image_pdf(local)
+  }
+
+  // pdf of sampling the image towards unit `local`.
+  fn image_pdf(&self, local: Direction) -> Float {
+    if self.distribution.integral() <= 0.0 {
+      return 0.0;
+    }
+    let (u, v) = uv_of(local);
+    let sin_theta = (PI * v).sin();
+    if sin_theta <= 0.0 {
+      return 0.0;
+    }
+    self.distribution.pdf((u, v)) / (2.0 * PI * PI * sin_theta)
+  }
+}
+
+// The unit direction at (u, v) of the image.
+fn direction_of(u: Float, v: Float) -> Direction {
+  let (sin_t, cos_t) = (PI * v).sin_cos();
+  let (sin_p, cos_p) = (2.0 * PI * u).sin_cos();
+  Direction::new(-sin_t * sin_p, cos_t, sin_t * cos_p)
+}
+
+// (u, v) of the unit direction `d`, in [0, 1].
+fn uv_of(d: Direction) -> (Float, Float) {
+  let phi = (-d.x).atan2(d.z);
+  let phi = if phi < 0.0 { phi + 2.0 * PI } else { phi };
+  (phi / (2.0 * PI), d.y.clamp(-1.0, 1.0).acos() / PI)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_environment_sampling() {
+    // a dim sky and a bright sun, turned so that it is straight ahead.
+    let sun = Direction::new(0.0, 0.5, 0.8).normalize();
+    let env = EnvironmentLight::from_fn(64, 32, |d| {
+      if d.dot(sun) > 0.97 { ColorRgb::new(500.0, 500.0, 500.0) } else { ColorRgb::new(0.1, 0.1, 0.1) }
+    })
+    .with_rotation(Mat4d::from_rotation_y(PI / 2.0));
+    let turned = Mat4d::from_rotation_y(PI / 2.0).transform_vector(sun);
+    assert_eq!(env.radiance(&turned).r, 500.0);
+
+    let mut sampler = sampler::IndependentSampler::new(3);
+    let mut towards_sun = 0;
+    let mut estimate = 0.0;
+    for index in 0..4000 {
+      sampler.start_pixel_sample((0, 0), index);
+      let (direction, radiance, pdf) = env.sample(&mut sampler).unwrap();
+      assert!((pdf - env.pdf(&direction)).abs() <= 1e-3 * pdf, "{} {}", pdf, env.pdf(&direction));
+      assert_eq!(radiance, env.radiance(&direction));
+      towards_sun += (direction.dot(turned) > 0.95) as u32;
+      estimate += radiance.r / pdf / 4000.0;
+    }
+    assert!(towards_sun > 3900, "{}", towards_sun);
+
+    // the power over the sphere, summed over the pixels.
+    let expected: Float = (0..32 * 64)
+      .map(|i| {
+        let (v0, v1) = ((i / 64) as Float / 32.0, (i / 64 + 1) as Float / 32.0);
+        let solid_angle = 2.0 * PI / 64.0 * ((PI * v0).cos() - (PI * v1).cos());
+        env.pixels[i].r * solid_angle
+      })
+      .sum();
+    assert!((estimate - expected).abs() < 0.01 * expected, "{} {}", estimate, expected);
+  }
+}
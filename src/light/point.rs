@@ -0,0 +1,37 @@
+use crate::prelude::*;
+
+/// Light from a single point, the same in every direction.
+pub struct PointLight {
+  position: Point,
+  /// Radiant intensity: what arrives 1 unit away.
+  intensity: ColorRgb,
+}
+
+impl PointLight {
+  pub fn new(position: Point, intensity: ColorRgb) -> Self {
+    Self { position, intensity }
+  }
+  pub fn new_arc(position: Point, intensity: ColorRgb) -> Arc<Self> {
+    Arc::new(Self::new(position, intensity))
+  }
+}
+
+impl Light for PointLight {
+  fn sample(&self, origin: &Point, _sampler: &mut dyn Sampler) -> Option<LightSample> {
+    let to_light = self.position - *origin;
+    let dist_sq = to_light.length_squared();
+    if dist_sq <= 0.0 {
+      return None;
+    }
+    Some(LightSample {
+      point: Some(self.position),
+      direction: to_light / dist_sq.sqrt(),
+      radiance: self.intensity / dist_sq,
+      pdf: 1.0,
+      is_delta: true,
+    })
+  }
+  fn bounds(&self) -> Option<light::LightBounds> {
+    Some(light::LightBounds::point(self.position, 4.0 * PI * self.intensity.luminance()))
+  }
+}
@@ -0,0 +1,196 @@
+use crate::prelude::*;
+
+/// Clear sky of Preetham, Shirley and Smits, "A Practical Analytic Model for Daylight", lit by
+/// the sun at `elevation` above the horizon and `azimuth` from -z towards +x.
+/// `turbidity` is the haze, from 2 (very clear) to 10 (hazy). Below the horizon is a diffuse
+/// ground of `ground_albedo`, lit by the sky and the sun.
+/// Radiance is in kcd/m² (a clear sky is a few thousand cd/m²), an exposure of about -5 brings
+/// it to the range of the other lights.
+pub struct PreethamSky {
+  sun_direction: Direction,
+  turbidity: Float,
+  // Y, x and y at the zenith, and the Perez coefficients A to E of each.
+  zenith: [Float; 3],
+  perez: [[Float; 5]; 3],
+  ground: ColorRgb,
+  // of the whole disk of the sun, on a surface facing it.
+  sun_irradiance: ColorRgb,
+}
+
+impl PreethamSky {
+  pub const TURBIDITY: std::ops::RangeInclusive<Float> = 1.7..=10.0;
+
+  /// Angles in radians. The elevation shall be in [0, pi / 2].
+  pub fn new(elevation: Float, azimuth: Float, turbidity: Float, ground_albedo: ColorRgb) -> Self {
+    let (sin_e, cos_e) = elevation.sin_cos();
+    let (sin_a, cos_a) = azimuth.sin_cos();
+    let sun_direction = Direction::new(cos_e * sin_a, sin_e, -cos_e * cos_a);
+    let t = turbidity;
+    let theta_s = PI / 2.0 - elevation;
+
+    let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+    let zenith_y = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+    let cubic = |c: [[Float; 4]; 3]| {
+      let poly = |c: [Float; 4]| ((c[0] * theta_s + c[1]) * theta_s + c[2]) * theta_s + c[3];
+      t * t * poly(c[0]) + t * poly(c[1]) + poly(c[2])
+    };
+    let zenith_x = cubic([
+      [0.00166, -0.00375, 0.00209, 0.0],
+      [-0.02903, 0.06377, -0.03202, 0.00394],
+      [0.11693, -0.21196, 0.06052, 0.25886],
+    ]);
+    let zenith_y_chroma = cubic([
+      [0.00275, -0.00610, 0.00317, 0.0],
+      [-0.04214, 0.08970, -0.04153, 0.00516],
+      [0.15346, -0.26756, 0.06670, 0.26688],
+    ]);
+    let perez = [
+      [0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703],
+      [-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452],
+      [-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529],
+    ];
+
+    let mut sky = Self {
+      sun_direction,
+      turbidity,
+      zenith: [zenith_y, zenith_x, zenith_y_chroma],
+      perez,
+      ground: ColorRgb::BLACK,
+      sun_irradiance: sun_irradiance(theta_s, turbidity),
+    };
+    // the ground reflects what the sky and the sun give to a horizontal surface.
+    let irradiance = sky.horizontal_irradiance() + sky.sun_irradiance * sin_e;
+    sky.ground = ground_albedo * irradiance / PI;
+    sky
+  }
+
+  /// Unit direction towards the sun.
+  pub fn sun_direction(&self) -> Direction {
+    self.sun_direction
+  }
+  pub fn turbidity(&self) -> Float {
+    self.turbidity
+  }
+
+  /// Radiance of the sky from unit `direction`, without the sun.
+  pub fn radiance(&self, direction: &Direction) -> ColorRgb {
+    if direction.y < 0.0 {
+      return self.ground;
+    }
+    // the model does not hold at the horizon itself.
+    let cos_theta = direction.y.max(0.01);
+    let cos_gamma = direction.dot(self.sun_direction).clamp(-1.0, 1.0);
+    let cos_theta_s = self.sun_direction.y.clamp(0.0, 1.0);
+    let perez = |[a, b, c, d, e]: [Float; 5], cos_theta: Float, cos_gamma: Float| {
+      let gamma = cos_gamma.acos();
+      (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+    };
+    let [big_y, x, y] = std::array::from_fn(|i| {
+      let coefficients = self.perez[i];
+      self.zenith[i] * perez(coefficients, cos_theta, cos_gamma) / perez(coefficients, 1.0, cos_theta_s)
+    });
+    xyy_to_rgb(x, y, big_y.max(0.0))
+  }
+
+  /// The sun disk matching the sky, of `angular_radius` (radians): however large, it gives the
+  /// light of the real sun, dimmed through the atmosphere.
+  pub fn sun(&self, angular_radius: Float) -> light::SunLight {
+    light::SunLight::from_irradiance(self.sun_direction, angular_radius, self.sun_irradiance)
+  }
+
+  /// The sky (and the ground) as an environment map of `width` x `height`, with the sun disk
+  /// of `sun_radius` if any, to be used as the background and the light of a world.
+  pub fn environment(&self, width: u32, height: u32, sun_radius: Option<Float>) -> light::EnvironmentLight {
+    let environment = light::EnvironmentLight::from_fn(width, height, |direction| self.radiance(&direction));
+    match sun_radius {
+      Some(radius) => environment.with_sun(self.sun(radius)),
+      None => environment,
+    }
+  }
+
+  // irradiance of the sky on a horizontal surface facing up, by the midpoint rule.
+  fn horizontal_irradiance(&self) -> ColorRgb {
+    const THETA_STEPS: u32 = 32;
+    const PHI_STEPS: u32 = 64;
+    let (d_theta, d_phi) = (PI / 2.0 / THETA_STEPS as Float, 2.0 * PI / PHI_STEPS as Float);
+    let mut sum = ColorRgb::BLACK;
+    for i in 0..THETA_STEPS {
+      let (sin_t, cos_t) = ((i as Float + 0.5) * d_theta).sin_cos();
+      for j in 0..PHI_STEPS {
+        let (sin_p, cos_p) = ((j as Float + 0.5) * d_phi).sin_cos();
+        let direction = Direction::new(sin_t * cos_p, cos_t, sin_t * sin_p);
+        sum += self.radiance(&direction) * (cos_t * sin_t * d_theta * d_phi);
+      }
+    }
+    sum
+  }
+}
+
+// CIE xyY to linear sRGB.
+fn xyy_to_rgb(x: Float, y: Float, big_y: Float) -> ColorRgb {
+  if y <= 0.0 {
+    return ColorRgb::BLACK;
+  }
+  let big_x = x / y * big_y;
+  let big_z = (1.0 - x - y) / y * big_y;
+  ColorRgb::new(
+    (3.2406 * big_x - 1.5372 * big_y - 0.4986 * big_z).max(0.0),
+    (-0.9689 * big_x + 1.8758 * big_y + 0.0415 * big_z).max(0.0),
+    (0.0557 * big_x - 0.2040 * big_y + 1.0570 * big_z).max(0.0),
+  )
+}
+
+// Irradiance of the sun at the ground, in klx, when it is `theta_s` from the zenith.
+// Outside of the atmosphere the sun gives about 128 klx of white light; on the way down,
+// Rayleigh and aerosol scattering take out the blue first (Preetham, appendix A.2), evaluated
+// at one wavelength for each of r, g and b.
+fn sun_irradiance(theta_s: Float, turbidity: Float) -> ColorRgb {
+  const EXTRATERRESTRIAL: Float = 128.0;
+  // relative optical mass of the air, Kasten and Young.
+  let degrees = theta_s.to_degrees().min(93.0);
+  let mass = 1.0 / (theta_s.cos().max(0.0) + 0.50572 * (96.07995 - degrees).powf(-1.6364));
+  let beta = 0.04608 * turbidity - 0.04586;
+  let transmittance = |lambda: Float| {
+    // lambda in micrometers.
+    let rayleigh = (-0.008735 * lambda.powf(-4.08) * mass).exp();
+    let aerosol = (-beta * lambda.powf(-1.3) * mass).exp();
+    rayleigh * aerosol
+  };
+  ColorRgb::new(transmittance(0.65), transmittance(0.55), transmittance(0.45)) * EXTRATERRESTRIAL
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_preetham_sky() {
+    let sky = PreethamSky::new((30.0 as Float).to_radians(), 0.0, 3.0, ColorRgb::new(0.3, 0.3, 0.3));
+    let up = sky.radiance(&Direction::new(0.0, 1.0, 0.0));
+    // a clear sky: a few kcd/m² and blue at the zenith, brighter around the sun.
+    assert!((1.0..10.0).contains(&up.luminance()), "{:?}", up);
+    assert!(up.b > up.g && up.g > up.r, "{:?}", up);
+    let near_sun = sky.radiance(&(sky.sun_direction() + Direction::new(0.0, 0.1, 0.0)).normalize());
+    assert!(near_sun.luminance() > up.luminance());
+    let ground = sky.radiance(&Direction::new(0.0, -1.0, 0.0));
+    assert!(ground.luminance() > 0.0 && ground.luminance() < up.luminance() * 10.0);
+
+    // the sun gives most of the light of a clear day, and is sampled as much.
+    let sun = sky.sun(light::SunLight::ANGULAR_RADIUS);
+    let sun_illuminance = sun.power() * sky.sun_direction().y;
+    let sky_illuminance = sky.horizontal_irradiance().luminance();
+    assert!(sun_illuminance > sky_illuminance, "{} {}", sun_illuminance, sky_illuminance);
+    let environment = sky.environment(128, 64, Some(light::SunLight::ANGULAR_RADIUS));
+    assert!(environment.radiance(&sky.sun_direction()).luminance() > 1e5);
+    let mut sampler = sampler::IndependentSampler::new(1);
+    let towards_sun = (0..1000)
+      .filter(|&index| {
+        sampler.start_pixel_sample((0, 0), index);
+        let (direction, _, pdf) = environment.sample(&mut sampler).unwrap();
+        assert!((pdf - environment.pdf(&direction)).abs() <= 1e-2 * pdf);
+        direction.dot(sky.sun_direction()) > 0.9999
+      })
+      .count();
+    assert!(towards_sun > 300, "{}", towards_sun);
+  }
+}
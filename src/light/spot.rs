@@ -0,0 +1,73 @@
+use crate::prelude::*;
+
+/// A point light shining in a cone: full within `falloff_start` of its axis, fading out
+/// smoothly until `cone_angle`, and dark beyond.
+pub struct SpotLight {
+  position: Point,
+  // unit axis of the cone.
+  direction: Direction,
+  intensity: ColorRgb,
+  cos_falloff_start: Float,
+  cos_cone: Float,
+}
+
+impl SpotLight {
+  /// `intensity` on the axis, angles in radians from the axis, `falloff_start` <= `cone_angle`.
+  pub fn new(
+    position: Point,
+    direction: Direction,
+    intensity: ColorRgb,
+    cone_angle: Float,
+    falloff_start: Float,
+  ) -> Self {
+    Self {
+      position,
+      direction: direction.normalize(),
+      intensity,
+      cos_falloff_start: falloff_start.min(cone_angle).cos(),
+      cos_cone: cone_angle.cos(),
+    }
+  }
+
+  // how much of the intensity goes along unit `direction`, away from the light.
+  fn falloff(&self, direction: Direction) -> Float {
+    let cos = direction.dot(self.direction);
+    if cos >= self.cos_falloff_start {
+      return 1.0;
+    }
+    if cos <= self.cos_cone {
+      return 0.0;
+    }
+    let t = (cos - self.cos_cone) / (self.cos_falloff_start - self.cos_cone);
+    t * t * (3.0 - 2.0 * t)
+  }
+}
+
+impl Light for SpotLight {
+  fn sample(&self, origin: &Point, _sampler: &mut dyn Sampler) -> Option<LightSample> {
+    let to_light = self.position - *origin;
+    let dist_sq = to_light.length_squared();
+    if dist_sq <= 0.0 {
+      return None;
+    }
+    let direction = to_light / dist_sq.sqrt();
+    let falloff = self.falloff(-direction);
+    if falloff <= 0.0 {
+      return None;
+    }
+    Some(LightSample {
+      point: Some(self.position),
+      direction,
+      radiance: self.intensity * (falloff / dist_sq),
+      pdf: 1.0,
+      is_delta: true,
+    })
+  }
+  fn bounds(&self) -> Option<light::LightBounds> {
+    // as much as a point light, so that neither is favored: only the directions differ.
+    let power = 4.0 * PI * self.intensity.luminance();
+    let cos_theta_e = (self.cos_cone.acos() - self.cos_falloff_start.acos()).cos();
+    let bounds = Aabb { min: self.position, max: self.position };
+    Some(light::LightBounds::new(bounds, power, self.direction, self.cos_falloff_start, cos_theta_e, false))
+  }
+}
@@ -0,0 +1,73 @@
+use crate::prelude::*;
+
+/// A disk of uniform radiance in the sky, infinitely far away, like the sun.
+/// Attached to an `EnvironmentLight` with `with_sun`, it is seen and sampled with it.
+#[derive(Clone, Copy, Debug)]
+pub struct SunLight {
+  /// Unit direction towards the center of the disk.
+  direction: Direction,
+  // 1 - cos and sin^2 of the angular radius, kept as such: 1 - cos is tiny for the sun, and
+  // cos itself too close to 1 for a Float to tell the rim of the disk.
+  one_minus_cos: Float,
+  sin2_max: Float,
+  radiance: ColorRgb,
+}
+
+impl SunLight {
+  /// The sun seen from the earth is 0.27° (0.0047 radians) in radius.
+  pub const ANGULAR_RADIUS: Float = 0.004_65;
+
+  /// `angular_radius` in radians.
+  pub fn new(direction: Direction, angular_radius: Float, radiance: ColorRgb) -> Self {
+    let half = (angular_radius / 2.0).sin();
+    let sin = angular_radius.sin();
+    Self { direction: direction.normalize(), one_minus_cos: 2.0 * half * half, sin2_max: sin * sin, radiance }
+  }
+
+  /// A disk of `angular_radius` giving the `irradiance` of the whole disk to a surface facing it,
+  /// so the disk may be made larger for softer shadows, without changing the light.
+  pub fn from_irradiance(direction: Direction, angular_radius: Float, irradiance: ColorRgb) -> Self {
+    let sun = Self::new(direction, angular_radius, ColorRgb::BLACK);
+    Self { radiance: irradiance / sun.solid_angle(), ..sun }
+  }
+
+  pub fn direction(&self) -> Direction {
+    self.direction
+  }
+
+  pub fn solid_angle(&self) -> Float {
+    2.0 * PI * self.one_minus_cos
+  }
+
+  /// Luminance times the solid angle, to share the samples with the rest of the sky.
+  pub fn power(&self) -> Float {
+    self.radiance.luminance() * self.solid_angle()
+  }
+
+  /// Radiance arriving from unit `direction`.
+  pub fn radiance(&self, direction: &Direction) -> ColorRgb {
+    if self.contains(direction) { self.radiance } else { ColorRgb::BLACK }
+  }
+
+  /// A unit direction in the disk, uniformly, its radiance and pdf (solid angle).
+  pub fn sample(&self, sampler: &mut dyn Sampler) -> (Direction, ColorRgb, Float) {
+    // uniform in the cone: 1 - cos(theta) is uniform in [0, 1 - cos_max].
+    let (u, v) = sampler.get_2d();
+    let one_minus_cos = u * self.one_minus_cos;
+    let sin_t = (one_minus_cos * (2.0 - one_minus_cos)).sqrt();
+    let (sin_p, cos_p) = (2.0 * PI * v).sin_cos();
+    let local = Direction::new(sin_t * cos_p, sin_t * sin_p, 1.0 - one_minus_cos);
+    let direction = Onb::from_w(self.direction).to_world(local).normalize();
+    (direction, self.radiance, 1.0 / self.solid_angle())
+  }
+
+  /// pdf of `sample` choosing unit `direction`.
+  pub fn pdf(&self, direction: &Direction) -> Float {
+    if self.contains(direction) { 1.0 / self.solid_angle() } else { 0.0 }
+  }
+
+  #[inline]
+  fn contains(&self, direction: &Direction) -> bool {
+    direction.dot(self.direction) > 0.0 && direction.cross(self.direction).length_squared() <= self.sin2_max
+  }
+}
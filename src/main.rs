@@ -1,145 +1,454 @@
-use rand::Rng;
+use clap::{Args, Parser, Subcommand};
 use raytracer_ramel::prelude::*;
+use raytracer_ramel::scene::{
+  CameraDesc, MaterialDesc, MaterialRef, ObjectDesc, SceneDescription, SceneError, TextureRef,
+};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
-fn main() {
-  let path = std::path::Path::new("output/image.png");
-  let prefix = path.parent().unwrap();
-  std::fs::create_dir_all(prefix).expect("Cannot create all the parents");
-
-  let aspect_ratio = 16.0 / 9.0;
-  let width = 400;
-  let height = (width as Float / aspect_ratio) as u32;
-
-  let film = film::SimpleFilm::new(width, height);
-
-  let mut filters = FilterList::new();
-  filters.add_filter(Arc::new(filter::ExposureFilter::new(-1.2)));
-  filters.add_filter(Arc::new(filter::AcesFilmicFilter));
-  filters.add_filter(Arc::new(filter::VignetteFilter::new(0.4)));
-  // filters.add_filter(Arc::new(filter::GammaFilter::new(2.2)));
-
-  let background_color = ColorRgb::lerp(ColorRgb::BLACK, ColorRgb::SKYBLUE, 0.2);
-  let mut world = World::new(move |_ray| background_color);
-
-  let mat_light = Arc::new(material::DiffusionLight::from_color(ColorRgb::lerp(
-    ColorRgb::YELLOW,
-    ColorRgb::WHITE,
-    0.8,
-  )));
-  let mat_mirror1 = Arc::new(material::Metal::new(ColorRgb::WHITE, 0.1));
-  let mat_mirror2 = Arc::new(material::Metal::new(ColorRgb::GREEN, 0.1));
-
-  let mat_error = Arc::new(material::DiffusionLight::new(Arc::new(
-    texture::UVCheckerTexture::new(
-      10.0,
-      10.0,
-      Arc::new(texture::SolidColorTexture::new(ColorRgb::MAGENTA)),
-      Arc::new(texture::SolidColorTexture::new(ColorRgb::SKYBLUE)),
-    ),
-  )));
-
-  let input_path = std::path::Path::new("input/girl-car-blue.jpg");
-  let tex_center = Arc::new(texture::ImageTexture::new(
-    input_path.to_str().unwrap(),
-    true,
-  ));
-  let mat_center = Arc::new(material::Lambertian::new(tex_center));
-
-  let unit_sphere_light = Arc::new(geometry::UnitSphere::new(mat_light.clone()));
-  let unit_sphere_m1 = Arc::new(geometry::UnitSphere::new(mat_mirror1.clone()));
-  let unit_sphere_m2 = Arc::new(geometry::UnitSphere::new(mat_mirror2.clone()));
-  let unit_sphere_center = Arc::new(geometry::UnitSphere::new(mat_center.clone()));
-  let unit_cube = Arc::new(geometry::UnitCube::from_one(mat_center.clone()));
-  let unit_quad_ground = Arc::new(geometry::UnitQuad::new(mat_error.clone()));
-
-  
-  world.add_object(Arc::new(geometry::Instance::new(
-    unit_sphere_light.clone(),
-    Mat4d::from_translation(Direction::new(3.0, 4.5, -1.0)) * Mat4d::from_scaling(4.0, 4.0, 4.0),
-  )));
-
-  world.add_object(geometry::Instance::new_arc(
-    unit_sphere_m1.clone(),
-    Mat4d::from_translation(Direction::new(-1.2, 0.0, -1.0)) * Mat4d::from_scaling(0.5, 0.5, 0.5),
-  ));
-
-  world.add_object(geometry::Instance::new_arc(
-    unit_sphere_m2.clone(),
-    Mat4d::from_translation(Direction::new(-0.6, 1.2, -1.0)) * Mat4d::from_scaling(0.5, 0.5, 0.5),
-  ));
-
-  world.add_object(geometry::Instance::new_arc(
-    unit_sphere_center.clone(),
-    Mat4d::from_translation(Direction::new(0.0, 0.0, -1.0))
-      * Mat4d::from_rotation_y(-PI / 2.0)
-      * Mat4d::from_scaling(0.5, 0.5, 0.5),
-  ));
-
-  world.add_object(geometry::Instance::new_arc(
-    unit_cube.clone(),
-    Mat4d::from_translation(Direction::new(1.1, 0.0, 0.0))
-      * Mat4d::from_rotation_y(PI / 4.0)
-      * Mat4d::from_rotation_x(PI / 4.0)
-      * Mat4d::from_scaling(0.7, 0.7, 0.7),
-  ));
-
-  world.add_object(geometry::Instance::new_arc(
-    unit_quad_ground.clone(),
-    Mat4d::from_translation(Direction::new(0.0, -1.0, 0.0))
-      * Mat4d::from_rotation_x(-PI / 6.0)
-      * Mat4d::from_scaling(10.0, 1.0, 10.0),
-  ));
-
-  let mut rng = rand::rng();
-  for _ in 0..500 {
-    let x = rng.random_range(-3.0..3.0);
-    let y = rng.random_range(-0.5..0.5);
-    let z = rng.random_range(-20.0..-5.0);
-    let r = rng.random_range(0.0..1.0);
-    let g = rng.random_range(0.0..1.0);
-    let b = rng.random_range(0.0..1.0);
-    let radius = rng.random_range(0.0..0.1);
-    world.add_object(geometry::Instance::new_arc(
-      Arc::new(geometry::UnitSphere::new(Arc::new(
-        material::Lambertian::new(Arc::new(texture::SolidColorTexture::new(ColorRgb::new(
-          r, g, b,
-        )))),
-      ))),
-      Mat4d::from_translation(Direction::new(x, y, z))
-        * Mat4d::from_scaling(radius, radius, radius),
-    ));
-  }
-
-  world.bvh_finalize();
-
-  let camera = camera::OrthographicCamera::new(
-    Point::new(0.0, 0.0, 10.0),
-    Point::new(0.0, 0.0, -10.0),
-    Direction::new(0.0, 1.0, 0.0),
-    2.0,
-    aspect_ratio,
-  );
+// exit codes. clap exits with 2 on bad arguments by itself.
+const EXIT_OUTPUT: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+const EXIT_SCENE: u8 = 3;
 
-  let renderer = renderer::SimpleRenderer::new(200, 200);
+const DEFAULT_OUTPUT: &str = "output/image.png";
 
-  let camera = Arc::new(camera);
-  let film = Arc::new(Mutex::new(film));
-  let filters = Arc::new(filters);
-  let world = Arc::new(world);
+#[derive(Parser)]
+#[command(name = "raytracer-ramel", version, about = "Render scene files with a path tracer.")]
+#[command(after_help = "Exit status:\n  \
+  0  success\n  \
+  1  the image cannot be written\n  \
+  2  bad command line\n  \
+  3  the scene cannot be read, parsed or built")]
+struct Cli {
+  #[command(subcommand)]
+  command: Command,
+}
 
-  let render_start = std::time::Instant::now();
+#[derive(Subcommand)]
+enum Command {
+  /// Render a scene file to an image.
+  Render(RenderArgs),
+  /// Print what a scene is made of.
+  Info {
+    /// The scene file (TOML).
+    scene: PathBuf,
+  },
+  /// List the materials defined in a scene.
+  ListMaterials {
+    /// The scene file (TOML).
+    scene: PathBuf,
+  },
+}
+
+#[derive(Args)]
+struct RenderArgs {
+  /// The scene file (TOML).
+  scene: PathBuf,
+  /// Where to save the image, the format follows the extension.
+  /// .exr, .hdr and .pfm keep the linear radiance, without the filters of the scene.
+  /// Defaults to `film.output` of the scene, then `output/image.png`.
+  #[arg(short, long)]
+  output: Option<PathBuf>,
+  /// Write 16-bit instead of 32-bit floats to an .exr output.
+  #[arg(long)]
+  half: bool,
+  /// AOV layers to render besides `film.aovs` of the scene: albedo, normal, depth, position,
+  /// uv, material_id, object_id, samples. They go into an .exr output as extra channels,
+  /// otherwise each is saved next to the image as <name>.<layer>.<extension>.
+  #[arg(long, value_delimiter = ',', value_parser = parse_aov)]
+  aov: Vec<film::Aov>,
+  /// Samples per pixel.
+  #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+  spp: Option<u32>,
+  /// Where the random numbers come from: independent, stratified, halton or sobol.
+  #[arg(long, value_parser = parse_sampler)]
+  sampler: Option<sampler::SamplerKind>,
+  /// How samples are weighted into the pixels: box, tent, gaussian, mitchell or lanczos,
+  /// with their usual radius, instead of `renderer.pixel_filter` of the scene.
+  #[arg(long, value_parser = parse_pixel_filter)]
+  pixel_filter: Option<PixelFilter>,
+  /// Side of the square tiles the film is rendered in, in pixels.
+  #[arg(long, default_value_t = 32, value_parser = clap::value_parser!(u32).range(1..))]
+  tile_size: u32,
+  /// The order tiles are rendered in: scanline, spiral or hilbert.
+  #[arg(long, default_value = "spiral", value_parser = parse_tile_order)]
+  tile_order: film::TileOrder,
+  /// Max count of surfaces a path may hit.
+  #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+  depth: Option<u32>,
+  /// Film width in pixels. The height keeps the aspect ratio of the scene.
+  #[arg(long, value_parser = clap::value_parser!(u32).range(2..))]
+  width: Option<u32>,
+  /// Render progressively for about this many seconds (or until the samples per pixel are
+  /// taken), saving the image after every pass.
+  #[arg(long, value_parser = parse_seconds)]
+  time: Option<std::time::Duration>,
+  /// Stop sampling a pixel once the standard error of its luminance, relative to the mean,
+  /// is below this (like 0.01). `--spp` is then the max samples per pixel.
+  #[arg(long)]
+  adaptive: Option<Float>,
+  /// Samples every pixel takes before adaptive sampling may stop it.
+  #[arg(long, requires = "adaptive", default_value_t = 16, value_parser = clap::value_parser!(u32).range(1..))]
+  min_spp: u32,
+  /// Save a false color map of the samples each pixel took (also added as the samples layer).
+  #[arg(long)]
+  heatmap: Option<PathBuf>,
+  /// Worker threads. All the cores by default.
+  #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+  threads: Option<u32>,
+  /// Seed of the random numbers, instead of `renderer.seed` of the scene.
+  /// The same seed renders the same image.
+  #[arg(long)]
+  seed: Option<u64>,
+}
+
+enum CliError {
+  Scene(SceneError),
+  Usage(String),
+  Output { path: PathBuf, message: String },
+}
+
+impl CliError {
+  fn exit_code(&self) -> ExitCode {
+    ExitCode::from(match self {
+      CliError::Scene(_) => EXIT_SCENE,
+      CliError::Usage(_) => EXIT_USAGE,
+      CliError::Output { .. } => EXIT_OUTPUT,
+    })
+  }
+}
+
+impl std::fmt::Display for CliError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      CliError::Scene(e) => write!(f, "{}", e),
+      CliError::Usage(message) => write!(f, "{}", message),
+      CliError::Output { path, message } => write!(f, "cannot write {}: {}", path.display(), message),
+    }
+  }
+}
+
+impl From<SceneError> for CliError {
+  fn from(e: SceneError) -> Self {
+    CliError::Scene(e)
+  }
+}
+
+fn parse_aov(name: &str) -> Result<film::Aov, String> {
+  film::Aov::from_name(name).ok_or_else(|| {
+    let names: Vec<&str> = film::Aov::ALL.iter().map(|aov| aov.name()).collect();
+    format!("expected one of {}", names.join(", "))
+  })
+}
 
-  renderer.render(RenderConfig {
-    camera,
-    film: film.clone(),
-    filters,
-    world,
-  });
+fn parse_sampler(name: &str) -> Result<sampler::SamplerKind, String> {
+  sampler::SamplerKind::from_name(name).ok_or_else(|| {
+    let names: Vec<&str> = sampler::SamplerKind::ALL.iter().map(|kind| kind.name()).collect();
+    format!("expected one of {}", names.join(", "))
+  })
+}
+
+fn parse_pixel_filter(name: &str) -> Result<PixelFilter, String> {
+  PixelFilter::from_name(name).ok_or_else(|| format!("expected one of {}", PixelFilter::NAMES.join(", ")))
+}
+
+fn parse_tile_order(name: &str) -> Result<film::TileOrder, String> {
+  film::TileOrder::from_name(name).ok_or_else(|| {
+    let names: Vec<&str> = film::TileOrder::ALL.iter().map(|order| order.name()).collect();
+    format!("expected one of {}", names.join(", "))
+  })
+}
+
+fn parse_seconds(s: &str) -> Result<std::time::Duration, String> {
+  let seconds: f64 = s.parse().map_err(|e| format!("{}", e))?;
+  std::time::Duration::try_from_secs_f64(seconds)
+    .ok()
+    .filter(|d| !d.is_zero())
+    .ok_or_else(|| "expected a positive number of seconds".to_string())
+}
+
+fn main() -> ExitCode {
+  let cli = Cli::parse();
+  let result = match cli.command {
+    Command::Render(args) => render(args),
+    Command::Info { scene } => info(&scene),
+    Command::ListMaterials { scene } => list_materials(&scene),
+  };
+  match result {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(e) => {
+      eprintln!("{} {}", console::style("error:").red().bold(), e);
+      e.exit_code()
+    }
+  }
+}
+
+// (source, description)
+fn read_scene(path: &Path) -> Result<(String, SceneDescription), SceneError> {
+  let source = std::fs::read_to_string(path)
+    .map_err(|e| SceneError::Io { path: path.to_path_buf(), source: e })?;
+  let description = SceneDescription::parse(&source, path)?;
+  Ok((source, description))
+}
+
+fn render(args: RenderArgs) -> Result<(), CliError> {
+  let (source, mut desc) = read_scene(&args.scene)?;
+  if let Some(spp) = args.spp {
+    desc.renderer.samples_per_pixel = spp;
+  }
+  if let Some(depth) = args.depth {
+    desc.renderer.max_depth = depth;
+  }
+  if let Some(kind) = args.sampler {
+    // not from the file, so with no span, but never an error either.
+    desc.renderer.sampler = Some(toml::Spanned::new(0..0, kind.name().to_string()));
+  }
+  if let Some(width) = args.width {
+    // an explicit height is scaled along, otherwise it follows `aspect_ratio` anyway.
+    let film = desc.film.get_mut();
+    if let Some(height) = film.height {
+      let height = (height as f64 * width as f64 / film.width as f64).round() as u32;
+      film.height = Some(height.max(2));
+    }
+    film.width = width;
+  }
+  if let Some(seed) = args.seed {
+    desc.renderer.seed = seed;
+  }
 
+  // fail before rendering rather than after.
+  let output = args.output.clone();
+  let check_format = |path: &Path| match film::OutputFormat::from_path(path) {
+    Some(film::OutputFormat::Exr(_)) if args.half => {
+      Ok(film::OutputFormat::Exr(film::ExrPrecision::Half))
+    }
+    Some(_) if args.half => Err(CliError::Usage(format!("--half needs an .exr output, not {}", path.display()))),
+    Some(format) => Ok(format),
+    None => Err(CliError::Usage(format!("unsupported image format: {}", path.display()))),
+  };
+  if let Some(ref path) = output {
+    check_format(path)?;
+  }
+
+  let mut scene = desc.build(&source, &args.scene)?;
+  if let Some(filter) = args.pixel_filter {
+    scene.renderer = scene.renderer.with_pixel_filter(filter);
+  }
+  scene.renderer = scene.renderer.with_tile_size(args.tile_size).with_tile_order(args.tile_order);
+  let path = output.or(scene.output.clone()).unwrap_or_else(|| PathBuf::from(DEFAULT_OUTPUT));
+  let format = check_format(&path)?;
+  // tone mapping and the like are left to whoever reads the HDR image.
+  if format.is_hdr() && !scene.config.filters.filters().is_empty() {
+    println!("Filters of the scene are skipped for the HDR output.");
+    scene.config.filters = Arc::new(FilterList::new());
+  }
+  if args.adaptive.is_some_and(|threshold| threshold <= 0.0) {
+    return Err(CliError::Usage("--adaptive needs a positive threshold".to_string()));
+  }
+  let mut aovs = args.aov.clone();
+  if args.heatmap.is_some() {
+    aovs.push(film::Aov::SampleCount);
+  }
+  if !aovs.is_empty() {
+    let film = {
+      let film = scene.config.film.lock().unwrap();
+      let mut layers = film.layers().to_vec();
+      layers.extend(&aovs);
+      film::SimpleFilm::new(film.width(), film.height()).with_layers(&layers)
+    };
+    scene.config.film = Arc::new(Mutex::new(film));
+  }
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)
+      .map_err(|e| CliError::Output { path: path.clone(), message: e.to_string() })?;
+  }
+
+  // 0 lets rayon pick one thread per core.
+  let threads = args.threads.unwrap_or(0) as usize;
+  let pool = rayon::ThreadPoolBuilder::new()
+    .num_threads(threads)
+    .build()
+    .map_err(|e| CliError::Usage(format!("cannot start the worker threads: {}", e)))?;
+
+  let film = scene.config.film.clone();
+  let render_start = std::time::Instant::now();
+  if args.time.is_some() || args.adaptive.is_some() {
+    let mut renderer = scene.renderer.into_progressive();
+    if let Some(threshold) = args.adaptive {
+      let min_samples = args.min_spp.min(desc.renderer.samples_per_pixel);
+      renderer = renderer.with_adaptive(renderer::AdaptiveSampling { min_samples, threshold });
+    }
+    if let Some(budget) = args.time {
+      let preview = path.clone();
+      renderer = renderer.with_time_budget(budget).on_pass(move |info, film| {
+        if let Err(e) = format.save(film, &preview) {
+          eprintln!("Warning: cannot save pass {} to {}: {}", info.pass, preview.display(), e);
+        }
+      });
+    }
+    pool.install(|| renderer.render(scene.config));
+  } else {
+    pool.install(|| scene.renderer.render(scene.config));
+  }
   let render_elapsed = render_start.elapsed();
 
-  let image = film.lock().unwrap().to_image_srgb();
-  image.save(path).expect("Cannot save the image to file.");
-  println!("Finished. .Image saved to {}", console::style("output.png").green().bright());
+  let film = film.lock().unwrap();
+  format
+    .save(&*film, &path)
+    .map_err(|e| CliError::Output { path: path.clone(), message: e.to_string() })?;
+  println!("Finished. Image saved to {}", console::style(path.display()).green().bright());
+  if !matches!(format, film::OutputFormat::Exr(_)) {
+    for aov in film.layers() {
+      // image.png -> image.albedo.png
+      let mut layer_path = path.with_extension(aov.name());
+      if let Some(extension) = path.extension() {
+        layer_path.as_mut_os_string().push(".");
+        layer_path.as_mut_os_string().push(extension);
+      }
+      format
+        .save_layer(&*film, *aov, &layer_path)
+        .map_err(|e| CliError::Output { path: layer_path.clone(), message: e.to_string() })?;
+      println!("Layer {} saved to {}", aov, console::style(layer_path.display()).green().bright());
+    }
+  }
+  if let Some(ref heatmap_path) = args.heatmap {
+    let heatmap = film::samples_heatmap(&*film).expect("the film has the samples layer");
+    heatmap
+      .save(heatmap_path)
+      .map_err(|e| CliError::Output { path: heatmap_path.clone(), message: e.to_string() })?;
+    println!("Heatmap saved to {}", console::style(heatmap_path.display()).green().bright());
+  }
   println!("Elapsed time: {}ms", render_elapsed.as_millis());
+  Ok(())
+}
+
+fn info(path: &Path) -> Result<(), CliError> {
+  let (source, desc) = read_scene(path)?;
+  let scene = desc.build(&source, path)?;
+  let stats = scene.stats;
+  let (width, height) = {
+    let film = scene.config.film.lock().unwrap();
+    (film.width(), film.height())
+  };
+  let camera = match desc.camera.get_ref() {
+    CameraDesc::Perspective { vfov, aperture, .. } if *aperture > 0.0 => {
+      format!("perspective, vfov {}°, aperture {}", vfov, aperture)
+    }
+    CameraDesc::Perspective { vfov, .. } => format!("perspective, vfov {}°", vfov),
+    CameraDesc::Orthographic { height, .. } => format!("orthographic, height {}", height),
+  };
+  let bounds = scene.config.world.objects().bounding_box();
+
+  println!("scene      {}", path.display());
+  println!("film       {}x{}", width, height);
+  let aovs: Vec<&str> = desc.film.get_ref().aovs.iter().map(|aov| aov.get_ref().as_str()).collect();
+  if !aovs.is_empty() {
+    println!("aovs       {}", aovs.join(", "));
+  }
+  println!("camera     {}", camera);
+  println!(
+    "renderer   {} spp, max depth {}, {} sampler, seed {}, {} pixel filter",
+    desc.renderer.samples_per_pixel,
+    desc.renderer.max_depth,
+    desc.renderer.sampler.as_ref().map_or(sampler::SamplerKind::default().name(), |name| name.get_ref()),
+    desc.renderer.seed,
+    desc.renderer.pixel_filter.as_ref().map_or(PixelFilter::default().name(), |filter| filter.get_ref().name())
+  );
+  println!(
+    "objects    {} primitives: {} spheres, {} quads, {} cubes, {} triangles in {} meshes",
+    stats.primitives(),
+    stats.spheres,
+    stats.quads,
+    stats.cubes,
+    stats.triangles,
+    stats.meshes
+  );
+  let environment = if scene.config.world.environment().is_some() { " and an environment map" } else { "" };
+  println!("lights     {} objects, {} light sources{}", stats.lights, stats.light_sources, environment);
+  if stats.media > 0 || desc.fog.is_some() {
+    let fog = desc.fog.as_ref().map_or(String::new(), |fog| format!(", fog of density {}", fog.get_ref().density));
+    println!("media      {} objects{}", stats.media, fog);
+  }
+  if let Some(bvh) = scene.config.world.objects().bvh_stats() {
+    println!("bvh        {}", bvh);
+  }
+  println!("materials  {}", desc.materials.len());
+  println!("textures   {}", desc.textures.len());
+  println!("filters    {}", desc.filters.len());
+  if stats.primitives() > 0 {
+    println!(
+      "bounds     ({:.3}, {:.3}, {:.3}) - ({:.3}, {:.3}, {:.3})",
+      bounds.min.x, bounds.min.y, bounds.min.z, bounds.max.x, bounds.max.y, bounds.max.z
+    );
+  }
+  match scene.output {
+    Some(output) => println!("output     {}", output.display()),
+    None => println!("output     (none, {} by default)", DEFAULT_OUTPUT),
+  }
+  Ok(())
+}
+
+fn list_materials(path: &Path) -> Result<(), CliError> {
+  let (_, desc) = read_scene(path)?;
+
+  let mut uses = std::collections::HashMap::<&str, usize>::new();
+  let mut mtl_meshes = Vec::new();
+  for object in &desc.objects {
+    let names: Vec<&str> = match object.get_ref() {
+      ObjectDesc::Sphere { material, .. } | ObjectDesc::Quad { material, .. } => vec![material],
+      ObjectDesc::Cube { material: MaterialRef::One(name), .. } => vec![name],
+      ObjectDesc::Cube { material: MaterialRef::Faces(names), .. } => {
+        // one use of each material, whatever faces it is on.
+        let mut names: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+      }
+      ObjectDesc::Mesh { material: Some(name), .. } => vec![name],
+      ObjectDesc::Mesh { path, material: None, .. } => {
+        mtl_meshes.push(path.as_str());
+        vec![]
+      }
+      // media have no material of their own.
+      ObjectDesc::Medium { .. } | ObjectDesc::Volume { .. } => vec![],
+    };
+    for name in names {
+      *uses.entry(name).or_default() += 1;
+    }
+  }
+
+  let name_width = desc.materials.keys().map(|name| name.len()).max().unwrap_or(0);
+  for (name, material) in &desc.materials {
+    let (kind, params) = match material.get_ref() {
+      MaterialDesc::Lambertian { albedo } => ("lambertian", format!("albedo {}", texture_ref(albedo))),
+      MaterialDesc::Metal { albedo, fuzz } => {
+        ("metal", format!("albedo {}, fuzz {}", color(albedo), fuzz))
+      }
+      MaterialDesc::Dielectric { ir, albedo } => {
+        ("dielectric", format!("ir {}, albedo {}", ir, texture_ref(albedo)))
+      }
+      MaterialDesc::DiffusionLight { emit } => ("diffusion_light", format!("emit {}", texture_ref(emit))),
+    };
+    let used = match uses.get(name.as_str()) {
+      Some(count) => format!("{} uses", count),
+      None => "unused".to_string(),
+    };
+    println!("{:name_width$}  {:15}  {}  ({})", name, kind, params, used);
+  }
+  for path in mtl_meshes {
+    println!("{}: materials from its .mtl file", path);
+  }
+  Ok(())
+}
+
+fn color(c: &[Float; 3]) -> String {
+  format!("[{}, {}, {}]", c[0], c[1], c[2])
+}
+
+fn texture_ref(tex_ref: &TextureRef) -> String {
+  match tex_ref {
+    TextureRef::Color(c) => color(c),
+    TextureRef::Name(name) => format!("texture '{}'", name),
+  }
 }
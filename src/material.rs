@@ -1,22 +1,275 @@
 use crate::prelude::*;
 
+/// A direction sampled from a BSDF, in the local shading frame.
+pub struct BsdfSample {
+  pub wi: Direction,
+  // f(wo, wi). For delta lobes, this already includes the 1 / |cos(wi)| factor,
+  // so `f * |cos(wi)| / pdf` is the path weight in all cases (`f / pdf` in a medium,
+  // see `cos_factor`).
+  pub f: ColorRgb,
+  // pdf with respect to solid angle. For delta lobes, the probability of choosing this lobe.
+  pub pdf: Float,
+  pub is_delta: bool,
+}
+
+/// Materials work in the local shading frame `Onb::from_w(record.unit_normal)`:
+/// +z is the outward normal, and `wo`, `wi` are unit vectors pointing away from the surface.
+/// `wo` is towards the viewer (where the light goes), `wi` is towards the light source.
 pub trait Material: Send + Sync {
-  fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(ColorRgb, Ray)>;
+  /// f(wo, wi). Delta lobes are never included.
+  fn eval(&self, _record: &HitRecord, _wo: Direction, _wi: Direction) -> ColorRgb {
+    ColorRgb::BLACK
+  }
+  /// Sample `wi` given `wo`. `None` if the material absorbs everything.
+  fn sample(&self, _record: &HitRecord, _wo: Direction, _sampler: &mut dyn Sampler) -> Option<BsdfSample> {
+    None
+  }
+  /// pdf (solid angle) of `sample` choosing `wi`. 0 for delta lobes.
+  fn pdf(&self, _record: &HitRecord, _wo: Direction, _wi: Direction) -> Float {
+    0.0
+  }
+  /// Whether all the lobes are delta (perfectly specular).
+  /// `eval` and `pdf` are always 0 for such materials, so explicit light sampling is skipped.
+  fn is_delta(&self) -> bool {
+    false
+  }
+  /// Scatters inside a participating medium rather than off a surface: `f` is the phase
+  /// function, with no cosine to multiply it by, and the normal of the record means nothing.
+  fn is_volumetric(&self) -> bool {
+    false
+  }
+
   fn emitted(&self, _uv: UV, _p: Point) -> ColorRgb {
     ColorRgb::BLACK
   }
+
+  /// The color of the surface regardless of the lighting, for the albedo AOV.
+  fn albedo(&self, _record: &HitRecord) -> ColorRgb {
+    ColorRgb::BLACK
+  }
+
+  /// Adapter for the old interface: returns (attenuation, scattered) in world space,
+  /// where attenuation = f * |cos| / pdf.
+  fn scatter(&self, ray_in: &Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(ColorRgb, Ray)> {
+    let frame = Onb::from_w(record.unit_normal);
+    let wo = frame.to_local(-ray_in.direction.normalize());
+    let sample = self.sample(record, wo, sampler)?;
+    if sample.pdf <= 0.0 {
+      return None;
+    }
+    let attenuation = sample.f * (cos_factor(self, sample.wi) / sample.pdf);
+    Some((attenuation, record.spawn_ray(frame.to_world(sample.wi))))
+  }
+}
+
+/// What `f` of `material` is multiplied by towards local `wi` in the path weight:
+/// |cos(wi)| off a surface, 1 in a medium.
+#[inline]
+pub fn cos_factor<M: Material + ?Sized>(material: &M, wi: Direction) -> Float {
+  if material.is_volumetric() { 1.0 } else { wi.z.abs() }
 }
 
-pub fn convert_material(_t_mat: tobj::Material) -> Arc<dyn Material> {
-  unimplemented!()
+/// Whether two local directions are on the same side of the surface.
+#[inline]
+pub fn same_hemisphere(a: Direction, b: Direction) -> bool {
+  a.z * b.z > 0.0
+}
+
+/// Convert a `.mtl` material into the closest material we support.
+/// Texture paths are resolved relative to `base_dir` (the directory of the `.mtl` file).
+/// Bump maps (`map_Bump` / `bump`) are not supported: they are ignored with a warning.
+///
+/// - Emissive (`Ke` / `map_Ke`)                      -> DiffusionLight
+/// - Transparent (`d` < 1, or `illum` 4, 6, 7, 9)   -> Dielectric with `Ni`
+/// - Reflective (`illum` 3, 5, or `Ks` over `Kd`)    -> Metal, fuzz derived from `Ns`
+/// - Otherwise                                       -> Lambertian with `Kd` / `map_Kd`
+pub fn convert_material(t_mat: &tobj::Material, base_dir: &std::path::Path) -> Arc<dyn Material> {
+  let to_color = |c: [f32; 3]| ColorRgb::new(c[0] as Float, c[1] as Float, c[2] as Float);
+  let load_texture = |raw: &str, fallback: ColorRgb| -> Arc<dyn Texture> {
+    let path = resolve_texture_path(base_dir, raw);
+    match texture::ImageTexture::load(&path, true) {
+      Ok(tex) => Arc::new(tex),
+      Err(e) => {
+        eprintln!(
+          "Warning: material '{}': cannot load texture {}: {}",
+          t_mat.name,
+          path.display(),
+          e
+        );
+        texture::SolidColorTexture::new_arc(fallback)
+      }
+    }
+  };
+
+  let illum = t_mat.illumination_model.unwrap_or(2);
+  let diffuse = t_mat.diffuse.map(to_color).unwrap_or(ColorRgb::new(0.8, 0.8, 0.8));
+  let specular = t_mat.specular.map(to_color).unwrap_or(ColorRgb::BLACK);
+
+  if let Some(ref raw) = t_mat.normal_texture {
+    let path = resolve_texture_path(base_dir, raw);
+    eprintln!(
+      "Warning: material '{}': bump map {} is not supported yet and is ignored.",
+      t_mat.name,
+      path.display()
+    );
+  }
+
+  let emission = t_mat.unknown_param.get("Ke").and_then(|ke| parse_color(ke));
+  let emission_map = t_mat.unknown_param.get("map_Ke");
+  if let Some(raw) = emission_map {
+    return Arc::new(DiffusionLight::new(load_texture(raw, emission.unwrap_or(ColorRgb::WHITE))));
+  }
+  if let Some(ke) = emission
+    && ke.r.max(ke.g).max(ke.b) > COLOR_EPSILON
+  {
+    return Arc::new(DiffusionLight::from_color(ke));
+  }
+
+  let dissolve = t_mat.dissolve.unwrap_or(1.0) as Float;
+  if dissolve < 1.0 || matches!(illum, 4 | 6 | 7 | 9) {
+    let ir = t_mat.optical_density.map(|ni| ni as Float).unwrap_or(1.5);
+    let tint = t_mat.unknown_param.get("Tf").and_then(|tf| parse_color(tf)).unwrap_or(ColorRgb::WHITE);
+    return Dielectric::new_arc(ir, texture::SolidColorTexture::new_arc(tint));
+  }
+
+  let max_of = |c: ColorRgb| c.r.max(c.g).max(c.b);
+  if matches!(illum, 3 | 5) || max_of(specular) > max_of(diffuse) {
+    // Blinn-Phong exponent to roughness: alpha = sqrt(2 / (Ns + 2)).
+    let shininess = t_mat.shininess.unwrap_or(0.0).max(0.0) as Float;
+    let fuzz = (2.0 / (shininess + 2.0)).sqrt().clamp(0.0, 1.0);
+    return Metal::new_arc(specular, fuzz);
+  }
+
+  let albedo = match t_mat.diffuse_texture {
+    Some(ref raw) => load_texture(raw, diffuse),
+    None => texture::SolidColorTexture::new_arc(diffuse),
+  };
+  Lambertian::new_arc(albedo)
+}
+
+/// `.mtl` texture statements may carry options before the file name, like `map_Kd -bm 0.5 wood.png`.
+/// We only keep the file name, and join it to `base_dir` if it is relative.
+fn resolve_texture_path(base_dir: &std::path::Path, raw: &str) -> std::path::PathBuf {
+  let raw = raw.trim();
+  let name = if raw.starts_with('-') {
+    raw.split_whitespace().last().unwrap_or(raw)
+  } else {
+    raw
+  };
+  // some exporters write windows separators.
+  let name = name.replace('\\', "/");
+  base_dir.join(name)
+}
+
+fn parse_color(s: &str) -> Option<ColorRgb> {
+  let values: Vec<Float> = s.split_whitespace().filter_map(|v| v.parse().ok()).collect();
+  match values.as_slice() {
+    [r, g, b, ..] => Some(ColorRgb::new(*r, *g, *b)),
+    [v] => Some(ColorRgb::new(*v, *v, *v)),
+    _ => None,
+  }
 }
 
 mod lambertian;
 mod diffusion_light;
 mod metal;
 mod dielectric;
+mod volumetric;
 
 pub use lambertian::Lambertian;
 pub use diffusion_light::DiffusionLight;
 pub use metal::Metal;
-pub use dielectric::Dielectric;
\ No newline at end of file
+pub use dielectric::Dielectric;
+pub use volumetric::Volumetric;
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_bsdf_consistency() {
+    let gray = ColorRgb::new(0.5, 0.5, 0.5);
+    let ray = Ray::new(Point::new(0.0, 0.0, 1.0), Direction::new(0.0, 0.0, -1.0));
+    let record_of = |mat: Arc<dyn Material>| {
+      HitRecord::from_ray(&ray, Direction::new(0.0, 0.0, 1.0), 1.0, mat, UV::new(0.5, 0.5))
+    };
+    let mut sampler = sampler::IndependentSampler::new(0);
+    let wos: Vec<Direction> = (0..32).map(|_| sampler::sample_uniform_sphere(sampler.get_2d())).collect();
+
+    // the pdf and f of a sample are those `pdf` and `eval` give for its direction.
+    let lambertian: Arc<dyn Material> = Lambertian::new_arc(texture::SolidColorTexture::new_arc(gray));
+    let fog: Arc<dyn Material> = Volumetric::new_arc(gray, medium::PhaseFunction::Isotropic);
+    let haze: Arc<dyn Material> = Volumetric::new_arc(gray, medium::PhaseFunction::HenyeyGreenstein(0.7));
+    for mat in [&lambertian, &fog, &haze] {
+      let record = record_of(mat.clone());
+      assert!(!mat.is_delta());
+      for &wo in &wos {
+        for _ in 0..32 {
+          let sample = mat.sample(&record, wo, &mut sampler).unwrap();
+          let (f, pdf) = (mat.eval(&record, wo, sample.wi), mat.pdf(&record, wo, sample.wi));
+          assert!(!sample.is_delta);
+          assert!((sample.pdf - pdf).abs() < 1e-3 * pdf, "{} {}", sample.pdf, pdf);
+          assert!((sample.f.g - f.g).abs() < 1e-3 * f.g, "{:?} {:?}", sample.f, f);
+        }
+      }
+    }
+
+    // the pdf of the sampling adds up to 1 over the sphere, and the reflected light estimated by
+    // sampling is what `eval` integrates to: the albedo.
+    let record = record_of(lambertian.clone());
+    let wo = Direction::new(0.6, 0.0, 0.8);
+    let count = 20000;
+    let (mut total_pdf, mut by_eval, mut by_sampling) = (0.0, 0.0, 0.0);
+    for _ in 0..count {
+      let wi = sampler::sample_uniform_sphere(sampler.get_2d());
+      total_pdf += lambertian.pdf(&record, wo, wi) * 4.0 * PI;
+      by_eval += lambertian.eval(&record, wo, wi).g * wi.z.abs() * 4.0 * PI;
+      let sample = lambertian.sample(&record, wo, &mut sampler).unwrap();
+      by_sampling += sample.f.g * sample.wi.z.abs() / sample.pdf;
+    }
+    let count = count as Float;
+    assert!((total_pdf / count - 1.0).abs() < 0.02, "{}", total_pdf / count);
+    assert!((by_eval / count - 0.5).abs() < 0.01, "{}", by_eval / count);
+    assert!((by_sampling / count - 0.5).abs() < 1e-4, "{}", by_sampling / count);
+
+    // metal, fuzzy or a mirror, and glass are delta lobes: a sample has a lobe probability for
+    // pdf, and `eval` and `pdf` are 0 even for the sampled direction, which MIS never sees.
+    let fuzzy: Arc<dyn Material> = Metal::new_arc(gray, 0.3);
+    let mirror: Arc<dyn Material> = Metal::new_arc(gray, 0.0);
+    let glass: Arc<dyn Material> = Dielectric::new_arc(1.5, texture::SolidColorTexture::new_arc(ColorRgb::WHITE));
+    for mat in [&fuzzy, &mirror, &glass] {
+      let record = record_of(mat.clone());
+      assert!(mat.is_delta());
+      for &wo in &wos {
+        let Some(sample) = mat.sample(&record, wo, &mut sampler) else {
+          continue;
+        };
+        assert!(sample.is_delta && sample.pdf > 0.0 && sample.pdf <= 1.0);
+        assert_eq!(mat.pdf(&record, wo, sample.wi), 0.0);
+        assert_eq!(mat.eval(&record, wo, sample.wi).g, 0.0);
+      }
+    }
+    let wo = Direction::new(0.6, 0.0, 0.8);
+    let sample = mirror.sample(&record_of(mirror.clone()), wo, &mut sampler).unwrap();
+    assert!((sample.wi - Direction::new(-0.6, 0.0, 0.8)).length() < 1e-6);
+    assert!((sample.f.g * sample.wi.z / sample.pdf - 0.5).abs() < 1e-6);
+
+    // a light seen in a mirror keeps its whole radiance: neither sampled at the mirror, nor
+    // weighted down by MIS when the mirror ray hits it.
+    let mut world = World::new(|_ray| ColorRgb::BLACK);
+    world.add_object(geometry::Instance::new_arc(
+      Arc::new(geometry::UnitQuad::new(mirror.clone())),
+      Mat4d::from_rotation_x(-PI / 2.0) * Mat4d::from_scaling(4.0, 4.0, 1.0),
+    ));
+    world.add_light(geometry::Instance::new_arc(
+      Arc::new(geometry::UnitQuad::new(DiffusionLight::arc_from_color(ColorRgb::new(8.0, 8.0, 8.0)))),
+      Mat4d::from_translation(Direction::new(0.0, 2.0, 0.0)) * Mat4d::from_rotation_x(PI / 2.0),
+    ));
+    world.bvh_finalize();
+    let ray = Ray::new(Point::new(0.0, 1.0, 0.0), Direction::new(0.0, -1.0, 0.0));
+    for index in 0..16 {
+      sampler.start_pixel_sample((0, 0), index);
+      let color = renderer::SimpleRenderer::ray_color(&ray, &world, 3, &mut sampler);
+      assert!((color.g - 4.0).abs() < 1e-4, "{:?}", color);
+    }
+  }
+}
@@ -1,6 +1,136 @@
 use crate::prelude::*;
 
+/// Clear material like glass or water.
+/// `ir` is the index of refraction inside the object (outside is treated as vacuum, ir = 1.0).
+/// `albedo` tints the transmitted light only; reflection keeps the original color.
 pub struct Dielectric {
   pub ir: Float,
   pub albedo: Arc<dyn Texture>,
-}
\ No newline at end of file
+}
+
+impl Dielectric {
+  #[inline]
+  pub fn new(ir: Float, albedo: Arc<dyn Texture>) -> Self {
+    Self { ir, albedo }
+  }
+  pub fn new_arc(ir: Float, albedo: Arc<dyn Texture>) -> Arc<Self> {
+    Arc::new(Self { ir, albedo })
+  }
+
+  /// Schlick's approximation of the Fresnel reflectance.
+  /// `cos_i` is the cosine between the incoming ray and the normal, in [0, 1].
+  /// Going from a dense medium into a thinner one, the transmitted angle is used instead,
+  /// so that the approximation stays symmetric.
+  pub fn reflectance(cos_i: Float, ref_idx_in: Float, ref_idx_out: Float) -> Float {
+    let cos = if ref_idx_in > ref_idx_out {
+      let sin_t2 = (ref_idx_in / ref_idx_out).powi(2) * (1.0 - cos_i * cos_i);
+      if sin_t2 >= 1.0 {
+        return 1.0; // total internal reflection
+      }
+      (1.0 - sin_t2).sqrt()
+    } else {
+      cos_i
+    };
+    let r0 = ((ref_idx_in - ref_idx_out) / (ref_idx_in + ref_idx_out)).powi(2);
+    r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+  }
+}
+
+impl Material for Dielectric {
+  fn albedo(&self, record: &HitRecord) -> ColorRgb {
+    self.albedo.value(record.mat_uv, &record.point)
+  }
+  fn sample(&self, record: &HitRecord, wo: Direction, sampler: &mut dyn Sampler) -> Option<BsdfSample> {
+    // +z is the outward normal, so `wo` is outside when wo.z > 0.
+    let outside = wo.z > 0.0;
+    let (ref_idx_in, ref_idx_out) = if outside { (1.0, self.ir) } else { (self.ir, 1.0) };
+
+    let cos_i = wo.z.abs().min(1.0);
+    let reflect_prob = Self::reflectance(cos_i, ref_idx_in, ref_idx_out);
+
+    if sampler.get_1d() < reflect_prob {
+      let wi = Direction::new(-wo.x, -wo.y, wo.z);
+      return Some(BsdfSample {
+        wi,
+        f: ColorRgb::WHITE * (reflect_prob / cos_i.max(FLOAT_EPSILON)),
+        pdf: reflect_prob,
+        is_delta: true,
+      });
+    }
+
+    // reflect_prob is 1 under total internal reflection, so refraction always exists here.
+    let eta = ref_idx_in / ref_idx_out;
+    let sin_t2 = eta * eta * (1.0 - cos_i * cos_i);
+    let cos_t = (1.0 - sin_t2).max(0.0).sqrt();
+    let normal = Direction::new(0.0, 0.0, if outside { 1.0 } else { -1.0 });
+    let wi = (-eta * wo + (eta * cos_i - cos_t) * normal).normalize();
+    let transmit_prob = 1.0 - reflect_prob;
+    let albedo = self.albedo.value(record.mat_uv, &record.point);
+    Some(BsdfSample {
+      wi,
+      f: albedo * (transmit_prob / cos_t.max(FLOAT_EPSILON)),
+      pdf: transmit_prob,
+      is_delta: true,
+    })
+  }
+  fn is_delta(&self) -> bool {
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // always draws the same number.
+  struct FixedSampler(Float);
+
+  impl Sampler for FixedSampler {
+    fn start_pixel_sample(&mut self, _pixel: (u32, u32), _index: u32) {}
+    fn get_1d(&mut self) -> Float {
+      self.0
+    }
+    fn get_2d(&mut self) -> (Float, Float) {
+      (self.0, self.0)
+    }
+    fn seeded(&self, _seed: u64) -> Box<dyn Sampler> {
+      Box::new(FixedSampler(self.0))
+    }
+  }
+
+  #[test]
+  fn test_dielectric() {
+    let tint = ColorRgb::new(0.9, 0.8, 0.7);
+    let glass = Dielectric::new_arc(1.5, texture::SolidColorTexture::new_arc(tint));
+    let ray = Ray::new(Point::new(0.0, 0.0, 1.0), Direction::new(0.0, 0.0, -1.0));
+    let record = HitRecord::from_ray(&ray, Direction::new(0.0, 0.0, 1.0), 1.0, glass.clone(), UV::new(0.0, 0.0));
+
+    // Schlick: r0 head on, 1 at grazing angles, and more reflection the flatter the angle.
+    assert!((Dielectric::reflectance(1.0, 1.0, 1.5) - 0.04).abs() < 1e-6);
+    assert!((Dielectric::reflectance(0.0, 1.0, 1.5) - 1.0).abs() < 1e-6);
+    assert!(Dielectric::reflectance(0.2, 1.0, 1.5) > Dielectric::reflectance(0.8, 1.0, 1.5));
+
+    // refraction into the glass at 45 degrees bends by Snell's law, away from the side of wo.
+    let (sin_i, cos_i) = (PI / 4.0).sin_cos();
+    let wo = Direction::new(sin_i, 0.0, cos_i);
+    let sample = glass.sample(&record, wo, &mut FixedSampler(0.999)).unwrap();
+    let sin_t = sin_i / 1.5;
+    let expected = Direction::new(-sin_t, 0.0, -(1.0 - sin_t * sin_t).sqrt());
+    assert!((sample.wi - expected).length() < 1e-5, "{:?}", sample.wi);
+    assert!(sample.is_delta);
+    let weight = sample.f * (sample.wi.z.abs() / sample.pdf);
+    assert!((weight.r - tint.r).abs() < 1e-4 && (weight.b - tint.b).abs() < 1e-4, "{:?}", weight);
+    // and it mirrors when the reflection is drawn.
+    let sample = glass.sample(&record, wo, &mut FixedSampler(0.0)).unwrap();
+    assert!((sample.wi - Direction::new(-sin_i, 0.0, cos_i)).length() < 1e-5);
+
+    // from inside at 60 degrees, past the critical angle of 41.8: total internal reflection,
+    // whatever is drawn.
+    let (sin_i, cos_i) = (PI / 3.0).sin_cos();
+    let wo = Direction::new(sin_i, 0.0, -cos_i);
+    assert_eq!(Dielectric::reflectance(cos_i, 1.5, 1.0), 1.0);
+    let sample = glass.sample(&record, wo, &mut FixedSampler(0.999)).unwrap();
+    assert!((sample.wi - Direction::new(-sin_i, 0.0, -cos_i)).length() < 1e-5, "{:?}", sample.wi);
+    assert_eq!(sample.pdf, 1.0);
+  }
+}
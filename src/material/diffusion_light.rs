@@ -18,9 +18,6 @@ impl DiffusionLight {
   }
 }
 impl Material for DiffusionLight {
-  fn scatter(&self, _ray_in: &Ray, _record: &HitRecord) -> Option<(ColorRgb, Ray)> {
-    None
-  }
   fn emitted(&self, uv: UV, p: Point) -> ColorRgb {
     self.tex_emit.value(uv, &p)
   }
@@ -16,13 +16,33 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-  fn scatter(&self, _ray_in: &Ray, record: &HitRecord) -> Option<(ColorRgb, Ray)> {
-    let mut direction = record.unit_normal + Vec3d::random_unit();
-    if direction.near_zero() {
-      direction = record.unit_normal;
+  fn albedo(&self, record: &HitRecord) -> ColorRgb {
+    self.albedo.value(record.mat_uv, &record.point)
+  }
+  fn eval(&self, record: &HitRecord, wo: Direction, wi: Direction) -> ColorRgb {
+    if !material::same_hemisphere(wo, wi) {
+      return ColorRgb::BLACK;
+    }
+    self.albedo.value(record.mat_uv, &record.point) / PI
+  }
+  // cosine weighted, on the side of `wo`.
+  fn sample(&self, record: &HitRecord, wo: Direction, sampler: &mut dyn Sampler) -> Option<BsdfSample> {
+    let mut wi = sampler::sample_cosine_hemisphere(sampler.get_2d());
+    if wo.z < 0.0 {
+      wi.z = -wi.z;
     }
-    let scattered = Ray::new(record.point, direction);
-    let attenuation = self.albedo.value(record.mat_uv, &record.point);
-    Some((attenuation, scattered))
+    let pdf = self.pdf(record, wo, wi);
+    if pdf <= 0.0 {
+      return None;
+    }
+    Some(BsdfSample {
+      wi,
+      f: self.eval(record, wo, wi),
+      pdf,
+      is_delta: false,
+    })
+  }
+  fn pdf(&self, _record: &HitRecord, wo: Direction, wi: Direction) -> Float {
+    if material::same_hemisphere(wo, wi) { wi.z.abs() / PI } else { 0.0 }
   }
 }
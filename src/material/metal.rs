@@ -12,14 +12,25 @@ impl Metal {
     Arc::new(Self { albedo, fuzz })
   }
 }
+// The fuzzy reflection has no closed form pdf, so the whole lobe is treated as specular.
 impl Material for Metal {
-  fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(ColorRgb, Ray)> {
-    let reflected = ray_in.reflect(record.hit_t, record.unit_normal).direction.normalize() + self.fuzz * Point::random_unit();
-    let scattered = Ray::new(record.point, reflected);
-    if scattered.direction.is_facing(record.unit_normal) {
-      Some((self.albedo, scattered))
-    } else {
-      None
+  fn albedo(&self, _record: &HitRecord) -> ColorRgb {
+    self.albedo
+  }
+  fn sample(&self, _record: &HitRecord, wo: Direction, sampler: &mut dyn Sampler) -> Option<BsdfSample> {
+    let reflected = Direction::new(-wo.x, -wo.y, wo.z);
+    let wi = (reflected + self.fuzz * sampler::sample_uniform_sphere(sampler.get_2d())).normalize();
+    if !material::same_hemisphere(wo, wi) {
+      return None;
     }
+    Some(BsdfSample {
+      wi,
+      f: self.albedo / wi.z.abs(),
+      pdf: 1.0,
+      is_delta: true,
+    })
+  }
+  fn is_delta(&self) -> bool {
+    true
   }
-}
\ No newline at end of file
+}
@@ -0,0 +1,40 @@
+use crate::prelude::*;
+use medium::PhaseFunction;
+
+/// What a participating medium scatters with: `albedo` of the light, spread by the `phase`
+/// function, the rest absorbed. The frame of the records is meaningless, see `is_volumetric`.
+pub struct Volumetric {
+  albedo: ColorRgb,
+  phase: PhaseFunction,
+}
+
+impl Volumetric {
+  pub fn new(albedo: ColorRgb, phase: PhaseFunction) -> Self {
+    Self { albedo, phase }
+  }
+  pub fn new_arc(albedo: ColorRgb, phase: PhaseFunction) -> Arc<Self> {
+    Arc::new(Self::new(albedo, phase))
+  }
+}
+
+impl Material for Volumetric {
+  fn albedo(&self, _record: &HitRecord) -> ColorRgb {
+    self.albedo
+  }
+  fn eval(&self, _record: &HitRecord, wo: Direction, wi: Direction) -> ColorRgb {
+    self.albedo * self.phase.eval(wo, wi)
+  }
+  fn sample(&self, _record: &HitRecord, wo: Direction, sampler: &mut dyn Sampler) -> Option<BsdfSample> {
+    let (wi, pdf) = self.phase.sample(wo, sampler.get_2d());
+    if pdf <= 0.0 {
+      return None;
+    }
+    Some(BsdfSample { wi, f: self.albedo * pdf, pdf, is_delta: false })
+  }
+  fn pdf(&self, _record: &HitRecord, wo: Direction, wi: Direction) -> Float {
+    self.phase.eval(wo, wi)
+  }
+  fn is_volumetric(&self) -> bool {
+    true
+  }
+}
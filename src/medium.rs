@@ -0,0 +1,106 @@
+use crate::prelude::*;
+
+/// How the light a medium scatters spreads out, by the angle between `wo` and `wi`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PhaseFunction {
+  /// The same in every direction.
+  Isotropic,
+  /// Henyey-Greenstein, of mean cosine `g` in (-1, 1): mostly forward for g > 0, backward
+  /// below, isotropic at 0.
+  HenyeyGreenstein(Float),
+}
+
+impl PhaseFunction {
+  /// Of unit `wo` (towards the viewer) and `wi` (towards the light), per steradian.
+  pub fn eval(&self, wo: Direction, wi: Direction) -> Float {
+    match *self {
+      PhaseFunction::Isotropic => 1.0 / (4.0 * PI),
+      PhaseFunction::HenyeyGreenstein(g) => henyey_greenstein(wo.dot(wi), g),
+    }
+  }
+
+  /// A unit `wi` for unit `wo`, and its pdf, which is `eval`.
+  pub fn sample(&self, wo: Direction, (u, v): (Float, Float)) -> (Direction, Float) {
+    let g = match *self {
+      PhaseFunction::Isotropic => 0.0,
+      PhaseFunction::HenyeyGreenstein(g) => g,
+    };
+    // cos of the angle between wo and wi, going on (forward) is wi = -wo.
+    let cos = if g.abs() < 1e-3 {
+      1.0 - 2.0 * u
+    } else {
+      let s = (1.0 - g * g) / (1.0 + g - 2.0 * g * u);
+      (-(1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+    };
+    let sin = (1.0 - cos * cos).max(0.0).sqrt();
+    let (sin_p, cos_p) = (2.0 * PI * v).sin_cos();
+    let wi = Onb::from_w(wo).to_world(Direction::new(sin * cos_p, sin * sin_p, cos));
+    (wi, self.eval(wo, wi))
+  }
+}
+
+// with cos_theta between wo and wi, so that g > 0 favors wi = -wo.
+fn henyey_greenstein(cos_theta: Float, g: Float) -> Float {
+  let denominator = (1.0 + g * g + 2.0 * g * cos_theta).max(0.0);
+  (1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt().max(FLOAT_EPSILON))
+}
+
+/// A participating medium that is not an object of the world, like fog or a cloud: rays go
+/// through it, and collide in it at random. See `World::add_medium`.
+pub trait Medium: Send + Sync {
+  /// The first collision of `ray` in the medium before `t_max`, as a hit on what it scatters
+  /// (and emits) with. None if the ray gets through.
+  fn sample_collision(&self, ray: &Ray, t_max: Float, sampler: &mut dyn Sampler) -> Option<HitRecord>;
+  /// How much of the light goes along `ray` from `t_max` back to its origin, or an unbiased
+  /// estimate of it.
+  fn transmittance(&self, ray: &Ray, t_max: Float, sampler: &mut dyn Sampler) -> Float;
+}
+
+// A collision of `ray` at `t`, as a hit on `material`.
+fn collision(ray: &Ray, t: Float, material: Arc<dyn Material>) -> HitRecord {
+  // there is no surface: any normal will do, this one faces the viewer.
+  let normal = -ray.direction.normalize();
+  HitRecord::from_ray(ray, normal, t, material, UV::new(0.0, 0.0))
+}
+
+mod constant;
+mod grid;
+mod homogeneous;
+
+pub use constant::ConstantMedium;
+pub use grid::{GridMedium, VoxelGrid};
+pub use homogeneous::HomogeneousMedium;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_phase_functions() {
+    let wo = Direction::new(0.0, 0.6, 0.8);
+    let mut sampler = sampler::IndependentSampler::new(5);
+    let phases = [PhaseFunction::Isotropic, PhaseFunction::HenyeyGreenstein(0.7), PhaseFunction::HenyeyGreenstein(-0.4)];
+    for phase in phases {
+      // sampled by itself: the pdf is the value, and the mean cosine with -wo is g.
+      let mut mean_cos = 0.0;
+      for index in 0..20000 {
+        sampler.start_pixel_sample((0, 0), index);
+        let (wi, pdf) = phase.sample(wo, sampler.get_2d());
+        assert!((wi.length() - 1.0).abs() < 1e-4);
+        assert!((pdf - phase.eval(wo, wi)).abs() <= 1e-3 * pdf);
+        mean_cos += -wo.dot(wi) / 20000.0;
+      }
+      let g = match phase {
+        PhaseFunction::Isotropic => 0.0,
+        PhaseFunction::HenyeyGreenstein(g) => g,
+      };
+      assert!((mean_cos - g).abs() < 0.02, "{:?} {}", phase, mean_cos);
+    }
+
+    let fog = HomogeneousMedium::new(0.5, ColorRgb::WHITE, PhaseFunction::Isotropic);
+    assert!((fog.transmittance(2.0) - (-1.0 as Float).exp()).abs() < 1e-6);
+    // half the paths are longer than the distance of transmittance 1/2.
+    assert!((fog.free_path(0.5) - (2.0 as Float).ln() / 0.5).abs() < 1e-5);
+    assert_eq!(HomogeneousMedium::new(0.0, ColorRgb::WHITE, PhaseFunction::Isotropic).transmittance(1e30), 1.0);
+  }
+}
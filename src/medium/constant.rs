@@ -0,0 +1,125 @@
+use crate::prelude::*;
+
+/// A homogeneous medium filling a closed `boundary`, like a puff of smoke: rays going through
+/// collide in it at random, and scatter with the material of the medium.
+/// Unlike a hittable wrapping its boundary, it is a `Medium`, added with `World::add_medium`,
+/// so that the renderer draws the collisions from its sampler and shadow rays go through with
+/// the exact transmittance. So it cannot go into an `Instance` or a BVH: the boundary is in
+/// world space, transform the boundary instead.
+pub struct ConstantMedium {
+  boundary: Arc<dyn Hittable>,
+  medium: medium::HomogeneousMedium,
+}
+
+impl ConstantMedium {
+  pub fn new(boundary: Arc<dyn Hittable>, medium: medium::HomogeneousMedium) -> Self {
+    Self { boundary, medium }
+  }
+  pub fn new_arc(boundary: Arc<dyn Hittable>, medium: medium::HomogeneousMedium) -> Arc<Self> {
+    Arc::new(Self::new(boundary, medium))
+  }
+
+  // The spans of `ray` inside the boundary, which need not be convex, within [0, t_max], in order.
+  fn spans<'a>(&'a self, ray: &'a Ray, t_max: Float) -> impl Iterator<Item = (Float, Float)> + 'a {
+    let mut t = -Float::MAX;
+    std::iter::from_fn(move || {
+      while t < t_max {
+        let enter = self.boundary.hit(ray, t, Float::MAX)?.hit_t;
+        let exit = self.boundary.hit(ray, past(enter), Float::MAX)?.hit_t;
+        t = past(exit);
+        let (t0, t1) = (enter.max(0.0), exit.min(t_max));
+        if t0 < t1 {
+          return Some((t0, t1));
+        }
+      }
+      None
+    })
+  }
+}
+
+// Just past `t` along a ray, not to hit the same surface again. The step grows with `t`, or it
+// would be lost in rounding far away.
+fn past(t: Float) -> Float {
+  t + RAY_EPSILON * t.abs().max(1.0)
+}
+
+impl Medium for ConstantMedium {
+  fn sample_collision(&self, ray: &Ray, t_max: Float, sampler: &mut dyn Sampler) -> Option<HitRecord> {
+    let length = ray.direction.length();
+    let mut distance = self.medium.free_path(sampler.get_1d());
+    for (t0, t1) in self.spans(ray, t_max) {
+      let inside = (t1 - t0) * length;
+      if distance < inside {
+        return Some(self.medium.collision(ray, t0 + distance / length));
+      }
+      distance -= inside;
+    }
+    None
+  }
+  fn transmittance(&self, ray: &Ray, t_max: Float, _sampler: &mut dyn Sampler) -> Float {
+    let length = ray.direction.length();
+    let inside: Float = self.spans(ray, t_max).map(|(t0, t1)| (t1 - t0) * length).sum();
+    self.medium.transmittance(inside)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_constant_medium() {
+    // a thick slab, two unit spheres side by side: rays through both spans are stopped as much
+    // as through one of twice the length.
+    let white = material::Lambertian::new_arc(texture::SolidColorTexture::new_arc(ColorRgb::WHITE));
+    let sphere = |x: Float| -> Arc<dyn Hittable> {
+      let at = Mat4d::from_translation(Direction::new(x, 0.0, 0.0));
+      geometry::Instance::new_arc(geometry::UnitSphere::new_arc(white.clone()), at)
+    };
+    let boundary = Arc::new(Aggregate::new(vec![sphere(-1.5), sphere(1.5)]));
+    let medium = medium::HomogeneousMedium::new(0.3, ColorRgb::WHITE, medium::PhaseFunction::Isotropic);
+    let smoke = ConstantMedium::new(boundary, medium);
+
+    let ray = Ray::new(Point::new(-5.0, 0.0, 0.0), Direction::new(1.0, 0.0, 0.0));
+    let expected = (-0.3 as Float * 4.0).exp();
+    let mut sampler = sampler::IndependentSampler::new(3);
+    assert!((smoke.transmittance(&ray, Float::MAX, &mut sampler) - expected).abs() < 1e-3);
+    let rays = 20000;
+    let through = (0..rays)
+      .filter(|&index| {
+        sampler.start_pixel_sample((0, 0), index);
+        match smoke.sample_collision(&ray, Float::MAX, &mut sampler) {
+          Some(record) => {
+            let x = record.point.x;
+            assert!((-2.5..=-0.5).contains(&x) || (0.5..=2.5).contains(&x), "{}", x);
+            assert!(record.material.is_volumetric());
+            false
+          }
+          None => true,
+        }
+      })
+      .count();
+    assert!((through as Float / rays as Float - expected).abs() < 0.01, "{} {}", through, expected);
+
+    // starting inside, and stopping before the medium.
+    let inside = Ray::new(Point::new(1.5, 0.0, 0.0), Direction::new(0.0, 1.0, 0.0));
+    let hit = (0..100).any(|index| {
+      sampler.start_pixel_sample((1, 0), index);
+      smoke.sample_collision(&inside, Float::MAX, &mut sampler).is_some_and(|record| record.point.y < 1.0)
+    });
+    assert!(hit);
+    assert!((smoke.transmittance(&inside, Float::MAX, &mut sampler) - (-0.3 as Float).exp()).abs() < 1e-3);
+    assert!(smoke.sample_collision(&ray, 2.0, &mut sampler).is_none());
+    assert_eq!(smoke.transmittance(&ray, 2.0, &mut sampler), 1.0);
+
+    // far away, where a fixed step past a surface would be lost in rounding.
+    let huge = geometry::Instance::new_arc(
+      geometry::UnitSphere::new_arc(white.clone()),
+      Mat4d::from_translation(Direction::new(1e5, 0.0, 0.0)) * Mat4d::from_scaling(1e4, 1e4, 1e4),
+    );
+    let medium = medium::HomogeneousMedium::new(1e-4, ColorRgb::WHITE, medium::PhaseFunction::Isotropic);
+    let far = ConstantMedium::new(huge, medium);
+    let expected = (-2.0 as Float).exp();
+    assert!((far.transmittance(&ray, Float::MAX, &mut sampler) - expected).abs() < 1e-3);
+  }
+}
@@ -0,0 +1,398 @@
+use crate::prelude::*;
+use medium::PhaseFunction;
+use std::io::{self, Read, Write};
+
+/// A dense grid of values in a box, like the `.vol` files of Mitsuba: `channels` values at
+/// every vertex of a `resolution[0]` x `resolution[1]` x `resolution[2]` lattice spanning
+/// `bounds`, interpolated trilinearly in between.
+pub struct VoxelGrid {
+  resolution: [usize; 3],
+  channels: usize,
+  bounds: Aabb,
+  // x fastest, then y, then z, with the channels of a vertex together.
+  data: Vec<Float>,
+}
+
+impl VoxelGrid {
+  /// `data` holds the channels of every vertex, x fastest, then y, then z.
+  /// Panics if its length does not match.
+  pub fn new(resolution: [usize; 3], channels: usize, bounds: Aabb, data: Vec<Float>) -> Self {
+    assert_eq!(data.len(), resolution.iter().product::<usize>() * channels, "wrong size of the voxel data");
+    Self { resolution, channels, bounds, data }
+  }
+
+  /// One channel of what `f` gives at every vertex.
+  pub fn from_fn<F>(resolution: [usize; 3], bounds: Aabb, f: F) -> Self
+  where
+    F: Fn(Point) -> Float,
+  {
+    let [nx, ny, nz] = resolution;
+    let extent = bounds.max - bounds.min;
+    let at = |i: usize, n: usize| if n > 1 { i as Float / (n - 1) as Float } else { 0.5 };
+    let data = (0..nx * ny * nz)
+      .map(|i| {
+        let (x, y, z) = (at(i % nx, nx), at(i / nx % ny, ny), at(i / (nx * ny), nz));
+        f(bounds.min + Direction::new(x * extent.x, y * extent.y, z * extent.z))
+      })
+      .collect();
+    Self::new(resolution, 1, bounds, data)
+  }
+
+  /// Read a Mitsuba `.vol` grid (version 3) of 32 bit floats or bytes.
+  /// A header asking for more values than the file holds is an error.
+  pub fn load<P: AsRef<std::path::Path>>(path: P) -> io::Result<Self> {
+    let file = std::fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    Self::read_sized(io::BufReader::new(file), Some(len))
+  }
+
+  /// See `load`. The values are read as they come, never allocated from the header alone.
+  pub fn read<R: Read>(reader: R) -> io::Result<Self> {
+    Self::read_sized(reader, None)
+  }
+
+  // `len`: of the whole file, if known.
+  fn read_sized<R: Read>(mut reader: R, len: Option<u64>) -> io::Result<Self> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut header = [0u8; 48];
+    reader.read_exact(&mut header)?;
+    if &header[..3] != b"VOL" || header[3] != 3 {
+      return Err(invalid("not a version 3 .vol file".to_string()));
+    }
+    let int = |at: usize| i32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+    let float = |at: usize| f32::from_le_bytes(header[at..at + 4].try_into().unwrap()) as Float;
+    let (encoding, channels) = (int(4), int(20));
+    let resolution = [int(8), int(12), int(16)];
+    if resolution.iter().any(|&n| n <= 0) || channels <= 0 {
+      return Err(invalid(format!("bad size {:?} x {} channels", resolution, channels)));
+    }
+    let resolution = resolution.map(|n| n as usize);
+    let bounds = Aabb { min: Point::new(float(24), float(28), float(32)), max: Point::new(float(36), float(40), float(44)) };
+    let value_size = match encoding {
+      1 => 4, // float32
+      3 => 1, // uint8, from 0 to 1.
+      _ => return Err(invalid(format!("unsupported encoding {}, expected 1 (float32) or 3 (uint8)", encoding))),
+    };
+    let size = resolution
+      .iter()
+      .try_fold(channels as usize * value_size, |size, &n| size.checked_mul(n))
+      .ok_or_else(|| invalid("the grid is too large".to_string()))?;
+    if let Some(len) = len.map(|len| len - header.len() as u64)
+      && len < size as u64
+    {
+      let needed = format!("{:?} x {} channels need {} bytes of values", resolution, channels, size);
+      return Err(invalid(format!("{}, the file has {}", needed, len)));
+    }
+
+    let mut bytes = Vec::new();
+    reader.take(size as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != size {
+      return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the file ends before the values do"));
+    }
+    let data = match value_size {
+      4 => bytes.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap()) as Float).collect(),
+      _ => bytes.iter().map(|&b| b as Float / 255.0).collect(),
+    };
+    Ok(Self::new(resolution, channels as usize, bounds, data))
+  }
+
+  /// Write the grid as a Mitsuba `.vol` of 32 bit floats.
+  #[allow(clippy::unnecessary_cast)] // only while Float is f32
+  pub fn save<P: AsRef<std::path::Path>>(&self, path: P) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(std::fs::File::create(path)?);
+    writer.write_all(b"VOL\x03")?;
+    let [nx, ny, nz] = self.resolution.map(|n| n as i32);
+    for int in [1, nx, ny, nz, self.channels as i32] {
+      writer.write_all(&int.to_le_bytes())?;
+    }
+    let (min, max) = (self.bounds.min, self.bounds.max);
+    for value in [min.x, min.y, min.z, max.x, max.y, max.z].iter().chain(&self.data) {
+      writer.write_all(&(*value as f32).to_le_bytes())?;
+    }
+    writer.flush()
+  }
+
+  pub fn resolution(&self) -> [usize; 3] {
+    self.resolution
+  }
+  pub fn channels(&self) -> usize {
+    self.channels
+  }
+  pub fn bounds(&self) -> Aabb {
+    self.bounds
+  }
+  /// The largest value of any channel.
+  pub fn max(&self) -> Float {
+    self.data.iter().fold(0.0, |max, &v| max.max(v))
+  }
+
+  /// `channel` at `point`, in the space of the grid, trilinearly. 0 outside of the bounds.
+  pub fn lookup(&self, point: &Point, channel: usize) -> Float {
+    let mut lower = [0; 3];
+    let mut upper = [0; 3];
+    let mut frac = [0.0; 3];
+    for axis in 0..3 {
+      let (min, max, n) = (self.bounds.min[axis], self.bounds.max[axis], self.resolution[axis]);
+      if !(min..=max).contains(&point[axis]) {
+        return 0.0;
+      }
+      let c = if max > min { (point[axis] - min) / (max - min) * (n - 1) as Float } else { 0.0 };
+      lower[axis] = (c as usize).min(n.saturating_sub(2));
+      upper[axis] = (lower[axis] + 1).min(n - 1);
+      frac[axis] = (c - lower[axis] as Float).clamp(0.0, 1.0);
+    }
+    let [nx, ny, _] = self.resolution;
+    let value = |x: usize, y: usize, z: usize| self.data[((z * ny + y) * nx + x) * self.channels + channel];
+    let lerp = |a: Float, b: Float, t: Float| a + (b - a) * t;
+    let at_z = |z: usize| {
+      let y0 = lerp(value(lower[0], lower[1], z), value(upper[0], lower[1], z), frac[0]);
+      let y1 = lerp(value(lower[0], upper[1], z), value(upper[0], upper[1], z), frac[0]);
+      lerp(y0, y1, frac[1])
+    };
+    lerp(at_z(lower[2]), at_z(upper[2]), frac[2])
+  }
+}
+
+/// A medium of varying density read from a `VoxelGrid`: clouds, smoke, explosions. It may glow
+/// too, by a grid of emitted radiance or of temperature, like a black body.
+/// Collisions are found by delta tracking against the density of the densest point (the
+/// majorant), and shadow rays go through by ratio tracking.
+pub struct GridMedium {
+  density: VoxelGrid,
+  density_scale: Float,
+  majorant: Float,
+  // from the world to the space of the grids.
+  to_grid: Mat4d,
+  material: Arc<GridMaterial>,
+}
+
+// What the collisions of a `GridMedium` scatter and emit with.
+struct GridMaterial {
+  scattering: material::Volumetric,
+  // 1 - albedo: the share of the collisions which absorb, and so emit.
+  absorption: ColorRgb,
+  emission: Option<(VoxelGrid, Float)>,
+  temperature: Option<(VoxelGrid, Float)>,
+  to_grid: Mat4d,
+}
+
+impl GridMedium {
+  /// The first channel of `density` times `density_scale` is the chance of a collision per
+  /// unit of length in the world, whatever the transform, see `with_transform`.
+  pub fn new(density: VoxelGrid, density_scale: Float, albedo: ColorRgb, phase: PhaseFunction) -> Self {
+    let majorant = density.max() * density_scale;
+    let material = GridMaterial {
+      scattering: material::Volumetric::new(albedo, phase),
+      absorption: ColorRgb::new(1.0 - albedo.r, 1.0 - albedo.g, 1.0 - albedo.b),
+      emission: None,
+      temperature: None,
+      to_grid: Mat4d::IDENTITY,
+    };
+    Self { density, density_scale, majorant, to_grid: Mat4d::IDENTITY, material: Arc::new(material) }
+  }
+
+  /// Place the grids in the world: `transform` goes from their space to the world.
+  /// It shall be invertible.
+  pub fn with_transform(mut self, transform: Mat4d) -> Self {
+    self.to_grid = transform.inverse().expect("the transform of a grid medium shall be invertible");
+    self.material_mut().to_grid = self.to_grid;
+    self
+  }
+  /// Glow by `emission` (radiance, rgb with 3 channels or more, gray otherwise) times `scale`,
+  /// where the medium absorbs. It shares the space of the density grid.
+  pub fn with_emission(mut self, emission: VoxelGrid, scale: Float) -> Self {
+    self.material_mut().emission = Some((emission, scale));
+    self
+  }
+  /// Glow like a black body at the `temperature` of the grid (kelvin), where the medium
+  /// absorbs. The colors are relative to their peak, `scale` sets the radiance.
+  pub fn with_temperature(mut self, temperature: VoxelGrid, scale: Float) -> Self {
+    self.material_mut().temperature = Some((temperature, scale));
+    self
+  }
+
+  fn material_mut(&mut self) -> &mut GridMaterial {
+    Arc::get_mut(&mut self.material).expect("a grid medium is built before it is rendered")
+  }
+
+  /// The highest density of the medium.
+  pub fn majorant(&self) -> Float {
+    self.majorant
+  }
+
+  // `ray` in the space of the grid, and its span within the bounds and [0, t_max].
+  fn clip(&self, ray: &Ray, t_max: Float) -> Option<(Point, Direction, Float, Float)> {
+    let origin = self.to_grid.transform_point(ray.origin);
+    let direction = self.to_grid.transform_vector(ray.direction);
+    let bounds = self.density.bounds();
+    let (mut t0, mut t1) = (0.0, t_max);
+    for axis in 0..3 {
+      let inv = 1.0 / direction[axis];
+      let (near, far) = ((bounds.min[axis] - origin[axis]) * inv, (bounds.max[axis] - origin[axis]) * inv);
+      let (near, far) = if inv < 0.0 { (far, near) } else { (near, far) };
+      // NaN (a ray along a face) leaves the span as is.
+      t0 = near.max(t0);
+      t1 = far.min(t1);
+      if t1 <= t0 {
+        return None;
+      }
+    }
+    Some((origin, direction, t0, t1))
+  }
+}
+
+impl Medium for GridMedium {
+  // delta tracking: tentative collisions as in a medium of the majorant everywhere, each one
+  // real as often as the density there fills the majorant.
+  fn sample_collision(&self, ray: &Ray, t_max: Float, sampler: &mut dyn Sampler) -> Option<HitRecord> {
+    if self.majorant <= 0.0 {
+      return None;
+    }
+    let (origin, direction, mut t, t1) = self.clip(ray, t_max)?;
+    let rate = self.majorant * ray.direction.length();
+    loop {
+      t -= (1.0 - sampler.get_1d()).ln() / rate;
+      if t >= t1 {
+        return None;
+      }
+      let density = self.density.lookup(&(origin + t * direction), 0) * self.density_scale;
+      if sampler.get_1d() * self.majorant < density {
+        return Some(super::collision(ray, t, self.material.clone()));
+      }
+    }
+  }
+
+  // ratio tracking: through the same tentative collisions, the transmittance keeps the share
+  // of the majorant which is not there.
+  fn transmittance(&self, ray: &Ray, t_max: Float, sampler: &mut dyn Sampler) -> Float {
+    if self.majorant <= 0.0 {
+      return 1.0;
+    }
+    let Some((origin, direction, mut t, t1)) = self.clip(ray, t_max) else {
+      return 1.0;
+    };
+    let rate = self.majorant * ray.direction.length();
+    let mut transmittance: Float = 1.0;
+    loop {
+      t -= (1.0 - sampler.get_1d()).ln() / rate;
+      if t >= t1 || transmittance <= 0.0 {
+        return transmittance.max(0.0);
+      }
+      let density = self.density.lookup(&(origin + t * direction), 0) * self.density_scale;
+      transmittance *= 1.0 - density / self.majorant;
+    }
+  }
+}
+
+impl Material for GridMaterial {
+  fn albedo(&self, record: &HitRecord) -> ColorRgb {
+    self.scattering.albedo(record)
+  }
+  fn eval(&self, record: &HitRecord, wo: Direction, wi: Direction) -> ColorRgb {
+    self.scattering.eval(record, wo, wi)
+  }
+  fn sample(&self, record: &HitRecord, wo: Direction, sampler: &mut dyn Sampler) -> Option<BsdfSample> {
+    self.scattering.sample(record, wo, sampler)
+  }
+  fn pdf(&self, record: &HitRecord, wo: Direction, wi: Direction) -> Float {
+    self.scattering.pdf(record, wo, wi)
+  }
+  fn is_volumetric(&self) -> bool {
+    true
+  }
+  // at a collision, the medium emits as much as it absorbs of what it would let through.
+  fn emitted(&self, _uv: UV, p: Point) -> ColorRgb {
+    if self.emission.is_none() && self.temperature.is_none() {
+      return ColorRgb::BLACK;
+    }
+    let local = self.to_grid.transform_point(p);
+    let mut radiance = ColorRgb::BLACK;
+    if let Some((ref grid, scale)) = self.emission {
+      radiance += match grid.channels() {
+        3.. => ColorRgb::new(grid.lookup(&local, 0), grid.lookup(&local, 1), grid.lookup(&local, 2)),
+        _ => ColorRgb::WHITE * grid.lookup(&local, 0),
+      } * scale;
+    }
+    if let Some((ref grid, scale)) = self.temperature {
+      radiance += blackbody(grid.lookup(&local, 0)) * scale;
+    }
+    radiance * self.absorption
+  }
+}
+
+// The color of a black body at `temperature` (kelvin), by Planck's law at one wavelength for
+// each of r, g and b, relative to its peak: only the color changes with the temperature.
+fn blackbody(temperature: Float) -> ColorRgb {
+  if temperature <= 0.0 {
+    return ColorRgb::BLACK;
+  }
+  let temperature = temperature as f64;
+  // wavelengths in meters.
+  let planck = |lambda: f64| 1.0 / (lambda.powi(5) * ((1.438_777e-2 / (lambda * temperature)).exp() - 1.0));
+  // Wien's displacement law.
+  let peak = planck(2.897_772e-3 / temperature);
+  let relative = |lambda_nm: f64| (planck(lambda_nm * 1e-9) / peak) as Float;
+  ColorRgb::new(relative(610.0), relative(550.0), relative(465.0))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_grid_medium() {
+    // a slab thicker and thicker along x, in [0, 1] x [0, 1] x [0, 1] scaled by 2.
+    let bounds = Aabb { min: Point::new(0.0, 0.0, 0.0), max: Point::new(1.0, 1.0, 1.0) };
+    let grid = VoxelGrid::from_fn([5, 3, 2], bounds, |p| p.x);
+    assert!((grid.lookup(&Point::new(0.3, 0.7, 0.2), 0) - 0.3).abs() < 1e-5);
+    assert_eq!(grid.lookup(&Point::new(1.5, 0.5, 0.5), 0), 0.0);
+
+    // through a .vol file and back.
+    let path = std::env::temp_dir().join(format!("grid_medium_{}.vol", std::process::id()));
+    grid.save(&path).unwrap();
+    let grid = VoxelGrid::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!((grid.resolution(), grid.channels()), ([5, 3, 2], 1));
+    assert!(VoxelGrid::read(&b"VOL\x02"[..]).is_err());
+    // a header of 2^31 values on 4 bytes each, or far more than the values that follow.
+    let header = |n: i32| {
+      let ints = [1, n, n, n, 1].into_iter().flat_map(i32::to_le_bytes);
+      b"VOL\x03".iter().copied().chain(ints).chain([0u8; 24]).chain([0u8; 16]).collect::<Vec<u8>>()
+    };
+    assert!(VoxelGrid::read(&header(1 << 30)[..]).is_err());
+    assert!(VoxelGrid::read(&header(1000)[..]).is_err());
+    std::fs::write(&path, header(1000)).unwrap();
+    assert_eq!(VoxelGrid::load(&path).err().unwrap().kind(), io::ErrorKind::InvalidData);
+    std::fs::remove_file(&path).unwrap();
+
+    let medium = GridMedium::new(grid, 1.5, ColorRgb::WHITE, PhaseFunction::Isotropic)
+      .with_transform(Mat4d::from_scaling(2.0, 2.0, 2.0));
+    assert_eq!(medium.majorant(), 1.5);
+    // along y at x = 1 (0.5 in the grid), the density is 0.75 over a length of 2.
+    let ray = Ray::new(Point::new(1.0, -1.0, 1.0), Direction::new(0.0, 1.0, 0.0));
+    let expected = (-0.75 as Float * 2.0).exp();
+    let mut sampler = sampler::IndependentSampler::new(9);
+    let (mut through, mut ratio) = (0, 0.0);
+    let rays = 20000;
+    for index in 0..rays {
+      sampler.start_pixel_sample((0, 0), index);
+      match medium.sample_collision(&ray, Float::MAX, &mut sampler) {
+        Some(record) => assert!((1.0..=3.0).contains(&record.hit_t), "{}", record.hit_t),
+        None => through += 1,
+      }
+      ratio += medium.transmittance(&ray, Float::MAX, &mut sampler) / rays as Float;
+    }
+    let delta = through as Float / rays as Float;
+    assert!((delta - expected).abs() < 0.01, "{} {}", delta, expected);
+    assert!((ratio - expected).abs() < 0.01, "{} {}", ratio, expected);
+    // stopped before the medium, or missing it.
+    assert_eq!(medium.transmittance(&ray, 0.5, &mut sampler), 1.0);
+    let aside = Ray::new(Point::new(3.0, -1.0, 1.0), Direction::new(0.0, 1.0, 0.0));
+    assert!(medium.sample_collision(&aside, Float::MAX, &mut sampler).is_none());
+
+    // hot spots glow orange, hotter ones white.
+    let (warm, hot) = (blackbody(1800.0), blackbody(6500.0));
+    assert!(warm.r > warm.g && warm.g > warm.b && warm.b > 0.0, "{:?}", warm);
+    assert!(hot.r.min(hot.g).min(hot.b) > 0.75, "{:?}", hot);
+  }
+}
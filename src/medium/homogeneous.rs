@@ -0,0 +1,49 @@
+use crate::prelude::*;
+use medium::PhaseFunction;
+
+/// A medium of the same density everywhere, scattering `albedo` of the light it stops and
+/// absorbing the rest: fog, smoke, haze.
+pub struct HomogeneousMedium {
+  density: Float,
+  material: Arc<dyn Material>,
+}
+
+impl HomogeneousMedium {
+  /// `density` is the chance of a collision per unit of length, the extinction coefficient.
+  pub fn new(density: Float, albedo: ColorRgb, phase: PhaseFunction) -> Self {
+    Self { density: density.max(0.0), material: material::Volumetric::new_arc(albedo, phase) }
+  }
+
+  pub fn density(&self) -> Float {
+    self.density
+  }
+  /// What the collisions in the medium scatter with, see `material::Volumetric`.
+  pub fn material(&self) -> &Arc<dyn Material> {
+    &self.material
+  }
+
+  /// How much of the light goes through `distance` of the medium.
+  pub fn transmittance(&self, distance: Float) -> Float {
+    if self.density <= 0.0 { 1.0 } else { (-self.density * distance).exp() }
+  }
+
+  /// Distance to the next collision, with `u` in [0, 1). Infinite without density.
+  pub fn free_path(&self, u: Float) -> Float {
+    if self.density <= 0.0 { Float::INFINITY } else { -(1.0 - u).ln() / self.density }
+  }
+
+  /// A collision of `ray` at `t`, as a hit on the material of the medium.
+  pub fn collision(&self, ray: &Ray, t: Float) -> HitRecord {
+    super::collision(ray, t, self.material.clone())
+  }
+}
+
+impl Medium for HomogeneousMedium {
+  fn sample_collision(&self, ray: &Ray, t_max: Float, sampler: &mut dyn Sampler) -> Option<HitRecord> {
+    let t = self.free_path(sampler.get_1d()) / ray.direction.length();
+    (t < t_max).then(|| self.collision(ray, t))
+  }
+  fn transmittance(&self, ray: &Ray, t_max: Float, _sampler: &mut dyn Sampler) -> Float {
+    HomogeneousMedium::transmittance(self, t_max * ray.direction.length())
+  }
+}
@@ -1,13 +1,18 @@
 pub use crate::camera::{self, Camera, OrthographicCamera, PerspectiveCamera};
 pub use crate::config::*;
-pub use crate::defs::{self, ColorRgb, Direction, Mat4d, Point, Ray, UV, Vec3d};
-pub use crate::film::{self, Film};
+pub use crate::defs::{self, ColorRgb, Direction, Mat4d, Onb, Point, Quat, Ray, UV, Vec3d};
+pub use crate::film::{self, Aov, Film, PixelFilter};
 pub use crate::filter::{self, Filter, FilterList};
 pub use crate::geometry::{
-  self, Aabb, Aggregate, BvhAggregate, HitRecord, Hittable, Triangle, TriangleMesh,
+  self, Aabb, Aggregate, BvhAggregate, BvhOptions, HitRecord, Hittable, MeshAccel, SurfaceSample,
+  Triangle, TriangleMesh,
 };
-pub use crate::material::{self, Material};
+pub use crate::light::{self, Light, LightSample};
+pub use crate::material::{self, BsdfSample, Material};
+pub use crate::medium::{self, Medium};
 pub use crate::renderer::{self, RenderConfig, Renderer};
+pub use crate::sampler::{self, Sampler};
+pub use crate::scene::{self, Scene};
 pub use crate::texture::{self, Texture};
 pub use crate::world::{self, World};
 
@@ -6,12 +6,16 @@ pub struct RenderConfig {
   pub camera: Arc<dyn Camera>,
   pub filters: Arc<FilterList>,
   pub world: Arc<World>,
+  /// Every random number of the render comes from this, so the same seed gives the same image.
+  pub seed: u64,
 }
 
 pub trait Renderer {
   fn render(&self, config: RenderConfig);
 }
 
+mod progressive;
 mod simple;
 
-pub use simple::SimpleRenderer;
\ No newline at end of file
+pub use progressive::{AdaptiveSampling, PassCallback, PassInfo, ProgressiveRenderer};
+pub use simple::{SimpleRenderer, TileCallback, TileInfo};
\ No newline at end of file
@@ -0,0 +1,295 @@
+use crate::prelude::*;
+use crate::renderer::{RenderConfig, SimpleRenderer};
+use rayon::prelude::*;
+use std::time::{Duration, Instant};
+
+// Rows are rendered this many at a time in parallel, each into a band of its own, and the bands
+// are merged in row order, so the sums do not depend on the scheduling.
+const ROWS_PER_BATCH: u32 = 64;
+
+// The rows of the film that the samples taken in row `y` reach through `filter`.
+fn row_band(y: u32, width: u32, filter: &PixelFilter) -> film::AccumulationBuffer {
+  let margin = (filter.radius() + 0.5).ceil() as i32;
+  film::AccumulationBuffer::new_window(0, y as i32 - margin, width, 2 * margin as u32 + 1)
+}
+
+/// Where a progressive render is, passed to the callback after each pass.
+#[derive(Clone, Copy, Debug)]
+pub struct PassInfo {
+  /// Starting from 1.
+  pub pass: u32,
+  /// Samples per pixel taken so far, by the pixels that were never skipped.
+  pub samples_per_pixel: u32,
+  /// Pixels sampled in this pass.
+  pub active_pixels: u32,
+  pub elapsed: Duration,
+}
+
+/// Stop sampling a pixel once its luminance is known well enough.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveSampling {
+  /// Every pixel takes at least this many samples before it is judged.
+  pub min_samples: u32,
+  /// Max standard error of the mean luminance, relative to the mean, like 0.01.
+  pub threshold: Float,
+}
+
+pub type PassCallback = Box<dyn Fn(&PassInfo, &dyn Film) + Send + Sync>;
+
+/// Renders in passes of a few samples per pixel, accumulated into a sum + weight buffer.
+/// After each pass, the film holds the average so far (with the filters applied), so it can be
+/// shown or saved while the render goes on.
+/// Stops at `samples_per_pixel`, at the first pass that ends beyond the time budget, or when
+/// adaptive sampling finds every pixel converged.
+pub struct ProgressiveRenderer {
+  samples_per_pixel: u32,
+  max_depth: u32,
+  samples_per_pass: u32,
+  time_budget: Option<Duration>,
+  adaptive: Option<AdaptiveSampling>,
+  on_pass: Option<PassCallback>,
+  sampler: Box<dyn Sampler>,
+  pixel_filter: PixelFilter,
+}
+
+impl ProgressiveRenderer {
+  pub fn new(samples_per_pixel: u32, max_depth: u32) -> Self {
+    Self {
+      samples_per_pixel,
+      max_depth,
+      samples_per_pass: 1,
+      time_budget: None,
+      adaptive: None,
+      on_pass: None,
+      sampler: Box::new(sampler::IndependentSampler::new(0)),
+      pixel_filter: PixelFilter::default(),
+    }
+  }
+
+  pub fn with_samples_per_pass(mut self, samples: u32) -> Self {
+    assert!(samples > 0, "ProgressiveRenderer: a pass needs at least 1 sample");
+    self.samples_per_pass = samples;
+    self
+  }
+  /// The sample indices go on from pass to pass, so the samples of all the passes together
+  /// are those of one `samples_per_pixel` render.
+  pub fn with_sampler(mut self, sampler: Box<dyn Sampler>) -> Self {
+    self.sampler = sampler;
+    self
+  }
+  pub fn with_pixel_filter(mut self, filter: PixelFilter) -> Self {
+    self.pixel_filter = filter;
+    self
+  }
+  /// Stop after the pass that reaches `budget`. Set `samples_per_pixel` to `u32::MAX` to only
+  /// stop by time.
+  pub fn with_time_budget(mut self, budget: Duration) -> Self {
+    self.time_budget = Some(budget);
+    self
+  }
+  /// Skip the pixels that have converged, then `samples_per_pixel` is the max.
+  /// The `Aov::SampleCount` layer shows where the samples went.
+  pub fn with_adaptive(mut self, adaptive: AdaptiveSampling) -> Self {
+    self.adaptive = Some(adaptive);
+    self
+  }
+  /// `callback` runs on the calling thread, with the film locked.
+  pub fn on_pass<F>(mut self, callback: F) -> Self
+  where
+    F: Fn(&PassInfo, &dyn Film) + Send + Sync + 'static,
+  {
+    self.on_pass = Some(Box::new(callback));
+    self
+  }
+}
+
+impl Renderer for ProgressiveRenderer {
+  fn render(&self, config: RenderConfig) {
+    let RenderConfig { film, camera, filters, world, seed } = config;
+
+    let (width, height, layers);
+    {
+      let film = film.lock().unwrap();
+      width = film.width();
+      height = film.height();
+      layers = film.layers().to_vec();
+    }
+    let mut buffer = film::AccumulationBuffer::new(width, height);
+    let mut aovs: Vec<film::AovAccumulator> =
+      (0..width * height).map(|_| film::AovAccumulator::new(&layers)).collect();
+    let trace_layers = layers.iter().any(|aov| aov.needs_hit());
+    // pixels to sample in the next pass.
+    let mut active = vec![true; (width * height) as usize];
+
+    let bar = match self.time_budget {
+      Some(_) if self.samples_per_pixel == u32::MAX => indicatif::ProgressBar::new_spinner(),
+      _ => indicatif::ProgressBar::new(self.samples_per_pixel as u64),
+    };
+    let start = Instant::now();
+    let mut info =
+      PassInfo { pass: 0, samples_per_pixel: 0, active_pixels: width * height, elapsed: Duration::ZERO };
+
+    while info.samples_per_pixel < self.samples_per_pixel && info.active_pixels > 0 {
+      let samples = self.samples_per_pass.min(self.samples_per_pixel - info.samples_per_pixel);
+      for batch in (0..height).step_by(ROWS_PER_BATCH as usize) {
+        let rows = (batch * width) as usize..((batch + ROWS_PER_BATCH).min(height) * width) as usize;
+        let bands = aovs[rows.clone()]
+          .par_chunks_mut(width as usize)
+          .zip(active[rows].par_chunks(width as usize))
+          .enumerate()
+          .map_init(|| self.sampler.seeded(seed), |sampler, (i, (aov_row, active_row))| {
+            let y = batch + i as u32;
+            let mut band = row_band(y, width, &self.pixel_filter);
+            for x in 0..width as usize {
+              if !active_row[x] {
+                continue;
+              }
+              for index in info.samples_per_pixel..info.samples_per_pixel + samples {
+                sampler.start_pixel_sample((x as u32, y), index);
+                let (du, dv) = sampler.get_2d();
+                let u = (x as Float + du) / (width - 1) as Float;
+                let v = 1.0 - (y as Float + dv) / (height - 1) as Float;
+                let ray = camera.get_ray(u, v, &mut **sampler);
+                let color = SimpleRenderer::ray_color(&ray, &world, self.max_depth, &mut **sampler);
+                band.add_sample(x as Float + du, y as Float + dv, color, &self.pixel_filter);
+                if !layers.is_empty() {
+                  let record = if trace_layers { world.hit(&ray, FLOAT_EPSILON, Float::MAX) } else { None };
+                  aov_row[x].add(&ray, record.as_ref(), &world);
+                }
+              }
+            }
+            band
+          })
+          .collect::<Vec<_>>();
+        for band in bands {
+          buffer.merge(&band);
+        }
+      }
+
+      info.pass += 1;
+      info.samples_per_pixel += samples;
+      info.elapsed = start.elapsed();
+      bar.inc(samples as u64);
+      if let Some(adaptive) = self.adaptive
+        && info.samples_per_pixel >= adaptive.min_samples
+      {
+        for (idx, active) in active.iter_mut().enumerate() {
+          let (x, y) = (idx as u32 % width, idx as u32 / width);
+          *active = *active && buffer.relative_error(x, y) > adaptive.threshold;
+        }
+      }
+
+      let mut film = film.lock().unwrap();
+      buffer.resolve(&mut *film);
+      for (idx, pixel_aovs) in aovs.iter().enumerate() {
+        let (x, y) = (idx as u32 % width, idx as u32 / width);
+        for (aov, value) in pixel_aovs.values() {
+          film.set_layer_pixel(aov, x, y, value);
+        }
+      }
+      filters.process(&mut *film);
+      if let Some(ref callback) = self.on_pass {
+        callback(&info, &*film);
+      }
+      info.active_pixels = active.iter().filter(|active| **active).count() as u32;
+      if self.time_budget.is_some_and(|budget| info.elapsed >= budget) {
+        break;
+      }
+    }
+    bar.finish_and_clear();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn config(film: Arc<Mutex<dyn Film>>) -> RenderConfig {
+    // a bright sky above, which the camera rays along -z do not see.
+    let mut world = World::new(|ray| {
+      if ray.direction.y > 0.1 { ColorRgb::WHITE } else { ColorRgb::new(0.5, 0.5, 0.5) }
+    });
+    let material = material::Lambertian::new_arc(texture::SolidColorTexture::new_arc(ColorRgb::WHITE));
+    world.add_object(geometry::UnitSphere::new_arc(material));
+    world.bvh_finalize();
+    let camera = OrthographicCamera::new(
+      Point::new(0.0, 0.0, 5.0),
+      Point::new(0.0, 0.0, 0.0),
+      Direction::new(0.0, 1.0, 0.0),
+      4.0,
+      1.0,
+    );
+    RenderConfig {
+      film,
+      camera: Arc::new(camera),
+      filters: Arc::new(FilterList::new()),
+      world: Arc::new(world),
+      seed: 0,
+    }
+  }
+
+  #[test]
+  fn test_progressive_passes() {
+    let film = Arc::new(Mutex::new(film::SimpleFilm::new(8, 8).with_layers(&[Aov::SampleCount])));
+    let passes = Arc::new(Mutex::new(Vec::new()));
+    let seen = passes.clone();
+    ProgressiveRenderer::new(10, 4)
+      .with_samples_per_pass(4)
+      .on_pass(move |info, film| {
+        // the film already holds the pass.
+        let samples = film.get_layer_pixel(Aov::SampleCount, 3, 5).unwrap().r;
+        seen.lock().unwrap().push((info.pass, info.samples_per_pixel, samples as u32));
+      })
+      .render(config(film.clone()));
+    assert_eq!(*passes.lock().unwrap(), [(1, 4, 4), (2, 8, 8), (3, 10, 10)]);
+    // the background (0.5) is seen directly in the corner.
+    let corner = film.lock().unwrap().get_pixel(0, 0);
+    assert!((corner.r - 0.5).abs() < 1e-5);
+
+    // a budget already spent stops after the first pass.
+    let film = Arc::new(Mutex::new(film::SimpleFilm::new(8, 8)));
+    let count = Arc::new(Mutex::new(0));
+    let counter = count.clone();
+    ProgressiveRenderer::new(u32::MAX, 4)
+      .with_time_budget(Duration::ZERO)
+      .on_pass(move |_, _| *counter.lock().unwrap() += 1)
+      .render(config(film));
+    assert_eq!(*count.lock().unwrap(), 1);
+  }
+
+  #[test]
+  fn test_adaptive_sampling() {
+    // the background is flat and converges at once, the upper half of the sphere, which sees
+    // both the bright sky and the gray horizon, stays noisy.
+    let film = Arc::new(Mutex::new(film::SimpleFilm::new(8, 8).with_layers(&[Aov::SampleCount])));
+    let adaptive = AdaptiveSampling { min_samples: 8, threshold: 1e-4 };
+    ProgressiveRenderer::new(32, 4).with_adaptive(adaptive).render(config(film.clone()));
+    let film = film.lock().unwrap();
+    let samples = |x, y| film.get_layer_pixel(Aov::SampleCount, x, y).unwrap().r;
+    assert_eq!(samples(0, 0), 8.0);
+    let busiest = (0..64).map(|i| (i % 8, i / 8)).max_by_key(|&(x, y)| samples(x, y) as u32).unwrap();
+    assert_eq!(samples(busiest.0, busiest.1), 32.0);
+    let heatmap = film::samples_heatmap(&*film).unwrap();
+    assert_ne!(heatmap.get_pixel(0, 0), heatmap.get_pixel(busiest.0, busiest.1));
+  }
+
+  #[test]
+  fn test_progressive_pixel_filter() {
+    // the passes add up to the samples of a single render, splatted the same way.
+    let filter = PixelFilter::Mitchell { radius: 2.0, b: 1.0 / 3.0, c: 1.0 / 3.0 };
+    let simple_film = Arc::new(Mutex::new(film::SimpleFilm::new(8, 8)));
+    let renderer = SimpleRenderer::new(12, 4).with_pixel_filter(filter);
+    renderer.render(config(simple_film.clone()));
+    let film = Arc::new(Mutex::new(film::SimpleFilm::new(8, 8)));
+    SimpleRenderer::new(12, 4)
+      .with_pixel_filter(filter)
+      .into_progressive()
+      .with_samples_per_pass(5)
+      .render(config(film.clone()));
+    let (a, b) = (simple_film.lock().unwrap(), film.lock().unwrap());
+    for (x, y) in (0..64).map(|i| (i % 8, i / 8)) {
+      let (a, b) = (a.get_pixel(x, y), b.get_pixel(x, y));
+      assert!((a.r - b.r).abs() < 1e-4 && (a.b - b.b).abs() < 1e-4, "{:?} {:?}", a, b);
+    }
+  }
+}
@@ -1,86 +1,463 @@
 use crate::prelude::*;
 use crate::renderer::RenderConfig;
-use rand::Rng;
 use rayon::prelude::*;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
+use crate::renderer::ProgressiveRenderer;
+
+/// A tile of the film that was just written, passed to the callback.
+#[derive(Clone, Copy, Debug)]
+pub struct TileInfo {
+  pub tile: film::Tile,
+  /// Tiles written so far, this one included.
+  pub tiles_done: u32,
+  pub tiles_total: u32,
+  pub elapsed: Duration,
+}
+
+pub type TileCallback = Box<dyn Fn(&TileInfo) + Send + Sync>;
+
+/// Renders the film tile by tile: every thread takes the next tile in `TileOrder` and renders it
+/// on its own. A tile is written into the film with `Film::write_tile` once the tiles around it
+/// that the pixel filter spreads samples from are rendered too, so the memory besides the film
+/// is the tiles in flight and those waiting on a neighbour.
 pub struct SimpleRenderer {
   samples_per_pixel: u32,
   max_depth: u32,
+  sampler: Box<dyn Sampler>,
+  pixel_filter: PixelFilter,
+  tile_size: u32,
+  tile_order: film::TileOrder,
+  on_tile: Option<TileCallback>,
 }
 
 impl SimpleRenderer {
+  /// Samples with `IndependentSampler` and a box filter, see `with_sampler` and `with_pixel_filter`.
   #[inline]
   pub fn new(samples_per_pixel: u32, max_depth: u32) -> SimpleRenderer {
-    Self { samples_per_pixel, max_depth }
-  }
-  // depth decreases while function is recursively called.
-  // When it reaches 0, terminate and return ColorRgb::BLACK.
-  fn ray_color(ray: &Ray, world: &World, depth: u32) -> ColorRgb {
-    if depth == 0 {
-      return ColorRgb::BLACK;
+    Self {
+      samples_per_pixel,
+      max_depth,
+      sampler: Box::new(sampler::IndependentSampler::new(0)),
+      pixel_filter: PixelFilter::default(),
+      tile_size: 32,
+      tile_order: film::TileOrder::default(),
+      on_tile: None,
     }
+  }
+  /// Every thread renders with a copy of `sampler`, seeded by `RenderConfig::seed`.
+  pub fn with_sampler(mut self, sampler: Box<dyn Sampler>) -> Self {
+    self.sampler = sampler;
+    self
+  }
+  pub fn with_pixel_filter(mut self, filter: PixelFilter) -> Self {
+    self.pixel_filter = filter;
+    self
+  }
+  /// 32 by default.
+  pub fn with_tile_size(mut self, size: u32) -> Self {
+    assert!(size > 0, "SimpleRenderer: a tile needs at least 1 pixel");
+    self.tile_size = size;
+    self
+  }
+  /// Spiral by default.
+  pub fn with_tile_order(mut self, order: film::TileOrder) -> Self {
+    self.tile_order = order;
+    self
+  }
+  /// `callback` runs on the thread that wrote the tile, after it is written into the film.
+  /// The render holds the lock of the film meanwhile: do not lock it from the callback.
+  pub fn on_tile<F>(mut self, callback: F) -> Self
+  where
+    F: Fn(&TileInfo) + Send + Sync + 'static,
+  {
+    self.on_tile = Some(Box::new(callback));
+    self
+  }
+  /// A progressive renderer with the same sampler and pixel filter.
+  pub fn into_progressive(self) -> ProgressiveRenderer {
+    ProgressiveRenderer::new(self.samples_per_pixel, self.max_depth)
+      .with_sampler(self.sampler)
+      .with_pixel_filter(self.pixel_filter)
+  }
+  // Path tracing with next-event estimation.
+  // `depth` is the max count of surfaces a path may hit.
+  // At every non-specular hit, one light is sampled explicitly, and its contribution is combined
+  // with the BSDF sampled path (which may also hit the light) by multiple importance sampling.
+  // Directions at a hit are handled in the local shading frame of the material.
+  // In the media of the world, a ray may collide before the surface it would hit, by free
+  // flight sampling (delta tracking where the density varies); it then scatters there like off
+  // a surface of the medium's material, and emits if the medium glows.
+  pub(crate) fn ray_color(ray: &Ray, world: &World, depth: u32, sampler: &mut dyn Sampler) -> ColorRgb {
+    let mut radiance = ColorRgb::BLACK;
+    let mut throughput = ColorRgb::WHITE;
+    let mut ray = *ray;
+    // pdf of the BSDF sample that produced `ray`. None for camera rays and specular bounces.
+    let mut bsdf_pdf: Option<Float> = None;
+
+    for bounce in 0..depth {
+      let mut hit = world.hit(&ray, FLOAT_EPSILON, Float::MAX);
+      let t_max = hit.as_ref().map_or(Float::INFINITY, |record| record.hit_t);
+      if let Some(collision) = world.sample_collision(&ray, t_max, sampler) {
+        hit = Some(collision);
+      }
+      let Some(record) = hit else {
+        // background color, or the environment and the directional lights, each of which may
+        // have been sampled as a light too.
+        for (background, light_pdf) in world.background_terms(&ray) {
+          let weight = bsdf_pdf.map_or(1.0, |pdf| power_heuristic(pdf, light_pdf));
+          radiance += throughput * background * weight;
+        }
+        break;
+      };
 
-    if let Some(record) = world.hit(ray, FLOAT_EPSILON, Float::MAX) {
       let emitted = record.material.emitted(record.mat_uv, record.point);
-      return if let Some((attenuation, scattered)) = record.material.clone().scatter(ray, &record) {
-        emitted + attenuation * Self::ray_color(&scattered, world, depth - 1)
-      } else {
-        emitted
+      let weight = match bsdf_pdf {
+        Some(pdf) => power_heuristic(pdf, world.light_pdf(&ray.origin, &ray.direction, record.object_id)),
+        None => 1.0,
       };
+      radiance += throughput * emitted * weight;
+
+      if bounce + 1 == depth {
+        break;
+      }
+      let frame = Onb::from_w(record.unit_normal);
+      let wo = frame.to_local(-ray.direction.normalize());
+      if !record.material.is_delta() {
+        radiance += throughput * Self::sample_light(&record, &frame, wo, world, sampler);
+      }
+
+      let Some(sample) = record.material.sample(&record, wo, sampler) else {
+        break;
+      };
+      if sample.pdf <= 0.0 {
+        break;
+      }
+      let cos = material::cos_factor(record.material.as_ref(), sample.wi);
+      throughput = throughput * sample.f * (cos / sample.pdf);
+      bsdf_pdf = if sample.is_delta { None } else { Some(sample.pdf) };
+      ray = record.spawn_ray(frame.to_world(sample.wi));
+    }
+    radiance
+  }
+
+  // Radiance from one explicitly sampled light, times f * cos, weighted by MIS.
+  fn sample_light(
+    record: &HitRecord,
+    frame: &Onb,
+    wo: Direction,
+    world: &World,
+    sampler: &mut dyn Sampler,
+  ) -> ColorRgb {
+    let Some(sample) = world.sample_light(&record.point, sampler) else {
+      return ColorRgb::BLACK;
+    };
+    let wi = frame.to_local(sample.direction);
+    let f = record.material.eval(record, wo, wi);
+    let scattering_pdf = record.material.pdf(record, wo, wi);
+    if scattering_pdf <= 0.0 {
+      return ColorRgb::BLACK;
     }
 
-    // background color
-    world.background_shader()(ray)
+    let origin = record.spawn_ray(sample.direction).origin;
+    let (shadow_ray, t_max) = match sample.point {
+      // aim from the offset origin, or the shadow ray may hit the light itself before `distance`.
+      Some(point) => {
+        let to_light = point - origin;
+        let distance = to_light.length();
+        (Ray::new_with_time(origin, to_light / distance, record.time), distance - RAY_EPSILON)
+      }
+      None => (Ray::new_with_time(origin, sample.direction, record.time), Float::MAX),
+    };
+    if world.hit(&shadow_ray, RAY_EPSILON, t_max).is_some() {
+      return ColorRgb::BLACK; // occluded
+    }
+    let transmittance = world.transmittance(&shadow_ray, t_max, sampler);
+    if transmittance <= 0.0 {
+      return ColorRgb::BLACK;
+    }
+    // no BSDF sample can hit a delta light, this is its only way in.
+    let weight = if sample.is_delta { 1.0 } else { power_heuristic(sample.pdf, scattering_pdf) };
+    let cos = material::cos_factor(record.material.as_ref(), wi);
+    sample.radiance * f * (cos * transmittance * weight / sample.pdf)
   }
 }
 
+// MIS weight of strategy `a` against strategy `b`, with beta = 2.
+#[inline]
+fn power_heuristic(pdf_a: Float, pdf_b: Float) -> Float {
+  let (a2, b2) = (pdf_a * pdf_a, pdf_b * pdf_b);
+  if a2 + b2 <= 0.0 { 0.0 } else { a2 / (a2 + b2) }
+}
+
 impl Renderer for SimpleRenderer {
   fn render(&self, config: RenderConfig) {
-    let RenderConfig { film, camera, filters, world } = config;
+    let RenderConfig { film, camera, filters, world, seed } = config;
 
-    let (width, height);
-    {
-      let film = film.lock().unwrap();
-      width = film.width();
-      height = film.height();
-    }
+    // The mutex is only taken to keep the film to this render until it is done: the threads
+    // never lock it, they share the film and write their tiles through `write_tile`, which takes
+    // `&self`. So the `on_tile` callback must not lock the film.
+    let mut film = film.lock().unwrap();
+    let (width, height) = (film.width(), film.height());
+    let layers = film.layers().to_vec();
+    let trace_layers = layers.iter().any(|aov| aov.needs_hit());
 
-    let bar = indicatif::ProgressBar::new(height as u64);
+    let filter = self.pixel_filter;
+    // Every sample is taken once, by the tile of its pixel, and splatted into a window around
+    // the tile as far as the filter reaches. A tile is written once all the tiles whose windows
+    // reach it are rendered, adding up their windows in grid order: every pixel sums the same
+    // samples in the same order whatever the threads, so the image does not depend on the
+    // scheduling. With the box filter of radius 0.5, the windows are the tiles.
+    let margin = ((filter.radius() + 0.5).ceil() as u32).saturating_sub(1);
+    let render_tile = |tile: film::Tile, sampler: &mut dyn Sampler| {
+      let (x0, y0) = (tile.x0 as i32 - margin as i32, tile.y0 as i32 - margin as i32);
+      let mut window = film::AccumulationBuffer::new_window(x0, y0, tile.width + 2 * margin, tile.height + 2 * margin);
+      let mut film_tile = film::FilmTile::new(tile, &layers);
+      for (x, y) in tile.pixels() {
+        let mut aovs = film::AovAccumulator::new(&layers);
 
-    let rows = (0..height)
-      .into_par_iter()
-      .map(|y| {
-        let mut rng = rand::rng();
-        let mut row = Vec::with_capacity(width as usize);
+        for index in 0..self.samples_per_pixel {
+          sampler.start_pixel_sample((x, y), index);
+          let (du, dv) = sampler.get_2d();
+          let u = (x as Float + du) / (width - 1) as Float;
+          let v = 1.0 - (y as Float + dv) / (height - 1) as Float;
 
-        for x in 0..width {
-          let mut pixel_color = ColorRgb::BLACK;
+          let ray = camera.get_ray(u, v, sampler);
+          let color = Self::ray_color(&ray, &world, self.max_depth, sampler);
+          window.add_sample(x as Float + du, y as Float + dv, color, &filter);
+          if !layers.is_empty() {
+            let record = if trace_layers { world.hit(&ray, FLOAT_EPSILON, Float::MAX) } else { None };
+            aovs.add(&ray, record.as_ref(), &world);
+          }
+        }
 
-          for _ in 0..self.samples_per_pixel {
-            let u = (x as Float + rng.random::<Float>()) / (width - 1) as Float;
-            let v = 1.0 - (y as Float + rng.random::<Float>()) / (height - 1) as Float;
+        for (aov, value) in aovs.values() {
+          film_tile.set_layer_pixel(aov, x, y, value);
+        }
+      }
+      (window, film_tile)
+    };
 
-            let ray = camera.get_ray(u, v);
-            pixel_color += Self::ray_color(&ray, &world, self.max_depth);
-          }
+    let tiles = self.tile_order.tiles(width, height, self.tile_size);
+    // the tiles on their grid, and the cells around (tx, ty) whose windows may reach it, itself
+    // included, row by row.
+    let size = self.tile_size;
+    let (columns, rows) = (width.div_ceil(size), height.div_ceil(size));
+    let reach = margin.div_ceil(size);
+    let cell = |tile: &film::Tile| (tile.x0 / size, tile.y0 / size);
+    let at = |(tx, ty): (u32, u32)| (ty * columns + tx) as usize;
+    let around = |(tx, ty): (u32, u32)| {
+      let xs = tx.saturating_sub(reach)..=(tx + reach).min(columns - 1);
+      let ys = ty.saturating_sub(reach)..=(ty + reach).min(rows - 1);
+      ys.flat_map(move |y| xs.clone().map(move |x| (x, y)))
+    };
+    // by cell: the rendered windows and tiles, how many tiles around are still to render before
+    // it is written, and how many are still to write before its window may go.
+    let windows: Vec<Mutex<Option<film::AccumulationBuffer>>> = tiles.iter().map(|_| Mutex::new(None)).collect();
+    let film_tiles: Vec<Mutex<Option<film::FilmTile>>> = tiles.iter().map(|_| Mutex::new(None)).collect();
+    let mut to_render: Vec<AtomicU32> = tiles.iter().map(|_| AtomicU32::new(0)).collect();
+    for tile in &tiles {
+      *to_render[at(cell(tile))].get_mut() = around(cell(tile)).count() as u32;
+    }
+    let to_write: Vec<AtomicU32> = to_render.iter().map(|count| AtomicU32::new(count.load(Ordering::Relaxed))).collect();
 
-          pixel_color /= self.samples_per_pixel as Float;
-          row.push(pixel_color);
+    let done = AtomicU32::new(0);
+    let bar = indicatif::ProgressBar::new((width * height) as u64);
+    let start = Instant::now();
+    let shared: &dyn Film = &*film;
+    let write = |(tx, ty): (u32, u32)| {
+      let mut film_tile = film_tiles[at((tx, ty))].lock().unwrap().take().unwrap();
+      let tile = film_tile.tile();
+      let mut sums = film::AccumulationBuffer::new_window(tile.x0 as i32, tile.y0 as i32, tile.width, tile.height);
+      for neighbour in around((tx, ty)) {
+        sums.merge(windows[at(neighbour)].lock().unwrap().as_ref().unwrap());
+      }
+      for (x, y) in tile.pixels() {
+        film_tile.set_pixel(x, y, sums.get(x - tile.x0, y - tile.y0));
+      }
+      shared.write_tile(&film_tile);
+      for neighbour in around((tx, ty)) {
+        if to_write[at(neighbour)].fetch_sub(1, Ordering::AcqRel) == 1 {
+          windows[at(neighbour)].lock().unwrap().take();
         }
-        bar.inc(1);
-        row
-      })
-      .collect::<Vec<_>>();
+      }
 
+      bar.inc((tile.width * tile.height) as u64);
+      let tiles_done = done.fetch_add(1, Ordering::Relaxed) + 1;
+      if let Some(ref callback) = self.on_tile {
+        callback(&TileInfo { tile, tiles_done, tiles_total: tiles.len() as u32, elapsed: start.elapsed() });
+      }
+    };
+
+    let next = AtomicUsize::new(0);
+    // every thread takes the tiles one at a time, in order, and writes those it is the last to
+    // render around.
+    (0..rayon::current_num_threads()).into_par_iter().for_each(|_| {
+      let mut sampler = self.sampler.seeded(seed);
+      while let Some(tile) = tiles.get(next.fetch_add(1, Ordering::Relaxed)) {
+        let (window, film_tile) = render_tile(*tile, &mut *sampler);
+        *windows[at(cell(tile))].lock().unwrap() = Some(window);
+        *film_tiles[at(cell(tile))].lock().unwrap() = Some(film_tile);
+        for neighbour in around(cell(tile)) {
+          if to_render[at(neighbour)].fetch_sub(1, Ordering::AcqRel) == 1 {
+            write(neighbour);
+          }
+        }
+      }
+    });
     bar.finish_and_clear();
 
-    let mut film = film.lock().unwrap();
-    for (y, row) in rows.iter().enumerate() {
-      for (x, pixel_color) in row.iter().enumerate() {
-        film.set_pixel(x as u32, y as u32, *pixel_color);
+    filters.process(&mut *film);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  struct CountingCamera(Arc<dyn Camera>, Arc<AtomicUsize>);
+
+  impl Camera for CountingCamera {
+    fn get_ray(&self, u: Float, v: Float, sampler: &mut dyn Sampler) -> Ray {
+      self.1.fetch_add(1, Ordering::Relaxed);
+      self.0.get_ray(u, v, sampler)
+    }
+  }
+
+  #[test]
+  fn test_tile_rendering() {
+    let world = Arc::new({
+      let mut world = World::new(|ray| ColorRgb::new(0.2, 0.4, 0.8) * (ray.direction.y + 1.0));
+      let white = material::Lambertian::new_arc(texture::SolidColorTexture::new_arc(ColorRgb::WHITE));
+      world.add_object(geometry::UnitSphere::new_arc(white));
+      world.bvh_finalize();
+      world
+    });
+    let camera: Arc<dyn Camera> = Arc::new(PerspectiveCamera::new(
+      Point::new(0.0, 0.0, 4.0),
+      Point::new(0.0, 0.0, 0.0),
+      Direction::new(0.0, 1.0, 0.0),
+      40.0,
+      1.5,
+    ));
+    let render = |renderer: SimpleRenderer| {
+      let film = Arc::new(Mutex::new(film::SimpleFilm::new(30, 20).with_layers(&[Aov::Normal])));
+      renderer.render(RenderConfig {
+        film: film.clone(),
+        camera: camera.clone(),
+        filters: Arc::new(FilterList::new()),
+        world: world.clone(),
+        seed: 0,
+      });
+      let film = film.lock().unwrap();
+      (0..600)
+        .map(|i| (film.get_pixel(i % 30, i / 30), film.get_layer_pixel(Aov::Normal, i % 30, i / 30)))
+        .collect::<Vec<_>>()
+    };
+
+    // the tiles do not show, whatever their size, order or filter.
+    let gaussian = PixelFilter::from_name("gaussian").unwrap();
+    let image = render(SimpleRenderer::new(4, 4).with_pixel_filter(gaussian).with_tile_size(30));
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let events = seen.clone();
+    let tiled = render(
+      SimpleRenderer::new(4, 4)
+        .with_pixel_filter(gaussian)
+        .with_tile_size(7)
+        .with_tile_order(film::TileOrder::Hilbert)
+        .on_tile(move |info| events.lock().unwrap().push(*info)),
+    );
+    assert!(image.iter().any(|(_, normal)| normal.unwrap().b > 0.5));
+    for (a, b) in image.iter().zip(&tiled) {
+      assert!((a.0.r - b.0.r).abs() < 1e-5 && (a.0.b - b.0.b).abs() < 1e-5, "{:?} {:?}", a, b);
+      assert_eq!(a.1, b.1);
+    }
+
+    // every sample is traced once, whatever the filter spreads across the tiles.
+    let rays = Arc::new(AtomicUsize::new(0));
+    let counted: Arc<dyn Camera> = Arc::new(CountingCamera(camera.clone(), rays.clone()));
+    let film = Arc::new(Mutex::new(film::SimpleFilm::new(30, 20)));
+    let mitchell = PixelFilter::from_name("mitchell").unwrap();
+    SimpleRenderer::new(4, 4).with_pixel_filter(mitchell).with_tile_size(4).render(RenderConfig {
+      film,
+      camera: counted,
+      filters: Arc::new(FilterList::new()),
+      world: world.clone(),
+      seed: 0,
+    });
+    assert_eq!(rays.load(Ordering::Relaxed), 30 * 20 * 4);
+
+    // one event for every tile, 5 x 3 of them.
+    let mut seen = seen.lock().unwrap();
+    seen.sort_by_key(|info| info.tiles_done);
+    assert_eq!(seen.iter().map(|info| info.tiles_done).collect::<Vec<_>>(), (1..=15).collect::<Vec<_>>());
+    assert!(seen.iter().all(|info| info.tiles_total == 15));
+    assert_eq!(seen.iter().map(|info| info.tile.width * info.tile.height).sum::<u32>(), 600);
+  }
+
+  #[test]
+  fn test_light_sampling() {
+    // a gray floor under a small light, which is either sampled (next-event estimation with MIS)
+    // or a mere emissive object only the BSDF rays find: the same image on average.
+    let camera: Arc<dyn Camera> = Arc::new(OrthographicCamera::new(
+      Point::new(0.0, 5.0, 0.0),
+      Point::new(0.0, 0.0, 0.0),
+      Direction::new(0.0, 0.0, -1.0),
+      3.0,
+      1.0,
+    ));
+    let render = |light: Arc<dyn Hittable>, sampled: bool| {
+      let mut world = World::new(|_ray| ColorRgb::BLACK);
+      let gray = material::Lambertian::new_arc(texture::SolidColorTexture::new_arc(ColorRgb::new(0.5, 0.5, 0.5)));
+      world.add_object(geometry::Instance::new_arc(
+        Arc::new(geometry::UnitQuad::new(gray)),
+        Mat4d::from_rotation_x(-PI / 2.0) * Mat4d::from_scaling(4.0, 4.0, 1.0),
+      ));
+      if sampled {
+        world.add_light(light);
+      } else {
+        world.add_object(light);
       }
+      world.bvh_finalize();
+      let film = Arc::new(Mutex::new(film::SimpleFilm::new(32, 32)));
+      SimpleRenderer::new(64, 2).render(RenderConfig {
+        film: film.clone(),
+        camera: camera.clone(),
+        filters: Arc::new(FilterList::new()),
+        world: Arc::new(world),
+        seed: 0,
+      });
+      let film = film.lock().unwrap();
+      (0..32 * 32).map(|i| film.get_pixel(i % 32, i / 32).g).sum::<Float>() / (32 * 32) as Float
+    };
+    let lamp = material::DiffusionLight::arc_from_color(ColorRgb::new(8.0, 8.0, 8.0));
+
+    let quad: Arc<dyn Hittable> = geometry::Instance::new_arc(
+      Arc::new(geometry::UnitQuad::new(lamp.clone())),
+      Mat4d::from_translation(Direction::new(0.0, 1.5, 0.0))
+        * Mat4d::from_rotation_x(PI / 2.0)
+        * Mat4d::from_scaling(0.5, 0.5, 1.0),
+    );
+    // an ellipsoid: the sphere sampled by its cone, through a transform that does not keep angles.
+    let ellipsoid: Arc<dyn Hittable> = geometry::Instance::new_arc(
+      geometry::UnitSphere::new_arc(lamp),
+      Mat4d::from_translation(Direction::new(0.5, 1.2, 0.0)) * Mat4d::from_scaling(0.3, 0.15, 0.3),
+    );
+    for light in [quad, ellipsoid.clone()] {
+      let (sampled, found) = (render(light.clone(), true), render(light, false));
+      assert!(sampled > 0.05 && (sampled - found).abs() < 0.05 * sampled, "{} {}", sampled, found);
     }
 
-    filters.process(&mut *film);
+    // the pdf of a sample is the pdf of its direction, and no sample is on the hidden side.
+    let origin = Point::new(-0.5, 0.0, 0.3);
+    let mut sampler = sampler::IndependentSampler::new(0);
+    for _ in 0..100 {
+      let (sample, pdf) = ellipsoid.sample_towards(&origin, &mut sampler).unwrap();
+      let direction = sample.point - origin;
+      assert!(sample.unit_normal.dot(direction) < 0.0);
+      let pdf_value = ellipsoid.pdf_value(&origin, &direction);
+      assert!((pdf - pdf_value).abs() < 1e-3 * pdf, "{} {}", pdf, pdf_value);
+    }
   }
 }
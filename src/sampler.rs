@@ -0,0 +1,287 @@
+use crate::prelude::*;
+
+/// Where the random numbers of a render come from.
+/// A sampler gives the numbers of one pixel sample at a time: `start_pixel_sample`, then
+/// `get_1d` / `get_2d` in the order they are needed (pixel jitter, camera, then the bounces),
+/// every call taking the next dimension(s).
+/// The values only depend on the seed, the pixel, the sample index and the dimension, never on
+/// what was drawn before, so an image does not depend on the order its pixels are rendered in:
+/// every pixel sample is a stream of its own.
+pub trait Sampler: Send + Sync {
+  /// Start sample `index` of `pixel`, from dimension 0.
+  fn start_pixel_sample(&mut self, pixel: (u32, u32), index: u32);
+  /// In [0, 1).
+  fn get_1d(&mut self) -> Float;
+  /// In [0, 1)^2.
+  fn get_2d(&mut self) -> (Float, Float);
+  /// A copy drawing from `seed` instead, for one thread of a render.
+  fn seeded(&self, seed: u64) -> Box<dyn Sampler>;
+}
+
+/// The samplers a scene can choose by name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SamplerKind {
+  #[default]
+  Independent,
+  Stratified,
+  Halton,
+  Sobol,
+}
+
+impl SamplerKind {
+  pub const ALL: [SamplerKind; 4] =
+    [SamplerKind::Independent, SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol];
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      SamplerKind::Independent => "independent",
+      SamplerKind::Stratified => "stratified",
+      SamplerKind::Halton => "halton",
+      SamplerKind::Sobol => "sobol",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Self> {
+    Self::ALL.into_iter().find(|kind| kind.name() == name)
+  }
+
+  /// The stratified sampler fits its strata to `samples_per_pixel`.
+  /// The seed is 0, renderers draw from `RenderConfig::seed` instead.
+  pub fn build(&self, samples_per_pixel: u32) -> Box<dyn Sampler> {
+    match self {
+      SamplerKind::Independent => Box::new(IndependentSampler::new(0)),
+      SamplerKind::Stratified => Box::new(StratifiedSampler::from_samples(samples_per_pixel, 0)),
+      SamplerKind::Halton => Box::new(HaltonSampler::new(0)),
+      SamplerKind::Sobol => Box::new(SobolSampler::new(0)),
+    }
+  }
+}
+
+impl std::fmt::Display for SamplerKind {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.name())
+  }
+}
+
+/// Uniform direction on the unit sphere.
+#[inline]
+pub fn sample_uniform_sphere(u: (Float, Float)) -> Vec3d {
+  let z = 1.0 - 2.0 * u.0;
+  let r = (1.0 - z * z).max(0.0).sqrt();
+  let (sin_p, cos_p) = (2.0 * PI * u.1).sin_cos();
+  Vec3d::new(r * cos_p, r * sin_p, z)
+}
+
+/// Cosine weighted direction on the hemisphere around +z.
+#[inline]
+pub fn sample_cosine_hemisphere(u: (Float, Float)) -> Vec3d {
+  let (sin_p, cos_p) = (2.0 * PI * u.0).sin_cos();
+  let r = u.1.sqrt();
+  Vec3d::new(r * cos_p, r * sin_p, (1.0 - u.1).max(0.0).sqrt())
+}
+
+/// Uniform point on the unit disk, as (x, y).
+#[inline]
+pub fn sample_uniform_disk(u: (Float, Float)) -> (Float, Float) {
+  let r = u.0.sqrt();
+  let (sin_t, cos_t) = (2.0 * PI * u.1).sin_cos();
+  (r * cos_t, r * sin_t)
+}
+
+/// Uniform barycentric coordinates (b1, b2) of a triangle.
+#[inline]
+pub fn sample_uniform_triangle(u: (Float, Float)) -> (Float, Float) {
+  let su = u.0.sqrt();
+  (su * (1.0 - u.1), su * u.1)
+}
+
+pub(crate) const ONE_MINUS_EPSILON: Float = 1.0 - Float::EPSILON / 2.0;
+
+// The finalizer of splitmix64.
+#[inline]
+fn mix_bits(mut v: u64) -> u64 {
+  v ^= v >> 31;
+  v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+  v ^= v >> 27;
+  v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+  v ^= v >> 33;
+  v
+}
+
+pub(crate) fn hash(values: &[u64]) -> u64 {
+  values.iter().fold(0x9e37_79b9_7f4a_7c15, |h, &v| mix_bits(h.rotate_left(23) ^ v))
+}
+
+// The high 24 bits of `bits`, as a Float in [0, 1).
+#[inline]
+pub(crate) fn to_unit(bits: u32) -> Float {
+  (bits >> 8) as Float / (1u32 << 24) as Float
+}
+
+// Element `i` of a random permutation of 0..len chosen by `seed`, without storing it.
+// Kensler, "Correlated Multi-Jittered Sampling".
+fn permutation_element(mut i: u32, len: u32, seed: u32) -> u32 {
+  let mut w = len - 1;
+  w |= w >> 1;
+  w |= w >> 2;
+  w |= w >> 4;
+  w |= w >> 8;
+  w |= w >> 16;
+  // cycle walking until the value is in range.
+  loop {
+    i ^= seed;
+    i = i.wrapping_mul(0xe170_893d);
+    i ^= seed >> 16;
+    i ^= (i & w) >> 4;
+    i ^= seed >> 8;
+    i = i.wrapping_mul(0x0929_eb3f);
+    i ^= seed >> 23;
+    i ^= (i & w) >> 1;
+    i = i.wrapping_mul(1 | seed >> 27);
+    i = i.wrapping_mul(0x6935_fa69);
+    i ^= (i & w) >> 11;
+    i = i.wrapping_mul(0x74dc_b303);
+    i ^= (i & w) >> 2;
+    i = i.wrapping_mul(0x9e50_1cc3);
+    i ^= (i & w) >> 2;
+    i = i.wrapping_mul(0xc860_a3df);
+    i &= w;
+    i ^= i >> 5;
+    if i < len {
+      break;
+    }
+  }
+  (i.wrapping_add(seed)) % len
+}
+
+// Owen scrambling of the bits of `v`, read as a binary fraction: every bit is flipped by a
+// random function of the bits above it. Burley, "Practical Hash-based Owen Scrambling".
+#[inline]
+fn owen_scramble(v: u32, seed: u32) -> u32 {
+  let mut x = v.reverse_bits();
+  // Laine-Karras: every bit only depends on the bits below it.
+  x = x.wrapping_add(seed);
+  x ^= x.wrapping_mul(0x6c50_b47c);
+  x ^= x.wrapping_mul(0xb82f_1e52);
+  x ^= x.wrapping_mul(0xc7af_e638);
+  x ^= x.wrapping_mul(0x8d22_f6e6);
+  x.reverse_bits()
+}
+
+mod distribution;
+mod halton;
+mod independent;
+mod sobol;
+mod stratified;
+
+pub use distribution::{Distribution1D, Distribution2D};
+pub use halton::HaltonSampler;
+pub use independent::IndependentSampler;
+pub use sobol::SobolSampler;
+pub use stratified::StratifiedSampler;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // no cell of an nx * ny grid holds more than one point.
+  fn assert_stratified(points: &[(Float, Float)], nx: usize, ny: usize, name: &str) {
+    let mut cells = vec![0; nx * ny];
+    for &(x, y) in points {
+      assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y), "{}", name);
+      cells[(y * ny as Float) as usize * nx + (x * nx as Float) as usize] += 1;
+    }
+    assert!(cells.iter().all(|&n| n <= 1), "{}: {:?}", name, cells);
+  }
+
+  #[test]
+  fn test_stratification() {
+    // (name, sampler, samples, 2D grid)
+    let samplers: [(_, Box<dyn Sampler>, usize, _); 3] = [
+      ("stratified", Box::new(StratifiedSampler::new(4, 4, 1)), 16, (4, 4)),
+      ("sobol", Box::new(SobolSampler::new(1)), 16, (4, 4)),
+      // bases 3 and 5 for the second and third dimension.
+      ("halton", Box::new(HaltonSampler::new(1)), 15, (3, 5)),
+    ];
+    for (name, mut sampler, count, (nx, ny)) in samplers {
+      for pixel in [(0, 0), (5, 9)] {
+        let (mut first, mut second) = (Vec::new(), Vec::new());
+        for index in 0..count as u32 {
+          sampler.start_pixel_sample(pixel, index);
+          first.push((sampler.get_1d(), 0.0));
+          second.push(sampler.get_2d());
+        }
+        // the first dimension is base 2 for halton too.
+        assert_stratified(&first, count.next_power_of_two(), 1, name);
+        assert_stratified(&second, nx, ny, name);
+      }
+    }
+
+    // the same pixel sample gives the same numbers, another pixel does not.
+    for kind in SamplerKind::ALL {
+      let mut sampler = kind.build(16).seeded(7);
+      let mut draw = |pixel, index| {
+        sampler.start_pixel_sample(pixel, index);
+        (sampler.get_2d(), sampler.get_1d(), sampler.get_2d())
+      };
+      let a = draw((3, 4), 2);
+      draw((1, 1), 0);
+      assert_eq!(a, draw((3, 4), 2), "{}", kind);
+      assert_ne!(a, draw((4, 3), 2), "{}", kind);
+    }
+  }
+
+  #[test]
+  fn test_deterministic_renders() {
+    let mut world = World::new(|ray| ColorRgb::new(0.2, 0.3, 0.5) * (ray.direction.y.max(0.0) + 0.2));
+    let white = material::Lambertian::new_arc(texture::SolidColorTexture::new_arc(ColorRgb::WHITE));
+    let glass = material::Dielectric::new_arc(1.5, texture::SolidColorTexture::new_arc(ColorRgb::WHITE));
+    let lamp = material::DiffusionLight::arc_from_color(ColorRgb::new(4.0, 4.0, 4.0));
+    world.add_object(geometry::UnitSphere::new_arc(white));
+    world.add_object(geometry::Instance::new_arc(
+      geometry::UnitSphere::new_arc(glass),
+      Mat4d::from_translation(Direction::new(1.2, 0.0, 1.0)) * Mat4d::from_scaling(0.5, 0.5, 0.5),
+    ));
+    world.add_light(geometry::Instance::new_arc(
+      Arc::new(geometry::UnitQuad::new(lamp)),
+      Mat4d::from_translation(Direction::new(0.0, 3.0, 0.0)) * Mat4d::from_rotation_x(PI / 2.0),
+    ));
+    world.bvh_finalize();
+    let world = Arc::new(world);
+    let camera: Arc<dyn Camera> = Arc::new(
+      PerspectiveCamera::new_thin_lens(
+        Point::new(0.0, 1.0, 5.0),
+        Point::new(0.0, 0.0, 0.0),
+        Direction::new(0.0, 1.0, 0.0),
+        40.0,
+        1.0,
+        0.2,
+        5.0,
+      )
+      .with_shutter(0.0, 1.0),
+    );
+
+    let render = |kind: SamplerKind, seed, threads| {
+      let film = Arc::new(Mutex::new(film::SimpleFilm::new(12, 12)));
+      let renderer = renderer::SimpleRenderer::new(4, 6).with_sampler(kind.build(4));
+      let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+      pool.install(|| {
+        renderer.render(RenderConfig {
+          film: film.clone(),
+          camera: camera.clone(),
+          filters: Arc::new(FilterList::new()),
+          world: world.clone(),
+          seed,
+        })
+      });
+      let film = film.lock().unwrap();
+      (0..144).map(|i| film.get_pixel(i % 12, i / 12)).collect::<Vec<_>>()
+    };
+    for kind in SamplerKind::ALL {
+      let image = render(kind, 5, 1);
+      assert!(image.iter().any(|c| c.r > 0.0), "{}", kind);
+      assert_eq!(image, render(kind, 5, 4), "{}", kind);
+      assert_ne!(image, render(kind, 6, 4), "{}", kind);
+    }
+  }
+}
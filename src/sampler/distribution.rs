@@ -0,0 +1,84 @@
+use crate::prelude::*;
+use crate::sampler::ONE_MINUS_EPSILON;
+
+/// Piecewise constant distribution over [0, 1), proportional to `func` (taken absolute).
+/// All zero is sampled uniformly.
+pub struct Distribution1D {
+  func: Vec<Float>,
+  // cdf[i] is the probability of the pieces before i, cdf[len] = 1.
+  cdf: Vec<Float>,
+  integral: Float,
+}
+
+impl Distribution1D {
+  pub fn new(func: &[Float]) -> Self {
+    assert!(!func.is_empty(), "Distribution1D: no pieces");
+    let len = func.len();
+    let func: Vec<Float> = func.iter().map(|f| f.abs()).collect();
+    let mut cdf = vec![0.0; len + 1];
+    for i in 0..len {
+      cdf[i + 1] = cdf[i] + func[i] / len as Float;
+    }
+    let integral = cdf[len];
+    for (i, c) in cdf.iter_mut().enumerate() {
+      *c = if integral > 0.0 { *c / integral } else { i as Float / len as Float };
+    }
+    Self { func, cdf, integral }
+  }
+
+  /// Integral of the function over [0, 1).
+  pub fn integral(&self) -> Float {
+    self.integral
+  }
+
+  /// A point in [0, 1) and its pdf, and the piece it is in.
+  pub fn sample(&self, u: Float) -> (Float, Float, usize) {
+    let len = self.func.len();
+    // the last piece starting at or before `u`, skipping the empty ones.
+    let offset = (self.cdf.partition_point(|&c| c <= u).max(1) - 1).min(len - 1);
+    let width = self.cdf[offset + 1] - self.cdf[offset];
+    let du = if width > 0.0 { ((u - self.cdf[offset]) / width).clamp(0.0, 1.0) } else { 0.0 };
+    let x = ((offset as Float + du) / len as Float).min(ONE_MINUS_EPSILON);
+    (x, self.pdf(x), offset)
+  }
+
+  /// pdf of `sample` returning `x`.
+  pub fn pdf(&self, x: Float) -> Float {
+    let idx = ((x * self.func.len() as Float) as usize).min(self.func.len() - 1);
+    if self.integral > 0.0 { self.func[idx] / self.integral } else { 1.0 }
+  }
+}
+
+/// Piecewise constant distribution over [0, 1)^2, proportional to `func`, a `nu` x `nv` grid
+/// stored row by row. A row is picked by its integral first, then a point in it.
+pub struct Distribution2D {
+  // one distribution for every row.
+  conditional: Vec<Distribution1D>,
+  marginal: Distribution1D,
+}
+
+impl Distribution2D {
+  pub fn new(func: &[Float], nu: usize, nv: usize) -> Self {
+    assert_eq!(func.len(), nu * nv, "Distribution2D: expected {} x {} values", nu, nv);
+    let conditional: Vec<_> = func.chunks(nu).map(Distribution1D::new).collect();
+    let marginal = Distribution1D::new(&conditional.iter().map(|row| row.integral()).collect::<Vec<_>>());
+    Self { conditional, marginal }
+  }
+
+  pub fn integral(&self) -> Float {
+    self.marginal.integral()
+  }
+
+  /// A point (u, v) in [0, 1)^2 and its pdf.
+  pub fn sample(&self, u: (Float, Float)) -> ((Float, Float), Float) {
+    let (v, pdf_v, row) = self.marginal.sample(u.1);
+    let (u, pdf_u, _) = self.conditional[row].sample(u.0);
+    ((u, v), pdf_u * pdf_v)
+  }
+
+  /// pdf of `sample` returning `point`.
+  pub fn pdf(&self, point: (Float, Float)) -> Float {
+    let row = ((point.1 * self.conditional.len() as Float) as usize).min(self.conditional.len() - 1);
+    self.conditional[row].pdf(point.0) * self.marginal.pdf(point.1)
+  }
+}
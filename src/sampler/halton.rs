@@ -0,0 +1,69 @@
+use crate::prelude::*;
+use crate::sampler::{ONE_MINUS_EPSILON, hash, mix_bits, permutation_element, to_unit};
+
+// bases of the dimensions, the later dimensions are independent random numbers.
+const PRIMES: [u32; 64] = [
+  2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+  101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+  197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+  311,
+];
+
+/// The Halton sequence, one per pixel, with the digits of every dimension scrambled by random
+/// permutations (which keeps its stratification).
+/// Dimension `d` is the radical inverse in the `d`th prime base, past the 64th dimension the
+/// numbers are independent.
+#[derive(Clone)]
+pub struct HaltonSampler {
+  seed: u64,
+  pixel_hash: u64,
+  index: u32,
+  dimension: usize,
+}
+
+impl HaltonSampler {
+  pub fn new(seed: u64) -> Self {
+    Self { seed, pixel_hash: 0, index: 0, dimension: 0 }
+  }
+}
+
+// The digits of `a` in `base`, mirrored around the radix point, each permuted by a function of
+// the digits before it. The digits past those of `a` (zeros) are permuted too, until the
+// precision of Float.
+fn scrambled_radical_inverse(base: u32, mut a: u64, seed: u64) -> Float {
+  let inv_base = 1.0 / base as Float;
+  let mut inv_base_m: Float = 1.0;
+  let mut reversed: u64 = 0;
+  while 1.0 - (base - 1) as Float * inv_base_m < 1.0 {
+    let next = a / base as u64;
+    let digit = (a - next * base as u64) as u32;
+    let digit = permutation_element(digit, base, mix_bits(seed ^ reversed) as u32);
+    reversed = reversed * base as u64 + digit as u64;
+    inv_base_m *= inv_base;
+    a = next;
+  }
+  (inv_base_m * reversed as Float).min(ONE_MINUS_EPSILON)
+}
+
+impl Sampler for HaltonSampler {
+  fn start_pixel_sample(&mut self, pixel: (u32, u32), index: u32) {
+    self.pixel_hash = hash(&[self.seed, pixel.0 as u64, pixel.1 as u64]);
+    self.index = index;
+    self.dimension = 0;
+  }
+  fn get_1d(&mut self) -> Float {
+    let seed = hash(&[self.pixel_hash, self.dimension as u64]);
+    let value = match PRIMES.get(self.dimension) {
+      Some(&base) => scrambled_radical_inverse(base, self.index as u64, seed),
+      None => to_unit((mix_bits(seed ^ self.index as u64) >> 32) as u32),
+    };
+    self.dimension += 1;
+    value
+  }
+  fn get_2d(&mut self) -> (Float, Float) {
+    (self.get_1d(), self.get_1d())
+  }
+  fn seeded(&self, seed: u64) -> Box<dyn Sampler> {
+    Box::new(Self { seed, ..self.clone() })
+  }
+}
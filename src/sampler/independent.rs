@@ -0,0 +1,34 @@
+use crate::prelude::*;
+use crate::sampler::{hash, mix_bits, to_unit};
+
+/// Uniform random numbers, as white noise.
+#[derive(Clone)]
+pub struct IndependentSampler {
+  seed: u64,
+  state: u64,
+  dimension: u64,
+}
+
+impl IndependentSampler {
+  pub fn new(seed: u64) -> Self {
+    Self { seed, state: 0, dimension: 0 }
+  }
+}
+
+impl Sampler for IndependentSampler {
+  fn start_pixel_sample(&mut self, pixel: (u32, u32), index: u32) {
+    self.state = hash(&[self.seed, pixel.0 as u64, pixel.1 as u64, index as u64]);
+    self.dimension = 0;
+  }
+  fn get_1d(&mut self) -> Float {
+    let bits = mix_bits(self.state ^ self.dimension.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    self.dimension += 1;
+    to_unit((bits >> 32) as u32)
+  }
+  fn get_2d(&mut self) -> (Float, Float) {
+    (self.get_1d(), self.get_1d())
+  }
+  fn seeded(&self, seed: u64) -> Box<dyn Sampler> {
+    Box::new(Self { seed, ..self.clone() })
+  }
+}
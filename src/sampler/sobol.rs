@@ -0,0 +1,63 @@
+use crate::prelude::*;
+use crate::sampler::{hash, owen_scramble, to_unit};
+
+/// Owen scrambled Sobol points. Every dimension pair is the 2D Sobol sequence with its own
+/// scrambling, and the sample index is shuffled per pair so the pairs are not correlated
+/// ("padding", Burley 2020). Best with power of 2 samples per pixel.
+#[derive(Clone)]
+pub struct SobolSampler {
+  seed: u64,
+  pixel_hash: u64,
+  index: u32,
+  dimension: u64,
+}
+
+impl SobolSampler {
+  pub fn new(seed: u64) -> Self {
+    Self { seed, pixel_hash: 0, index: 0, dimension: 0 }
+  }
+
+  // (shuffled index, seeds of the two coordinates) of the next dimension.
+  fn next_dimension(&mut self) -> (u32, u32, u32) {
+    let h = hash(&[self.pixel_hash, self.dimension]);
+    self.dimension += 1;
+    // scrambling the index like a value permutes every aligned block of 2^k indices, which
+    // stays a (0, k, 2)-net.
+    let index = owen_scramble(self.index, h as u32);
+    (index, (h >> 32) as u32, (h >> 16) as u32 ^ 0x5bd1_e995)
+  }
+}
+
+// The first two dimensions of the Sobol sequence: van der Corput, and the one by x + 1.
+#[inline]
+fn sobol_2d(index: u32) -> (u32, u32) {
+  let (mut i, mut v, mut y) = (index, 1u32 << 31, 0);
+  while i != 0 {
+    if i & 1 != 0 {
+      y ^= v;
+    }
+    i >>= 1;
+    v ^= v >> 1;
+  }
+  (index.reverse_bits(), y)
+}
+
+impl Sampler for SobolSampler {
+  fn start_pixel_sample(&mut self, pixel: (u32, u32), index: u32) {
+    self.pixel_hash = hash(&[self.seed, pixel.0 as u64, pixel.1 as u64]);
+    self.index = index;
+    self.dimension = 0;
+  }
+  fn get_1d(&mut self) -> Float {
+    let (index, seed, _) = self.next_dimension();
+    to_unit(owen_scramble(index.reverse_bits(), seed))
+  }
+  fn get_2d(&mut self) -> (Float, Float) {
+    let (index, seed_x, seed_y) = self.next_dimension();
+    let (x, y) = sobol_2d(index);
+    (to_unit(owen_scramble(x, seed_x)), to_unit(owen_scramble(y, seed_y)))
+  }
+  fn seeded(&self, seed: u64) -> Box<dyn Sampler> {
+    Box::new(Self { seed, ..self.clone() })
+  }
+}
@@ -0,0 +1,64 @@
+use crate::prelude::*;
+use crate::sampler::{ONE_MINUS_EPSILON, hash, mix_bits, permutation_element, to_unit};
+
+/// Jittered samples: every dimension is cut into strata, and the samples of a pixel visit the
+/// strata in a random order, one random point in each.
+/// 1D dimensions have `x_strata * y_strata` strata, 2D ones a grid of `x_strata` by `y_strata`.
+/// Samples past the count of strata start another round in a new order.
+#[derive(Clone)]
+pub struct StratifiedSampler {
+  x_strata: u32,
+  y_strata: u32,
+  seed: u64,
+  pixel: (u32, u32),
+  index: u32,
+  dimension: u64,
+}
+
+impl StratifiedSampler {
+  pub fn new(x_strata: u32, y_strata: u32, seed: u64) -> Self {
+    assert!(x_strata > 0 && y_strata > 0, "StratifiedSampler: needs at least 1 stratum");
+    Self { x_strata, y_strata, seed, pixel: (0, 0), index: 0, dimension: 0 }
+  }
+
+  /// The squarest grid of strata with at most `samples_per_pixel` cells.
+  pub fn from_samples(samples_per_pixel: u32, seed: u64) -> Self {
+    let samples = samples_per_pixel.max(1);
+    let x_strata = (samples as f64).sqrt() as u32;
+    Self::new(x_strata, samples / x_strata, seed)
+  }
+
+  // (stratum, jitter bits) of the current sample in the current dimension.
+  fn next_stratum(&mut self) -> (u32, u64) {
+    let count = self.x_strata * self.y_strata;
+    let round = (self.index / count) as u64;
+    let h = hash(&[self.seed, self.pixel.0 as u64, self.pixel.1 as u64, self.dimension, round]);
+    self.dimension += 1;
+    let stratum = permutation_element(self.index % count, count, h as u32);
+    (stratum, mix_bits(h ^ self.index as u64))
+  }
+}
+
+impl Sampler for StratifiedSampler {
+  fn start_pixel_sample(&mut self, pixel: (u32, u32), index: u32) {
+    self.pixel = pixel;
+    self.index = index;
+    self.dimension = 0;
+  }
+  fn get_1d(&mut self) -> Float {
+    let (stratum, jitter) = self.next_stratum();
+    let count = (self.x_strata * self.y_strata) as Float;
+    ((stratum as Float + to_unit(jitter as u32)) / count).min(ONE_MINUS_EPSILON)
+  }
+  fn get_2d(&mut self) -> (Float, Float) {
+    let (stratum, jitter) = self.next_stratum();
+    let (x, y) = (stratum % self.x_strata, stratum / self.x_strata);
+    (
+      ((x as Float + to_unit(jitter as u32)) / self.x_strata as Float).min(ONE_MINUS_EPSILON),
+      ((y as Float + to_unit((jitter >> 32) as u32)) / self.y_strata as Float).min(ONE_MINUS_EPSILON),
+    )
+  }
+  fn seeded(&self, seed: u64) -> Box<dyn Sampler> {
+    Box::new(Self { seed, ..self.clone() })
+  }
+}
@@ -0,0 +1,1168 @@
+use crate::prelude::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+mod description;
+
+pub use description::*;
+
+// Width of the environment map a sky is tabulated into, the height is half of it.
+const SKY_WIDTH: u32 = 1024;
+// The largest film a scene may ask for, 16384 x 16384: a few gigabytes with the layers.
+const MAX_FILM_PIXELS: u64 = 1 << 28;
+
+/// A scene loaded from a scene file (TOML), ready to be rendered.
+///
+/// ```toml
+/// [film]
+/// width = 400
+/// aspect_ratio = 1.7778
+///
+/// [camera]
+/// type = "perspective"
+/// look_from = [0, 1, 5]
+/// look_at = [0, 0, 0]
+/// vfov = 40
+///
+/// [materials.red]
+/// type = "lambertian"
+/// albedo = [0.8, 0.1, 0.1]
+///
+/// [[objects]]
+/// type = "sphere"
+/// material = "red"
+/// transform = [{ translate = [0, 0, 0] }, { scale = 0.5 }]
+/// ```
+/// See `scenes/` for complete examples.
+pub struct Scene {
+  pub config: RenderConfig,
+  pub renderer: renderer::SimpleRenderer,
+  /// Where the scene wants the image to be saved, already resolved against the scene file.
+  pub output: Option<PathBuf>,
+  pub stats: SceneStats,
+}
+
+/// What a scene is made of, counted while it is built.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SceneStats {
+  pub spheres: usize,
+  pub quads: usize,
+  pub cubes: usize,
+  pub meshes: usize,
+  pub triangles: usize,
+  /// Objects sampled explicitly by the renderer.
+  pub lights: usize,
+  /// Point, spot and directional lights.
+  pub light_sources: usize,
+  /// Objects made of a participating medium.
+  pub media: usize,
+}
+
+impl SceneStats {
+  pub fn primitives(&self) -> usize {
+    self.spheres + self.quads + self.cubes + self.triangles
+  }
+}
+
+impl Scene {
+  pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SceneError> {
+    let path = path.as_ref();
+    let source = std::fs::read_to_string(path)
+      .map_err(|e| SceneError::Io { path: path.to_path_buf(), source: e })?;
+    let description = SceneDescription::parse(&source, path)?;
+    description.build(&source, path)
+  }
+}
+
+impl SceneDescription {
+  /// `path` is only used in error messages.
+  pub fn parse(source: &str, path: &Path) -> Result<Self, SceneError> {
+    toml::from_str(source).map_err(|e| {
+      let line = e.span().map(|span| line_of(source, span.start));
+      SceneError::Parse { path: path.to_path_buf(), line, message: e.message().to_string() }
+    })
+  }
+
+  /// Resolve all the names and build the scene.
+  /// `source` is the text the description is parsed from, used to find lines for errors.
+  /// Relative paths are resolved against the directory of `path`.
+  pub fn build(&self, source: &str, path: &Path) -> Result<Scene, SceneError> {
+    SceneBuilder {
+      desc: self,
+      source,
+      path,
+      base_dir: path.parent().unwrap_or(Path::new("")),
+      textures: HashMap::new(),
+      materials: HashMap::new(),
+      resolving: Vec::new(),
+      meshes: HashMap::new(),
+      stats: SceneStats::default(),
+    }
+    .build()
+  }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+  Io { path: PathBuf, source: std::io::Error },
+  /// Syntax errors, missing keys, wrong types...
+  Parse { path: PathBuf, line: Option<usize>, message: String },
+  /// The file is well-formed, but refers to things that do not exist or makes no sense.
+  Invalid { path: PathBuf, line: Option<usize>, field: String, message: String },
+}
+
+impl std::fmt::Display for SceneError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let location = |path: &Path, line: &Option<usize>| match line {
+      Some(line) => format!("{}:{}", path.display(), line),
+      None => path.display().to_string(),
+    };
+    match self {
+      SceneError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+      SceneError::Parse { path, line, message } => {
+        write!(f, "{}: {}", location(path, line), message.trim_end())
+      }
+      SceneError::Invalid { path, line, field, message } => {
+        write!(f, "{}: {}: {}", location(path, line), field, message)
+      }
+    }
+  }
+}
+
+impl std::error::Error for SceneError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      SceneError::Io { source, .. } => Some(source),
+      _ => None,
+    }
+  }
+}
+
+// 1-based line of the byte offset `pos`.
+fn line_of(source: &str, pos: usize) -> usize {
+  source[..pos.min(source.len())].matches('\n').count() + 1
+}
+
+struct SceneBuilder<'a> {
+  desc: &'a SceneDescription,
+  source: &'a str,
+  path: &'a Path,
+  base_dir: &'a Path,
+  textures: HashMap<String, Arc<dyn Texture>>,
+  // (material, is emissive)
+  materials: HashMap<String, (Arc<dyn Material>, bool)>,
+  // texture names being resolved, for detecting cycles.
+  resolving: Vec<String>,
+  // loaded meshes by (path, material), for instancing.
+  meshes: HashMap<(PathBuf, Option<String>), Arc<MeshAccel>>,
+  stats: SceneStats,
+}
+
+impl SceneBuilder<'_> {
+  fn error(&self, span: std::ops::Range<usize>, field: String, message: String) -> SceneError {
+    SceneError::Invalid {
+      path: self.path.to_path_buf(),
+      line: Some(line_of(self.source, span.start)),
+      field,
+      message,
+    }
+  }
+
+  fn build(mut self) -> Result<Scene, SceneError> {
+    let desc = self.desc;
+
+    let film_desc = desc.film.get_ref();
+    let width = film_desc.width;
+    let height = match (film_desc.height, film_desc.aspect_ratio) {
+      (Some(height), _) => height,
+      (None, Some(ratio)) if ratio.is_finite() && ratio > 0.0 => (width as Float / ratio) as u32,
+      (None, Some(ratio)) => {
+        let message = format!("aspect_ratio must be a positive number, got {}", ratio);
+        return Err(self.error(desc.film.span(), "film.aspect_ratio".to_string(), message));
+      }
+      (None, None) => (width as Float / (16.0 / 9.0)) as u32,
+    };
+    if width < 2 || height < 2 {
+      let message = format!("film must be at least 2x2, got {}x{}", width, height);
+      return Err(self.error(desc.film.span(), "film".to_string(), message));
+    }
+    // `as u32` saturates, so a tiny aspect ratio ends up here too.
+    if (width as u64).checked_mul(height as u64).is_none_or(|pixels| pixels > MAX_FILM_PIXELS) {
+      let message = format!("film of {}x{} is too large, at most {} pixels", width, height, MAX_FILM_PIXELS);
+      return Err(self.error(desc.film.span(), "film".to_string(), message));
+    }
+    let aspect_ratio = width as Float / height as Float;
+    let mut aovs = Vec::with_capacity(film_desc.aovs.len());
+    for name in &film_desc.aovs {
+      let Some(aov) = Aov::from_name(name.get_ref()) else {
+        let names: Vec<&str> = Aov::ALL.iter().map(|aov| aov.name()).collect();
+        let message = format!("unknown AOV '{}', expected one of {}", name, names.join(", "));
+        return Err(self.error(name.span(), "film.aovs".to_string(), message));
+      };
+      aovs.push(aov);
+    }
+    let film = film::SimpleFilm::new(width, height).with_layers(&aovs);
+    let sampler = match desc.renderer.sampler {
+      None => sampler::SamplerKind::default(),
+      Some(ref name) => sampler::SamplerKind::from_name(name.get_ref()).ok_or_else(|| {
+        let names: Vec<&str> = sampler::SamplerKind::ALL.iter().map(|kind| kind.name()).collect();
+        let message = format!("unknown sampler '{}', expected one of {}", name, names.join(", "));
+        self.error(name.span(), "renderer.sampler".to_string(), message)
+      })?,
+    };
+    let pixel_filter = match desc.renderer.pixel_filter {
+      None => PixelFilter::default(),
+      Some(ref filter) => self.pixel_filter(filter)?,
+    };
+
+    let camera: Arc<dyn Camera> = match *desc.camera.get_ref() {
+      CameraDesc::Perspective {
+        look_from,
+        look_at,
+        vup,
+        vfov,
+        aperture,
+        focus_distance,
+        blades,
+        blade_rotation,
+        shutter,
+      } => {
+        let invalid = |message: &str| {
+          let span = desc.camera.span();
+          Err(self.error(span, "camera".to_string(), message.to_string()))
+        };
+        if aperture < 0.0 {
+          return invalid("aperture must not be negative");
+        }
+        if focus_distance.is_some_and(|d| d <= 0.0) {
+          return invalid("focus_distance must be positive");
+        }
+        if blades.is_some_and(|n| n < 3) {
+          return invalid("an aperture needs at least 3 blades");
+        }
+        if shutter[0] > shutter[1] {
+          return invalid("the shutter must open before it closes");
+        }
+        let (look_from, look_at) = (to_vec(look_from), to_vec(look_at));
+        let focus_distance = focus_distance.unwrap_or_else(|| (look_at - look_from).length());
+        let shape = match blades {
+          Some(blades) => camera::ApertureShape::Polygon { blades, rotation: blade_rotation },
+          None => camera::ApertureShape::Disk,
+        };
+        Arc::new(
+          PerspectiveCamera::new_thin_lens(
+            look_from,
+            look_at,
+            to_vec(vup),
+            vfov,
+            aspect_ratio,
+            aperture,
+            focus_distance,
+          )
+          .with_aperture_shape(shape)
+          .with_shutter(shutter[0], shutter[1]),
+        )
+      }
+      CameraDesc::Orthographic { look_from, look_at, vup, height, shutter } => {
+        if shutter[0] > shutter[1] {
+          let message = "the shutter must open before it closes".to_string();
+          return Err(self.error(desc.camera.span(), "camera".to_string(), message));
+        }
+        Arc::new(
+          OrthographicCamera::new(to_vec(look_from), to_vec(look_at), to_vec(vup), height, aspect_ratio)
+            .with_shutter(shutter[0], shutter[1]),
+        )
+      }
+    };
+
+    let mut filters = FilterList::new();
+    for filter in &desc.filters {
+      let filter: Arc<dyn Filter> = match *filter.get_ref() {
+        FilterDesc::Exposure { exposure } => Arc::new(filter::ExposureFilter::new(exposure)),
+        FilterDesc::Aces => Arc::new(filter::AcesFilmicFilter),
+        FilterDesc::Vignette { intensity } => Arc::new(filter::VignetteFilter::new(intensity)),
+        FilterDesc::Gamma { gamma } => Arc::new(filter::GammaFilter::new(gamma)),
+        FilterDesc::Reinhard => Arc::new(filter::ReinhardFilter),
+      };
+      filters.add_filter(filter);
+    }
+
+    let mut world = match desc.background.as_ref().map(|b| b.get_ref()) {
+      None | Some(BackgroundDesc::Default) => World::default(),
+      Some(&BackgroundDesc::Sky { elevation, azimuth, turbidity, ground_albedo, intensity, sun_radius }) => {
+        let span = desc.background.as_ref().unwrap().span();
+        if !(0.0..=90.0).contains(&elevation) {
+          let message = format!("the sun shall be between 0 and 90 degrees high, got {}", elevation);
+          return Err(self.error(span, "background.elevation".to_string(), message));
+        }
+        if !light::PreethamSky::TURBIDITY.contains(&turbidity) {
+          let (min, max) = light::PreethamSky::TURBIDITY.into_inner();
+          let message = format!("turbidity shall be in [{}, {}], got {}", min, max, turbidity);
+          return Err(self.error(span, "background.turbidity".to_string(), message));
+        }
+        let sky = light::PreethamSky::new(
+          elevation.to_radians(),
+          azimuth.to_radians(),
+          turbidity,
+          to_color(ground_albedo),
+        );
+        let sun_radius = (sun_radius > 0.0).then(|| sun_radius.to_radians());
+        let mut world = World::default();
+        world.set_environment(sky.environment(SKY_WIDTH, SKY_WIDTH / 2, sun_radius).with_scale(intensity));
+        world
+      }
+      Some(BackgroundDesc::Environment { path, intensity, transform }) => {
+        let span = desc.background.as_ref().unwrap().span();
+        let mut world = World::default();
+        world.set_environment(self.environment(path, *intensity, transform, span)?);
+        world
+      }
+      Some(&BackgroundDesc::Solid { color }) => {
+        let color = to_color(color);
+        World::new(move |_ray| color)
+      }
+      Some(&BackgroundDesc::Gradient { bottom, top }) => {
+        let (bottom, top) = (to_color(bottom), to_color(top));
+        World::new(move |ray| {
+          let t = 0.5 * (ray.direction.normalize().y + 1.0);
+          ColorRgb::lerp(bottom, top, t)
+        })
+      }
+    };
+
+    for (idx, object) in desc.objects.iter().enumerate() {
+      self.add_object(&mut world, idx, object)?;
+    }
+    for (idx, light) in desc.lights.iter().enumerate() {
+      world.add_light_source(self.light_source(idx, light)?);
+    }
+    if let Some(ref fog) = desc.fog {
+      let MediumDesc { density, albedo, g } = *fog.get_ref();
+      world.set_medium(self.medium(density, albedo, g, fog.span(), "fog")?);
+    }
+    world.bvh_finalize();
+    self.stats.lights = world.lights().len();
+    self.stats.light_sources = world.light_sources().len();
+
+    Ok(Scene {
+      config: RenderConfig {
+        film: Arc::new(Mutex::new(film)),
+        camera,
+        filters: Arc::new(filters),
+        world: Arc::new(world),
+        seed: desc.renderer.seed,
+      },
+      renderer: renderer::SimpleRenderer::new(
+        desc.renderer.samples_per_pixel,
+        desc.renderer.max_depth,
+      )
+      .with_sampler(sampler.build(desc.renderer.samples_per_pixel))
+      .with_pixel_filter(pixel_filter),
+      output: film_desc.output.as_ref().map(|output| self.base_dir.join(output)),
+      stats: self.stats,
+    })
+  }
+
+  fn pixel_filter(&self, desc: &toml::Spanned<PixelFilterDesc>) -> Result<PixelFilter, SceneError> {
+    let invalid = |message: String| self.error(desc.span(), "renderer.pixel_filter".to_string(), message);
+    let or = |value: Option<Float>, default: Float| value.unwrap_or(default);
+    let filter = match (*desc.get_ref(), PixelFilter::from_name(desc.get_ref().name()).unwrap()) {
+      (PixelFilterDesc::Box { radius }, PixelFilter::Box { radius: r }) => PixelFilter::Box { radius: or(radius, r) },
+      (PixelFilterDesc::Tent { radius }, PixelFilter::Tent { radius: r }) => PixelFilter::Tent { radius: or(radius, r) },
+      (PixelFilterDesc::Gaussian { radius, sigma }, PixelFilter::Gaussian { radius: r, sigma: s }) => {
+        if sigma.is_some_and(|sigma| sigma <= 0.0) {
+          return Err(invalid("sigma must be positive".to_string()));
+        }
+        PixelFilter::Gaussian { radius: or(radius, r), sigma: or(sigma, s) }
+      }
+      (PixelFilterDesc::Mitchell { radius, b, c }, PixelFilter::Mitchell { radius: r, b: b0, c: c0 }) => {
+        PixelFilter::Mitchell { radius: or(radius, r), b: or(b, b0), c: or(c, c0) }
+      }
+      (PixelFilterDesc::Lanczos { radius }, PixelFilter::Lanczos { radius: r }) => PixelFilter::Lanczos { radius: or(radius, r) },
+      _ => unreachable!("the defaults are of the same type"),
+    };
+    if filter.radius() <= 0.0 {
+      return Err(invalid(format!("radius must be positive, got {}", filter.radius())));
+    }
+    Ok(filter)
+  }
+
+  fn light_source(&self, idx: usize, desc: &toml::Spanned<LightDesc>) -> Result<Arc<dyn Light>, SceneError> {
+    let field = format!("lights[{}]", idx);
+    let light: Arc<dyn Light> = match *desc.get_ref() {
+      LightDesc::Point { position, intensity } => light::PointLight::new_arc(to_vec(position), to_color(intensity)),
+      LightDesc::Spot { position, look_at, intensity, cone_angle, falloff_start } => {
+        let falloff_start = falloff_start.unwrap_or(cone_angle);
+        if !(0.0..=180.0).contains(&cone_angle) || !(0.0..=cone_angle).contains(&falloff_start) {
+          let message = format!(
+            "expected 0 <= falloff_start <= cone_angle <= 180, got {} and {}",
+            falloff_start, cone_angle
+          );
+          return Err(self.error(desc.span(), field + ".cone_angle", message));
+        }
+        let axis = to_vec(look_at) - to_vec(position);
+        if axis.length_squared() == 0.0 {
+          let message = "the light looks at its own position".to_string();
+          return Err(self.error(desc.span(), field + ".look_at", message));
+        }
+        Arc::new(light::SpotLight::new(
+          to_vec(position),
+          axis,
+          to_color(intensity),
+          cone_angle.to_radians(),
+          falloff_start.to_radians(),
+        ))
+      }
+      LightDesc::Directional { direction, irradiance, angular_diameter } => {
+        if to_vec(direction).length_squared() == 0.0 {
+          return Err(self.error(desc.span(), field + ".direction", "the direction is zero".to_string()));
+        }
+        if !(0.0..180.0).contains(&angular_diameter) {
+          let message = format!("expected an angular diameter in [0, 180), got {}", angular_diameter);
+          return Err(self.error(desc.span(), field + ".angular_diameter", message));
+        }
+        Arc::new(light::DirectionalLight::new(to_vec(direction), to_color(irradiance), angular_diameter.to_radians()))
+      }
+    };
+    Ok(light)
+  }
+
+  fn environment(
+    &self,
+    path: &str,
+    intensity: Float,
+    transform: &[TransformDesc],
+    span: std::ops::Range<usize>,
+  ) -> Result<light::EnvironmentLight, SceneError> {
+    let full_path = self.base_dir.join(path);
+    let environment = light::EnvironmentLight::load(&full_path).map_err(|e| {
+      let message = format!("cannot load image {}: {}", full_path.display(), e);
+      self.error(span.clone(), "background.path".to_string(), message)
+    })?;
+    let rotation = to_transform(transform);
+    // the axes stay unit and orthogonal, and keep their handedness.
+    let axes = [Direction::new(1.0, 0.0, 0.0), Direction::new(0.0, 1.0, 0.0), Direction::new(0.0, 0.0, 1.0)]
+      .map(|axis| rotation.transform_vector(axis));
+    let is_rotation = (rotation.linear_determinant() - 1.0).abs() < 1e-3
+      && axes.iter().all(|axis| (axis.length() - 1.0).abs() < 1e-3)
+      && axes[0].dot(axes[1]).abs() < 1e-3
+      && axes[1].dot(axes[2]).abs() < 1e-3
+      && axes[0].dot(axes[2]).abs() < 1e-3;
+    if !is_rotation {
+      let message = "the environment can only be rotated".to_string();
+      return Err(self.error(span, "background.transform".to_string(), message));
+    }
+    Ok(environment.with_rotation(rotation).with_scale(intensity))
+  }
+
+  fn texture(
+    &mut self,
+    tex_ref: &TextureRef,
+    span: std::ops::Range<usize>,
+    field: &str,
+  ) -> Result<Arc<dyn Texture>, SceneError> {
+    let name = match tex_ref {
+      TextureRef::Color(color) => return Ok(texture::SolidColorTexture::new_arc(to_color(*color))),
+      TextureRef::Name(name) => name,
+    };
+    if let Some(tex) = self.textures.get(name) {
+      return Ok(tex.clone());
+    }
+    let Some(desc) = self.desc.textures.get(name) else {
+      return Err(self.error(span, field.to_string(), format!("unknown texture '{}'", name)));
+    };
+    let span = desc.span();
+    let field = format!("textures.{}", name);
+    if self.resolving.contains(name) {
+      return Err(self.error(span, field, "textures refer to each other in a cycle".to_string()));
+    }
+    self.resolving.push(name.clone());
+
+    let tex: Arc<dyn Texture> = match desc.get_ref() {
+      TextureDesc::Solid { color } => texture::SolidColorTexture::new_arc(to_color(*color)),
+      TextureDesc::Image { path, srgb } => {
+        let full_path = self.base_dir.join(path);
+        match texture::ImageTexture::load(&full_path, *srgb) {
+          Ok(tex) => Arc::new(tex),
+          Err(e) => {
+            let message = format!("cannot load image {}: {}", full_path.display(), e);
+            return Err(self.error(span, field + ".path", message));
+          }
+        }
+      }
+      TextureDesc::Noise { scale, seed } => Arc::new(texture::PerlinNoiseTexture::new(*scale, *seed)),
+      TextureDesc::UvChecker { scale, even, odd } => {
+        let even = self.texture(even, span.clone(), &(field.clone() + ".even"))?;
+        let odd = self.texture(odd, span.clone(), &(field.clone() + ".odd"))?;
+        texture::UVCheckerTexture::new_arc(scale[0], scale[1], even, odd)
+      }
+      TextureDesc::SpatialChecker { scale, even, odd } => {
+        let even = self.texture(even, span.clone(), &(field.clone() + ".even"))?;
+        let odd = self.texture(odd, span.clone(), &(field.clone() + ".odd"))?;
+        Arc::new(texture::SpatialCheckerTexture::new(scale[0], scale[1], scale[2], even, odd))
+      }
+    };
+
+    self.resolving.pop();
+    self.textures.insert(name.clone(), tex.clone());
+    Ok(tex)
+  }
+
+  // returns (material, is emissive)
+  fn material(
+    &mut self,
+    name: &str,
+    span: std::ops::Range<usize>,
+    field: &str,
+  ) -> Result<(Arc<dyn Material>, bool), SceneError> {
+    if let Some(mat) = self.materials.get(name) {
+      return Ok(mat.clone());
+    }
+    let Some(desc) = self.desc.materials.get(name) else {
+      return Err(self.error(span, field.to_string(), format!("unknown material '{}'", name)));
+    };
+    let span = desc.span();
+    let field = format!("materials.{}", name);
+    let mat: (Arc<dyn Material>, bool) = match desc.get_ref() {
+      MaterialDesc::Lambertian { albedo } => {
+        let albedo = self.texture(albedo, span, &(field + ".albedo"))?;
+        (material::Lambertian::new_arc(albedo), false)
+      }
+      MaterialDesc::Metal { albedo, fuzz } => {
+        (material::Metal::new_arc(to_color(*albedo), *fuzz), false)
+      }
+      MaterialDesc::Dielectric { ir, albedo } => {
+        let albedo = self.texture(albedo, span, &(field + ".albedo"))?;
+        (material::Dielectric::new_arc(*ir, albedo), false)
+      }
+      MaterialDesc::DiffusionLight { emit } => {
+        let emit = self.texture(emit, span, &(field + ".emit"))?;
+        (material::DiffusionLight::new_arc(emit), true)
+      }
+    };
+    self.materials.insert(name.to_string(), mat.clone());
+    Ok(mat)
+  }
+
+  fn add_object(
+    &mut self,
+    world: &mut World,
+    idx: usize,
+    object: &toml::Spanned<ObjectDesc>,
+  ) -> Result<(), SceneError> {
+    let span = object.span();
+    let field = format!("objects[{}]", idx);
+
+    let add = |world: &mut World, object: Arc<dyn Hittable>, is_light: bool| {
+      if is_light { world.add_light(object) } else { world.add_object(object) }
+    };
+
+    match object.get_ref() {
+      ObjectDesc::Sphere { material, transform, motion, light } => {
+        let (mat, emissive) = self.material(material, span.clone(), &(field.clone() + ".material"))?;
+        world.register_material(&mat);
+        let shape = geometry::UnitSphere::new_arc(mat);
+        let shape = self.transformed(shape, transform, motion, span.clone(), &field)?;
+        let is_light = self.is_light(*light, emissive, motion, span, &field)?;
+        add(world, shape, is_light);
+        self.stats.spheres += 1;
+      }
+      ObjectDesc::Quad { material, transform, motion, light } => {
+        let (mat, emissive) = self.material(material, span.clone(), &(field.clone() + ".material"))?;
+        world.register_material(&mat);
+        let shape: Arc<dyn Hittable> = Arc::new(geometry::UnitQuad::new(mat));
+        let shape = self.transformed(shape, transform, motion, span.clone(), &field)?;
+        let is_light = self.is_light(*light, emissive, motion, span, &field)?;
+        add(world, shape, is_light);
+        self.stats.quads += 1;
+      }
+      ObjectDesc::Cube { material, transform, motion } => {
+        let material_field = field.clone() + ".material";
+        let cube = match material {
+          MaterialRef::One(name) => {
+            let mat = self.material(name, span.clone(), &material_field)?.0;
+            world.register_material(&mat);
+            geometry::UnitCube::from_one(mat)
+          }
+          MaterialRef::Faces(names) => {
+            if names.len() != 6 {
+              let message = format!("a cube needs 1 or 6 materials, got {}", names.len());
+              return Err(self.error(span, material_field, message));
+            }
+            let mut faces = Vec::with_capacity(6);
+            for name in names {
+              let mat = self.material(name, span.clone(), &material_field)?.0;
+              world.register_material(&mat);
+              faces.push(mat);
+            }
+            geometry::UnitCube::new(faces.try_into().unwrap_or_else(|_| unreachable!()))
+          }
+        };
+        // cubes cannot be sampled, so they are never explicit lights.
+        let shape = self.transformed(Arc::new(cube), transform, motion, span, &field)?;
+        world.add_object(shape);
+        self.stats.cubes += 1;
+      }
+      ObjectDesc::Mesh { path, material, transform, motion, light } => {
+        let full_path = self.base_dir.join(path);
+        let (mat, emissive) = match material {
+          Some(name) => {
+            let (mat, emissive) = self.material(name, span.clone(), &(field.clone() + ".material"))?;
+            (Some(mat), emissive)
+          }
+          None => (None, false),
+        };
+        // every object with the same file and material shares one BVH.
+        let key = (full_path.clone(), material.clone());
+        let mesh = match self.meshes.get(&key) {
+          Some(mesh) => mesh.clone(),
+          None => {
+            let full_path_str = full_path.to_string_lossy();
+            let meshes = match mat {
+              Some(mat) => geometry::TriangleMesh::load_obj_with_material(&full_path_str, mat),
+              None => geometry::TriangleMesh::load_obj(&full_path_str),
+            };
+            let meshes = meshes.map_err(|e| {
+              let message = format!("cannot load {}: {}", full_path.display(), e);
+              self.error(span.clone(), field.clone() + ".path", message)
+            })?;
+            if meshes.iter().all(|mesh| mesh.triangle_count() == 0) {
+              let message = format!("{} has no triangles", full_path.display());
+              return Err(self.error(span, field + ".path", message));
+            }
+            for mesh in &meshes {
+              world.register_material(mesh.material());
+            }
+            let mesh = Arc::new(MeshAccel::from_meshes(meshes));
+            self.meshes.insert(key, mesh.clone());
+            mesh
+          }
+        };
+        self.stats.meshes += 1;
+        self.stats.triangles += mesh.triangle_count();
+        let shape = self.transformed(mesh, transform, motion, span.clone(), &field)?;
+        let is_light = self.is_light(*light, emissive, motion, span, &field)?;
+        add(world, shape, is_light);
+      }
+      ObjectDesc::Medium { boundary, density, albedo, g, transform, motion } => {
+        let medium = self.medium(*density, *albedo, *g, span.clone(), &field)?;
+        // the boundary is never hit as such, its material does not matter.
+        let shape: Arc<dyn Hittable> = match boundary {
+          BoundaryDesc::Sphere => geometry::UnitSphere::new_arc(medium.material().clone()),
+          BoundaryDesc::Cube => Arc::new(geometry::UnitCube::from_one(medium.material().clone())),
+        };
+        let shape = self.transformed(shape, transform, motion, span, &field)?;
+        world.add_medium(medium::ConstantMedium::new_arc(shape, medium));
+        self.stats.media += 1;
+      }
+      ObjectDesc::Volume {
+        path,
+        density_scale,
+        albedo,
+        g,
+        emission,
+        emission_scale,
+        temperature,
+        temperature_scale,
+        transform,
+      } => {
+        let scales = [("density_scale", density_scale), ("emission_scale", emission_scale), ("temperature_scale", temperature_scale)];
+        for (name, scale) in scales {
+          if !scale.is_finite() || *scale < 0.0 {
+            let message = format!("expected a scale >= 0, got {}", scale);
+            return Err(self.error(span, field + "." + name, message));
+          }
+        }
+        let (albedo, phase) = self.scattering(*albedo, *g, span.clone(), &field)?;
+        let load = |path: &str, name: &str| {
+          let full_path = self.base_dir.join(path);
+          medium::VoxelGrid::load(&full_path).map_err(|e| {
+            let message = format!("cannot load {}: {}", full_path.display(), e);
+            self.error(span.clone(), field.clone() + "." + name, message)
+          })
+        };
+        let mut volume = medium::GridMedium::new(load(path, "path")?, *density_scale, albedo, phase);
+        if let Some(emission) = emission {
+          volume = volume.with_emission(load(emission, "emission")?, *emission_scale);
+        }
+        if let Some(temperature) = temperature {
+          volume = volume.with_temperature(load(temperature, "temperature")?, *temperature_scale);
+        }
+        let mat = to_transform(transform);
+        if mat.inverse().is_none() {
+          let message = "transform is not invertible".to_string();
+          return Err(self.error(span, field + ".transform", message));
+        }
+        world.add_medium(Arc::new(volume.with_transform(mat)));
+        self.stats.media += 1;
+      }
+    }
+    Ok(())
+  }
+
+  // with the fields of the medium under `field`.
+  fn medium(
+    &self,
+    density: Float,
+    albedo: Vec3,
+    g: Float,
+    span: std::ops::Range<usize>,
+    field: &str,
+  ) -> Result<medium::HomogeneousMedium, SceneError> {
+    if !density.is_finite() || density < 0.0 {
+      let message = format!("expected a density >= 0, got {}", density);
+      return Err(self.error(span, field.to_string() + ".density", message));
+    }
+    let (albedo, phase) = self.scattering(albedo, g, span, field)?;
+    Ok(medium::HomogeneousMedium::new(density, albedo, phase))
+  }
+
+  // the albedo and phase function of a medium, with their fields under `field`.
+  fn scattering(
+    &self,
+    albedo: Vec3,
+    g: Float,
+    span: std::ops::Range<usize>,
+    field: &str,
+  ) -> Result<(ColorRgb, medium::PhaseFunction), SceneError> {
+    if albedo.iter().any(|c| !(0.0..=1.0).contains(c)) {
+      let message = format!("expected an albedo in [0, 1], got {:?}", albedo);
+      return Err(self.error(span, field.to_string() + ".albedo", message));
+    }
+    if g.is_nan() || g.abs() >= 1.0 {
+      let message = format!("expected g in (-1, 1), got {}", g);
+      return Err(self.error(span, field.to_string() + ".g", message));
+    }
+    let phase = if g == 0.0 { medium::PhaseFunction::Isotropic } else { medium::PhaseFunction::HenyeyGreenstein(g) };
+    Ok((to_color(albedo), phase))
+  }
+
+  // `light` defaults to whether the material emits. Moving objects cannot be sampled.
+  fn is_light(
+    &self,
+    light: Option<bool>,
+    emissive: bool,
+    motion: &[KeyframeDesc],
+    span: std::ops::Range<usize>,
+    field: &str,
+  ) -> Result<bool, SceneError> {
+    if motion.is_empty() {
+      return Ok(light.unwrap_or(emissive));
+    }
+    if light == Some(true) {
+      let message = "a moving object cannot be a light".to_string();
+      return Err(self.error(span, field.to_string() + ".light", message));
+    }
+    Ok(false)
+  }
+
+  fn transformed(
+    &self,
+    object: Arc<dyn Hittable>,
+    transform: &[TransformDesc],
+    motion: &[KeyframeDesc],
+    span: std::ops::Range<usize>,
+    field: &str,
+  ) -> Result<Arc<dyn Hittable>, SceneError> {
+    let object = if transform.is_empty() {
+      object
+    } else {
+      let mat = to_transform(transform);
+      if mat.inverse().is_none() {
+        let message = "transform is not invertible".to_string();
+        return Err(self.error(span, field.to_string() + ".transform", message));
+      }
+      geometry::Instance::new_arc(object, mat)
+    };
+    if motion.is_empty() {
+      return Ok(object);
+    }
+    let mut keyframes = Vec::with_capacity(motion.len());
+    for (idx, key) in motion.iter().enumerate() {
+      let Some(keyframe) = geometry::Keyframe::from_matrix(key.time, &to_transform(&key.transform)) else {
+        let message = "a keyframe must be made of translation, rotation and non-zero scale".to_string();
+        return Err(self.error(span, format!("{}.motion[{}].transform", field, idx), message));
+      };
+      keyframes.push(keyframe);
+    }
+    Ok(geometry::MotionInstance::new_arc(object, keyframes))
+  }
+}
+
+fn to_vec(v: Vec3) -> Vec3d {
+  Vec3d::new(v[0], v[1], v[2])
+}
+
+fn to_color(c: Vec3) -> ColorRgb {
+  ColorRgb::new(c[0], c[1], c[2])
+}
+
+fn to_transform(steps: &[TransformDesc]) -> Mat4d {
+  steps.iter().fold(Mat4d::IDENTITY, |acc, step| acc * to_matrix(step))
+}
+
+fn to_matrix(step: &TransformDesc) -> Mat4d {
+  match *step {
+    TransformDesc::Translate(v) => Mat4d::from_translation(to_vec(v)),
+    TransformDesc::Scale(ScaleDesc::Uniform(s)) => Mat4d::from_scaling(s, s, s),
+    TransformDesc::Scale(ScaleDesc::Axes(s)) => Mat4d::from_scaling(s[0], s[1], s[2]),
+    TransformDesc::RotateX(degrees) => Mat4d::from_rotation_x(degrees.to_radians()),
+    TransformDesc::RotateY(degrees) => Mat4d::from_rotation_y(degrees.to_radians()),
+    TransformDesc::RotateZ(degrees) => Mat4d::from_rotation_z(degrees.to_radians()),
+    TransformDesc::Rotate { axis, degrees } => Mat4d::rotation_axis(to_vec(axis), degrees.to_radians()),
+    TransformDesc::Matrix(data) => Mat4d { data },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn build(source: &str) -> Result<Scene, SceneError> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes/test.toml");
+    SceneDescription::parse(source, &path)?.build(source, &path)
+  }
+
+  const HEADER: &str = r#"
+[film]
+width = 64
+height = 32
+
+[camera]
+type = "perspective"
+look_from = [0, 0, 5]
+look_at = [0, 0, 0]
+vfov = 40
+"#;
+
+  #[test]
+  fn test_load_example_scene() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes/spheres.toml");
+    let scene = Scene::load(&path).unwrap_or_else(|e| panic!("{}", e));
+    let film = scene.config.film.lock().unwrap();
+    assert_eq!((film.width(), film.height()), (400, 224));
+    assert_eq!(scene.config.world.lights().len(), 1);
+    assert_eq!(scene.config.filters.filters().len(), 3);
+    assert_eq!((scene.stats.primitives(), scene.stats.lights), (6, 1));
+    assert!(scene.output.unwrap().ends_with("output/image.png"));
+  }
+
+  #[test]
+  fn test_unknown_material() {
+    let source = HEADER.to_string()
+      + r#"
+[materials.red]
+type = "lambertian"
+albedo = [0.8, 0.1, 0.1]
+
+[[objects]]
+type = "sphere"
+material = "red"
+
+[[objects]]
+type = "quad"
+material = "blue"
+"#;
+    let err = build(&source).err().expect("unknown material must be an error");
+    match err {
+      SceneError::Invalid { line, ref field, .. } => {
+        assert_eq!(line, Some(20)); // the [[objects]] header
+        assert_eq!(field, "objects[1].material");
+      }
+      _ => panic!("unexpected error: {}", err),
+    }
+  }
+
+  #[test]
+  fn test_settings_errors() {
+    let field_and_line = |source: &str| match build(source) {
+      Err(SceneError::Invalid { line, field, .. }) => (field, line),
+      Err(e) => panic!("unexpected error: {}", e),
+      Ok(_) => panic!("{} must be an error", source),
+    };
+    let small = HEADER.replace("height = 32", "height = 1");
+    assert_eq!(field_and_line(&small), ("film".to_string(), Some(2))); // the [film] header
+    for ratio in ["0", "-1.5", "nan", "inf"] {
+      let ratio = HEADER.replace("height = 32", &format!("aspect_ratio = {}", ratio));
+      assert_eq!(field_and_line(&ratio), ("film.aspect_ratio".to_string(), Some(2)));
+    }
+    for size in ["aspect_ratio = 1e-9", "height = 4294967295"] {
+      let huge = HEADER.replace("height = 32", size);
+      assert_eq!(field_and_line(&huge), ("film".to_string(), Some(2)));
+    }
+    let aovs = HEADER.replace("height = 32", "height = 32\naovs = [\"albedo\",\n  \"glow\"]");
+    assert_eq!(field_and_line(&aovs), ("film.aovs".to_string(), Some(6)));
+
+    let renderer = HEADER.to_string()
+      + r#"
+[renderer]
+sampler = "magic"
+pixel_filter = { type = "gaussian", sigma = -1 }
+"#;
+    assert_eq!(field_and_line(&renderer), ("renderer.sampler".to_string(), Some(13)));
+    let renderer = renderer.replace("magic", "sobol");
+    assert_eq!(field_and_line(&renderer), ("renderer.pixel_filter".to_string(), Some(14)));
+  }
+
+  #[test]
+  fn test_texture_cycle_and_parse_error() {
+    let source = HEADER.to_string()
+      + r#"
+[textures.a]
+type = "uv_checker"
+scale = [1, 1]
+even = "b"
+odd = [0, 0, 0]
+
+[textures.b]
+type = "uv_checker"
+scale = [1, 1]
+even = "a"
+odd = [0, 0, 0]
+
+[materials.m]
+type = "lambertian"
+albedo = "a"
+
+[[objects]]
+type = "sphere"
+material = "m"
+"#;
+    assert!(matches!(build(&source), Err(SceneError::Invalid { .. })));
+
+    let source = HEADER.to_string() + "\n[renderer]\nsamples_per_pixel = \"many\"\n";
+    match build(&source) {
+      Err(SceneError::Parse { line, .. }) => assert_eq!(line, Some(13)),
+      _ => panic!("wrong type must be a parse error"),
+    }
+  }
+
+  #[test]
+  fn test_motion() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes/motion_blur.toml");
+    let scene = Scene::load(&path).unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!((scene.stats.primitives(), scene.stats.lights), (4, 1));
+
+    let source = HEADER.to_string()
+      + r#"
+[materials.lamp]
+type = "diffusion_light"
+emit = [1, 1, 1]
+
+[[objects]]
+type = "sphere"
+material = "lamp"
+light = true
+motion = [{ time = 0 }, { time = 1, transform = [{ translate = [1, 0, 0] }] }]
+"#;
+    match build(&source) {
+      Err(SceneError::Invalid { field, .. }) => assert_eq!(field, "objects[0].light"),
+      _ => panic!("a moving light must be an error"),
+    }
+    let source = source.replace("light = true\n", "")
+      + "\n[[objects]]\ntype = \"quad\"\nmaterial = \"lamp\"\nmotion = [{ time = 0, transform = [{ matrix = [1, 0, 0, 0, 1, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1] }] }]\n";
+    match build(&source) {
+      Err(SceneError::Invalid { field, .. }) => assert_eq!(field, "objects[1].motion[0].transform"),
+      _ => panic!("a sheared keyframe must be an error"),
+    }
+  }
+
+  #[test]
+  fn test_environment_background() {
+    let dir = std::env::temp_dir().join(format!("raytracer-ramel-env-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("gray.exr");
+    image::Rgb32FImage::from_pixel(32, 16, image::Rgb([0.5, 0.5, 0.5])).save(&path).unwrap();
+    let source = HEADER.to_string()
+      + &format!(
+        r#"
+[renderer]
+samples_per_pixel = 32
+max_depth = 2
+
+[background]
+type = "environment"
+path = '{}'
+intensity = 2
+transform = [{{ rotate_y = 30 }}]
+
+[materials.gray]
+type = "lambertian"
+albedo = [0.5, 0.5, 0.5]
+
+[[objects]]
+type = "sphere"
+material = "gray"
+"#,
+        path.display()
+      );
+    let scene = build(&source).unwrap_or_else(|e| panic!("{}", e));
+    assert!(scene.config.world.environment().is_some());
+    let film = scene.config.film.clone();
+    scene.renderer.render(scene.config);
+    // the sphere sees the environment all over its upper hemisphere: 0.5 * 2 * 0.5.
+    let film = film.lock().unwrap();
+    let center = (0..9).map(|i| film.get_pixel(31 + i % 3, 15 + i / 3).g).sum::<Float>() / 9.0;
+    assert!((center - 0.5).abs() < 0.025, "{}", center);
+    assert_eq!(film.get_pixel(0, 0), ColorRgb::new(1.0, 1.0, 1.0));
+
+    match build(&source.replace("rotate_y = 30", "scale = 2")) {
+      Err(SceneError::Invalid { field, .. }) => assert_eq!(field, "background.transform"),
+      _ => panic!("a scaled environment must be an error"),
+    }
+    match build(&source.replace("gray.exr", "missing.exr")) {
+      Err(SceneError::Invalid { field, .. }) => assert_eq!(field, "background.path"),
+      _ => panic!("a missing environment map must be an error"),
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let sky = HEADER.to_string() + "\n[background]\ntype = \"sky\"\nelevation = 40\nturbidity = 20\n";
+    match build(&sky) {
+      Err(SceneError::Invalid { field, .. }) => assert_eq!(field, "background.turbidity"),
+      _ => panic!("a turbidity out of the model must be an error"),
+    }
+    let scene = build(&sky.replace("turbidity = 20", "sun_radius = 2")).unwrap_or_else(|e| panic!("{}", e));
+    let sun = scene.config.world.environment().and_then(|environment| environment.sun()).unwrap();
+    assert!(sun.direction().y > 0.6 && sun.direction().z < -0.7);
+  }
+
+  #[test]
+  fn test_light_sources() {
+    let source = HEADER.to_string()
+      + r#"
+[[lights]]
+type = "point"
+position = [0, 3, 0]
+intensity = [10, 10, 10]
+
+[[lights]]
+type = "spot"
+position = [0, 3, 0]
+look_at = [0, 0, 0]
+intensity = [10, 10, 10]
+cone_angle = 30
+falloff_start = 20
+
+[[lights]]
+type = "directional"
+direction = [0, -1, -1]
+irradiance = [3, 3, 3]
+angular_diameter = 0.5
+"#;
+    let scene = build(&source).unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!((scene.stats.lights, scene.stats.light_sources), (0, 3));
+    match build(&source.replace("falloff_start = 20", "falloff_start = 40")) {
+      Err(SceneError::Invalid { field, line, .. }) => {
+        assert_eq!(field, "lights[1].cone_angle");
+        assert_eq!(line, Some(17)); // the [[lights]] header
+      }
+      _ => panic!("a falloff outside of the cone must be an error"),
+    }
+  }
+
+  #[test]
+  fn test_media() {
+    let source = HEADER.to_string()
+      + r#"
+[fog]
+density = 0.05
+
+[[objects]]
+type = "medium"
+boundary = "cube"
+density = 2
+albedo = [0.8, 0.8, 0.9]
+g = 0.3
+transform = [{ scale = 0.5 }]
+"#;
+    let scene = build(&source).unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!((scene.stats.media, scene.stats.primitives(), scene.config.world.media().len()), (1, 0, 1));
+    assert_eq!(scene.config.world.medium().map(|fog| fog.density()), Some(0.05));
+    match build(&source.replace("g = 0.3", "g = 1")) {
+      Err(SceneError::Invalid { field, line, .. }) => {
+        assert_eq!(field, "objects[0].g");
+        assert_eq!(line, Some(15)); // the [[objects]] header
+      }
+      _ => panic!("g = 1 must be an error"),
+    }
+  }
+
+  #[test]
+  fn test_volumes() {
+    let bounds = Aabb { min: Point::new(-1.0, -1.0, -1.0), max: Point::new(1.0, 1.0, 1.0) };
+    let grid = medium::VoxelGrid::from_fn([4, 4, 4], bounds, |p| (1.0 - p.length()).max(0.0));
+    let path = std::env::temp_dir().join(format!("scene_volume_{}.vol", std::process::id()));
+    grid.save(&path).unwrap();
+    let source = HEADER.to_string()
+      + &format!(
+        r#"
+[[objects]]
+type = "volume"
+path = {:?}
+density_scale = 4
+temperature = {:?}
+temperature_scale = 0.5
+transform = [{{ translate = [0, 1, 0] }}]
+"#,
+        path.to_string_lossy(),
+        path.to_string_lossy()
+      );
+    let result = build(&source);
+    let missing = build(&source.replacen("volume_", "missing_", 1));
+    std::fs::remove_file(&path).unwrap();
+    let scene = result.unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!((scene.stats.media, scene.config.world.media().len()), (1, 1));
+    match missing {
+      Err(SceneError::Invalid { field, line, .. }) => {
+        assert_eq!(field, "objects[0].path");
+        assert_eq!(line, Some(12)); // the [[objects]] header
+      }
+      _ => panic!("a missing grid must be an error"),
+    }
+  }
+
+  #[test]
+  fn test_seeded_render() {
+    let source = HEADER.to_string()
+      + r#"
+[renderer]
+samples_per_pixel = 4
+max_depth = 4
+sampler = "sobol"
+seed = 11
+
+[textures.marble]
+type = "noise"
+scale = 4
+seed = 3
+
+[materials.m]
+type = "lambertian"
+albedo = "marble"
+
+[materials.lamp]
+type = "diffusion_light"
+emit = [4, 4, 4]
+
+[[objects]]
+type = "sphere"
+material = "m"
+
+[[objects]]
+type = "quad"
+material = "lamp"
+transform = [{ translate = [0, 2, 0] }, { rotate_x = 90 }]
+"#;
+    // everything is built again, the noise texture too.
+    let render = |source: &str| {
+      let scene = build(source).unwrap_or_else(|e| panic!("{}", e));
+      let film = scene.config.film.clone();
+      scene.renderer.render(scene.config);
+      let film = film.lock().unwrap();
+      film.to_image_hdr().into_raw()
+    };
+    let image = render(&source);
+    assert_eq!(image, render(&source));
+    assert_ne!(image, render(&source.replace("seed = 11", "seed = 12")));
+  }
+}
@@ -0,0 +1,409 @@
+use crate::prelude::*;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use toml::Spanned;
+
+pub type Vec3 = [Float; 3];
+
+/// The raw content of a scene file, before any name is resolved.
+/// Every field maps to a TOML table or key of the same name.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDescription {
+  pub film: Spanned<FilmDesc>,
+  #[serde(default)]
+  pub renderer: RendererDesc,
+  pub camera: Spanned<CameraDesc>,
+  pub background: Option<Spanned<BackgroundDesc>>,
+  #[serde(default)]
+  pub filters: Vec<Spanned<FilterDesc>>,
+  #[serde(default)]
+  pub textures: BTreeMap<String, Spanned<TextureDesc>>,
+  #[serde(default)]
+  pub materials: BTreeMap<String, Spanned<MaterialDesc>>,
+  #[serde(default)]
+  pub objects: Vec<Spanned<ObjectDesc>>,
+  #[serde(default)]
+  pub lights: Vec<Spanned<LightDesc>>,
+  /// A medium filling the space between the objects.
+  pub fog: Option<Spanned<MediumDesc>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct FilmDesc {
+  pub width: u32,
+  /// If absent, derived from `aspect_ratio`.
+  pub height: Option<u32>,
+  /// width / height, 16:9 if neither this nor `height` is given.
+  pub aspect_ratio: Option<Float>,
+  /// Where the image is saved, relative to the scene file.
+  pub output: Option<String>,
+  /// Names of the AOV layers to render besides the color, see `film::Aov::name`.
+  #[serde(default)]
+  pub aovs: Vec<Spanned<String>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RendererDesc {
+  #[serde(default = "default_spp")]
+  pub samples_per_pixel: u32,
+  #[serde(default = "default_depth")]
+  pub max_depth: u32,
+  /// Name of the sampler, see `sampler::SamplerKind::name`. Independent if absent.
+  pub sampler: Option<Spanned<String>>,
+  /// Seed of the random numbers, the same seed renders the same image.
+  #[serde(default)]
+  pub seed: u64,
+  /// How samples are weighted into the pixels. A box of radius 0.5 (a plain average) if absent.
+  pub pixel_filter: Option<Spanned<PixelFilterDesc>>,
+}
+
+/// The parameters left out take the values of `film::PixelFilter::from_name`.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum PixelFilterDesc {
+  Box { radius: Option<Float> },
+  Tent { radius: Option<Float> },
+  Gaussian { radius: Option<Float>, sigma: Option<Float> },
+  Mitchell { radius: Option<Float>, b: Option<Float>, c: Option<Float> },
+  Lanczos { radius: Option<Float> },
+}
+
+impl PixelFilterDesc {
+  pub fn name(&self) -> &'static str {
+    match self {
+      PixelFilterDesc::Box { .. } => "box",
+      PixelFilterDesc::Tent { .. } => "tent",
+      PixelFilterDesc::Gaussian { .. } => "gaussian",
+      PixelFilterDesc::Mitchell { .. } => "mitchell",
+      PixelFilterDesc::Lanczos { .. } => "lanczos",
+    }
+  }
+}
+
+impl Default for RendererDesc {
+  fn default() -> Self {
+    Self { samples_per_pixel: default_spp(), max_depth: default_depth(), sampler: None, seed: 0, pixel_filter: None }
+  }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum CameraDesc {
+  Perspective {
+    look_from: Vec3,
+    look_at: Vec3,
+    #[serde(default = "default_vup")]
+    vup: Vec3,
+    /// in degrees.
+    vfov: Float,
+    /// Diameter of the lens. 0 for a pinhole camera, where everything is in focus.
+    #[serde(default)]
+    aperture: Float,
+    /// Distance to the sharp plane. Focuses on `look_at` if absent.
+    focus_distance: Option<Float>,
+    /// Sides of a polygonal aperture (>= 3). A disk if absent.
+    blades: Option<u32>,
+    /// Rotation of the polygonal aperture, in degrees.
+    #[serde(default)]
+    blade_rotation: Float,
+    /// [open, close] times of the shutter, for motion blur.
+    #[serde(default)]
+    shutter: [Float; 2],
+  },
+  Orthographic {
+    look_from: Vec3,
+    look_at: Vec3,
+    #[serde(default = "default_vup")]
+    vup: Vec3,
+    /// height of the view port.
+    height: Float,
+    #[serde(default)]
+    shutter: [Float; 2],
+  },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum BackgroundDesc {
+  Solid { color: Vec3 },
+  /// Lerp from `bottom` to `top` by the y component of the ray direction.
+  Gradient { bottom: Vec3, top: Vec3 },
+  /// `World::default_background_shader`.
+  Default,
+  /// `light::PreethamSky` and its sun, lighting the scene. Angles in degrees, the azimuth
+  /// from -z towards +x.
+  Sky {
+    elevation: Float,
+    #[serde(default)]
+    azimuth: Float,
+    #[serde(default = "default_turbidity")]
+    turbidity: Float,
+    #[serde(default = "default_ground_albedo")]
+    ground_albedo: Vec3,
+    #[serde(default = "default_one")]
+    intensity: Float,
+    /// Angular radius of the sun disk, 0 for none.
+    #[serde(default = "default_sun_radius")]
+    sun_radius: Float,
+  },
+  /// An equirectangular .hdr or .exr image lighting the scene, path relative to the scene file.
+  /// `transform` may only rotate it.
+  Environment {
+    path: String,
+    #[serde(default = "default_one")]
+    intensity: Float,
+    #[serde(default)]
+    transform: Vec<TransformDesc>,
+  },
+}
+
+/// Lights which are not objects. Angles in degrees, `intensity` of point and spot lights is
+/// what arrives 1 unit away, `irradiance` of a directional light what a surface facing it gets.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum LightDesc {
+  Point {
+    position: Vec3,
+    intensity: Vec3,
+  },
+  Spot {
+    position: Vec3,
+    look_at: Vec3,
+    intensity: Vec3,
+    /// From the axis to the edge of the light.
+    cone_angle: Float,
+    /// From the axis to where the light starts fading, `cone_angle` (a sharp edge) if absent.
+    falloff_start: Option<Float>,
+  },
+  Directional {
+    /// Where the light goes, like [0, -1, 0] straight down.
+    direction: Vec3,
+    irradiance: Vec3,
+    /// Size of the light in the sky, 0 for sharp shadows.
+    #[serde(default)]
+    angular_diameter: Float,
+  },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum FilterDesc {
+  Exposure { exposure: Float },
+  Aces,
+  Vignette { intensity: Float },
+  Gamma { gamma: Float },
+  Reinhard,
+}
+
+/// Either the name of a texture in `[textures]`, or an inline color.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum TextureRef {
+  Color(Vec3),
+  Name(String),
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum TextureDesc {
+  Solid { color: Vec3 },
+  /// Path relative to the scene file.
+  Image {
+    path: String,
+    #[serde(default = "default_true")]
+    srgb: bool,
+  },
+  Noise {
+    scale: Float,
+    #[serde(default)]
+    seed: u32,
+  },
+  UvChecker { scale: [Float; 2], even: TextureRef, odd: TextureRef },
+  SpatialChecker { scale: Vec3, even: TextureRef, odd: TextureRef },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MaterialDesc {
+  Lambertian { albedo: TextureRef },
+  Metal {
+    albedo: Vec3,
+    #[serde(default)]
+    fuzz: Float,
+  },
+  Dielectric {
+    ir: Float,
+    #[serde(default = "default_white")]
+    albedo: TextureRef,
+  },
+  DiffusionLight { emit: TextureRef },
+}
+
+/// Either one material name, or one for each face of a cube.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum MaterialRef {
+  One(String),
+  Faces(Vec<String>),
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ObjectDesc {
+  Sphere {
+    material: String,
+    #[serde(default)]
+    transform: Vec<TransformDesc>,
+    #[serde(default)]
+    motion: Vec<KeyframeDesc>,
+    light: Option<bool>,
+  },
+  Quad {
+    material: String,
+    #[serde(default)]
+    transform: Vec<TransformDesc>,
+    #[serde(default)]
+    motion: Vec<KeyframeDesc>,
+    light: Option<bool>,
+  },
+  Cube {
+    material: MaterialRef,
+    #[serde(default)]
+    transform: Vec<TransformDesc>,
+    #[serde(default)]
+    motion: Vec<KeyframeDesc>,
+  },
+  /// Wavefront `.obj`, path relative to the scene file.
+  /// Without `material`, the `.mtl` materials are used.
+  Mesh {
+    path: String,
+    material: Option<String>,
+    #[serde(default)]
+    transform: Vec<TransformDesc>,
+    #[serde(default)]
+    motion: Vec<KeyframeDesc>,
+    light: Option<bool>,
+  },
+  /// A medium filling `boundary`, like smoke, see `MediumDesc`.
+  Medium {
+    boundary: BoundaryDesc,
+    density: Float,
+    #[serde(default = "default_albedo")]
+    albedo: Vec3,
+    #[serde(default)]
+    g: Float,
+    #[serde(default)]
+    transform: Vec<TransformDesc>,
+    #[serde(default)]
+    motion: Vec<KeyframeDesc>,
+  },
+  /// A medium of varying density, like a cloud, from a Mitsuba `.vol` grid, path relative to the
+  /// scene file. The density is the grid times `density_scale`, and it glows by the `emission`
+  /// grid or like a black body at the `temperature` grid (kelvin), each times its scale.
+  /// `transform` places the box of the grids in the world.
+  Volume {
+    path: String,
+    #[serde(default = "default_one")]
+    density_scale: Float,
+    #[serde(default = "default_albedo")]
+    albedo: Vec3,
+    #[serde(default)]
+    g: Float,
+    emission: Option<String>,
+    #[serde(default = "default_one")]
+    emission_scale: Float,
+    temperature: Option<String>,
+    #[serde(default = "default_one")]
+    temperature_scale: Float,
+    #[serde(default)]
+    transform: Vec<TransformDesc>,
+  },
+}
+
+/// Inside of a medium: the unit sphere or the unit cube, transformed.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum BoundaryDesc {
+  Sphere,
+  Cube,
+}
+
+/// A homogeneous medium of `density` collisions per unit of length, scattering `albedo` of the
+/// light with a Henyey-Greenstein phase function of mean cosine `g`, isotropic at 0.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct MediumDesc {
+  pub density: Float,
+  #[serde(default = "default_albedo")]
+  pub albedo: Vec3,
+  #[serde(default)]
+  pub g: Float,
+}
+
+/// One step of a transform. The steps are multiplied in the written order,
+/// so the last one is applied first, like `translate * rotate * scale` in code.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum TransformDesc {
+  Translate(Vec3),
+  /// Either one factor for all the axes, or one per axis.
+  Scale(ScaleDesc),
+  /// in degrees.
+  RotateX(Float),
+  RotateY(Float),
+  RotateZ(Float),
+  Rotate { axis: Vec3, degrees: Float },
+  /// Column major, the same as `Mat4d::data`.
+  Matrix([Float; 16]),
+}
+
+/// The pose of a moving object at `time`, applied after its `transform`.
+/// Only translation, rotation and scale are allowed, no shear.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct KeyframeDesc {
+  pub time: Float,
+  #[serde(default)]
+  pub transform: Vec<TransformDesc>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ScaleDesc {
+  Uniform(Float),
+  Axes(Vec3),
+}
+
+fn default_spp() -> u32 {
+  100
+}
+fn default_depth() -> u32 {
+  50
+}
+fn default_vup() -> Vec3 {
+  [0.0, 1.0, 0.0]
+}
+fn default_turbidity() -> Float {
+  3.0
+}
+fn default_ground_albedo() -> Vec3 {
+  [0.3, 0.3, 0.3]
+}
+fn default_sun_radius() -> Float {
+  0.27
+}
+fn default_albedo() -> Vec3 {
+  [1.0, 1.0, 1.0]
+}
+fn default_one() -> Float {
+  1.0
+}
+fn default_true() -> bool {
+  true
+}
+fn default_white() -> TextureRef {
+  TextureRef::Color([1.0, 1.0, 1.0])
+}
@@ -8,8 +8,14 @@ pub struct ImageTexture {
 
 impl ImageTexture {
   pub fn new(path: &str, is_srgb: bool) -> Self {
-    let image = image::open(path).expect("Failed to load texture image");
-    Self { image, is_srgb }
+    Self::load(path, is_srgb).expect("Failed to load texture image")
+  }
+  pub fn load<P: AsRef<std::path::Path>>(path: P, is_srgb: bool) -> Result<Self, image::ImageError> {
+    let image = image::open(path)?;
+    Ok(Self { image, is_srgb })
+  }
+  pub fn new_arc(path: &str, is_srgb: bool) -> Arc<Self> {
+    Arc::new(Self::new(path, is_srgb))
   }
 }
 
@@ -9,9 +9,12 @@ struct Perlin {
 
 impl Perlin {
   pub fn new(mut seed: u32) -> Self {
+    // the gradients come from the seed too, so the noise is the same in every run.
+    let mut sampler = sampler::IndependentSampler::new(seed as u64);
     let mut rand_vec = Vec::with_capacity(256);
-    for _ in 0..256 {
-      rand_vec.push(Vec3d::random_unit());
+    for i in 0..256 {
+      sampler.start_pixel_sample((0, 0), i);
+      rand_vec.push(sampler::sample_uniform_sphere(sampler.get_2d()));
     }
 
     Self {
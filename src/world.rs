@@ -1,10 +1,55 @@
 use crate::prelude::*;
+use std::collections::HashMap;
 
 pub type BackgroundShader = Box<dyn Fn(&Ray) -> ColorRgb + Send + Sync>;
 
 pub struct World {
   objects: Aggregate,
+  // emissive objects that are sampled explicitly. They are also in `objects`.
+  lights: Vec<Arc<dyn Hittable>>,
+  // object ids of `lights`, to tell which one a ray hit.
+  light_ids: HashMap<u32, usize>,
+  // point, spot and directional lights, which are not objects.
+  light_sources: Vec<Arc<dyn Light>>,
+  // picks the lights and the light sources by how much they may give to a point, built by
+  // `bvh_finalize`. The lights are numbered as in `light_ref`.
+  light_bvh: Option<light::LightBvh>,
+  // the light sources of no bounds, infinitely far away, picked apart with the environment.
+  distant_sources: Vec<usize>,
   background_shader: BackgroundShader,
+  // replaces the background shader, and is sampled like the lights.
+  environment: Option<light::EnvironmentLight>,
+  // fills the space between the objects.
+  medium: Option<medium::HomogeneousMedium>,
+  // other media, which are not objects either, like clouds.
+  media: Vec<Arc<dyn Medium>>,
+  // ids of registered materials by address, starting from 1.
+  material_ids: HashMap<usize, u32>,
+}
+
+// One of the lights `sample_light` picks from.
+#[derive(Clone, Copy)]
+enum LightRef {
+  Object(usize),
+  Source(usize),
+  Environment,
+}
+
+// Stamps the records of a top level object with its id.
+struct Identified {
+  object: Arc<dyn Hittable>,
+  id: u32,
+}
+
+impl Hittable for Identified {
+  fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+    let mut record = self.object.hit(ray, t_min, t_max)?;
+    record.object_id = self.id;
+    Some(record)
+  }
+  fn bounding_box(&self) -> Aabb {
+    self.object.bounding_box()
+  }
 }
 
 impl World {
@@ -12,7 +57,16 @@ impl World {
   pub fn new_with(background_shader: BackgroundShader) -> Self {
     World {
       objects: Aggregate::default(),
+      lights: Vec::new(),
+      light_ids: HashMap::new(),
+      light_sources: Vec::new(),
+      light_bvh: None,
+      distant_sources: Vec::new(),
       background_shader,
+      environment: None,
+      medium: None,
+      media: Vec::new(),
+      material_ids: HashMap::new(),
     }
   }
 
@@ -30,6 +84,67 @@ impl World {
   pub fn background_shader(&self) -> &BackgroundShader {
     &self.background_shader
   }
+  /// Light from an environment map, seen by the rays that hit nothing instead of the background
+  /// shader, and sampled explicitly like the lights.
+  pub fn set_environment(&mut self, environment: light::EnvironmentLight) {
+    self.environment = Some(environment);
+    self.light_bvh = None;
+  }
+  pub fn environment(&self) -> Option<&light::EnvironmentLight> {
+    self.environment.as_ref()
+  }
+  /// A medium filling the whole space between the objects, for fog. It is infinite, so it
+  /// hides the background and the lights infinitely far away, however thin.
+  pub fn set_medium(&mut self, medium: medium::HomogeneousMedium) {
+    self.medium = Some(medium);
+  }
+  pub fn medium(&self) -> Option<&medium::HomogeneousMedium> {
+    self.medium.as_ref()
+  }
+  /// Add a medium which is not an object, like a `medium::ConstantMedium` or a
+  /// `medium::GridMedium`. Rays go through it, see `sample_collision`.
+  pub fn add_medium(&mut self, medium: Arc<dyn Medium>) {
+    self.media.push(medium);
+  }
+  pub fn media(&self) -> &[Arc<dyn Medium>] {
+    &self.media
+  }
+  /// The first collision of `ray` before `t_max` in the medium of the world or the others.
+  /// The objects are not tested.
+  pub fn sample_collision(&self, ray: &Ray, t_max: Float, sampler: &mut dyn Sampler) -> Option<HitRecord> {
+    let fog = self.medium.iter().map(|medium| medium as &dyn Medium);
+    // the media are independent: the first collision in any is the first of them all.
+    fog.chain(self.media.iter().map(|medium| medium.as_ref())).fold(None, |first, medium| {
+      let t_max = first.as_ref().map_or(t_max, |record: &HitRecord| record.hit_t);
+      medium.sample_collision(ray, t_max, sampler).or(first)
+    })
+  }
+  /// How much of the light goes along `ray` from `t_max` back to its origin, through the
+  /// media of `sample_collision`.
+  pub fn transmittance(&self, ray: &Ray, t_max: Float, sampler: &mut dyn Sampler) -> Float {
+    let fog = self.medium.iter().map(|medium| medium as &dyn Medium);
+    fog.chain(self.media.iter().map(|medium| medium.as_ref())).fold(1.0, |transmittance, medium| {
+      if transmittance <= 0.0 { 0.0 } else { transmittance * medium.transmittance(ray, t_max, sampler) }
+    })
+  }
+  /// What a ray that hits nothing sees, the directional lights with a size included.
+  pub fn background(&self, ray: &Ray) -> ColorRgb {
+    self.background_terms(ray).fold(ColorRgb::BLACK, |sum, (radiance, _)| sum + radiance)
+  }
+  /// `background` by what it comes from: the environment or the background shader, then each
+  /// light source, with the pdf of `sample_light` choosing the direction of `ray` on it. The
+  /// background shader is never sampled, its pdf is 0.
+  pub fn background_terms(&self, ray: &Ray) -> impl Iterator<Item = (ColorRgb, Float)> + '_ {
+    let direction = ray.direction.normalize();
+    let pmf = self.distant_pmf();
+    let background = match self.environment {
+      Some(ref environment) => (environment.radiance(&direction), environment.pdf(&direction) * pmf),
+      None => ((self.background_shader)(ray), 0.0),
+    };
+    // the light sources with bounds are points, which no direction hits.
+    let sources = self.light_sources.iter().map(move |light| (light.radiance(&direction), light.pdf(&direction) * pmf));
+    std::iter::once(background).chain(sources)
+  }
   // blue to white
   pub fn default_background_shader() -> BackgroundShader {
     Box::new(|_ray: &Ray| {
@@ -38,17 +153,164 @@ impl World {
       ColorRgb::lerp(ColorRgb::WHITE, ColorRgb::SKYBLUE, t)
     })
   }
+  /// Objects are numbered from 1 in the order they are added, see `HitRecord::object_id`.
   pub fn add_object(&mut self, object: Arc<dyn Hittable>) {
-    self.objects.add_object(object);
+    let id = self.objects.len() as u32 + 1;
+    self.objects.add_object(Arc::new(Identified { object, id }));
+  }
+  /// Add an emissive object, which is also sampled explicitly by the renderer.
+  /// The object shall support `Hittable::sample_surface`.
+  pub fn add_light(&mut self, light: Arc<dyn Hittable>) {
+    self.light_ids.insert(self.objects.len() as u32 + 1, self.lights.len());
+    self.add_object(light.clone());
+    self.lights.push(light);
+    self.light_bvh = None;
+  }
+
+  /// Add a light which is not an object, like `light::PointLight`, sampled explicitly.
+  pub fn add_light_source(&mut self, light: Arc<dyn Light>) {
+    self.light_sources.push(light);
+    self.light_bvh = None;
+  }
+  pub fn light_sources(&self) -> &[Arc<dyn Light>] {
+    &self.light_sources
+  }
+
+  /// Give `material` the next material id, if it has none yet. Ids start from 1.
+  pub fn register_material(&mut self, material: &Arc<dyn Material>) -> u32 {
+    let next = self.material_ids.len() as u32 + 1;
+    *self.material_ids.entry(material_key(material)).or_insert(next)
+  }
+  /// 0 if `material` is not registered.
+  pub fn material_id(&self, material: &Arc<dyn Material>) -> u32 {
+    self.material_ids.get(&material_key(material)).copied().unwrap_or(0)
+  }
+  pub fn lights(&self) -> &Vec<Arc<dyn Hittable>> {
+    &self.lights
+  }
+
+  // the lights, the light sources, and the environment if any.
+  fn light_count(&self) -> usize {
+    self.lights.len() + self.light_sources.len() + self.environment.is_some() as usize
+  }
+
+  /// Pick a light, a light source or the environment and sample it from `origin`.
+  /// Once the world is finalized, the lights and the light sources with bounds are picked by
+  /// how much they may give to `origin` with a `LightBvh`; the light sources infinitely far away
+  /// and the environment uniformly, as if the whole tree were one more light. Uniformly before.
+  pub fn sample_light(&self, origin: &Point, sampler: &mut dyn Sampler) -> Option<light::LightSample> {
+    let (light, pmf) = self.pick_light(origin, sampler.get_1d())?;
+    let mut sample = match light {
+      LightRef::Object(idx) => {
+        let (surface, pdf) = self.lights[idx].sample_towards(origin, sampler)?;
+        light::LightSample {
+          point: Some(surface.point),
+          direction: (surface.point - *origin).normalize(),
+          radiance: surface.material.emitted(surface.mat_uv, surface.point),
+          pdf,
+          is_delta: false,
+        }
+      }
+      LightRef::Source(idx) => self.light_sources[idx].sample(origin, sampler)?,
+      LightRef::Environment => {
+        let (direction, radiance, pdf) = self.environment.as_ref()?.sample(sampler)?;
+        light::LightSample { point: None, direction, radiance, pdf, is_delta: false }
+      }
+    };
+    sample.pdf *= pmf;
+    Some(sample)
+  }
+
+  // the light picked by `u` for `origin`, and the probability of picking it.
+  fn pick_light(&self, origin: &Point, u: Float) -> Option<(LightRef, Float)> {
+    let Some(ref bvh) = self.light_bvh else {
+      let count = self.light_count();
+      if count == 0 {
+        return None;
+      }
+      let idx = ((u * count as Float) as usize).min(count - 1);
+      return Some((self.light_ref(idx), 1.0 / count as Float));
+    };
+    let distant = self.distant_count();
+    let p_distant = self.distant_pmf() * distant as Float;
+    if u < p_distant {
+      let idx = ((u / p_distant * distant as Float) as usize).min(distant - 1);
+      let light = self.distant_sources.get(idx).map_or(LightRef::Environment, |&source| LightRef::Source(source));
+      return Some((light, self.distant_pmf()));
+    }
+    let u = ((u - p_distant) / (1.0 - p_distant)).min(crate::sampler::ONE_MINUS_EPSILON);
+    let (idx, pmf) = bvh.sample(origin, u)?;
+    Some((self.light_ref(idx), (1.0 - p_distant) * pmf))
+  }
+
+  // lights are numbered from the objects, then the light sources, then the environment.
+  fn light_ref(&self, idx: usize) -> LightRef {
+    if idx < self.lights.len() {
+      LightRef::Object(idx)
+    } else if idx < self.lights.len() + self.light_sources.len() {
+      LightRef::Source(idx - self.lights.len())
+    } else {
+      LightRef::Environment
+    }
+  }
+
+  fn distant_count(&self) -> usize {
+    self.distant_sources.len() + self.environment.is_some() as usize
+  }
+  // probability of picking one of the distant light sources or the environment.
+  fn distant_pmf(&self) -> Float {
+    match self.light_bvh {
+      Some(ref bvh) => {
+        let count = self.distant_count() + !bvh.is_empty() as usize;
+        if count == 0 { 0.0 } else { 1.0 / count as Float }
+      }
+      None => 1.0 / self.light_count().max(1) as Float,
+    }
+  }
+
+  /// pdf of `sample_light` choosing `direction` from `origin` on the light of `object_id`, which
+  /// the ray towards `direction` hit. 0 if it is not a light.
+  pub fn light_pdf(&self, origin: &Point, direction: &Direction, object_id: u32) -> Float {
+    let Some(&idx) = self.light_ids.get(&object_id) else {
+      return 0.0;
+    };
+    let pmf = match self.light_bvh {
+      Some(ref bvh) => (1.0 - self.distant_pmf() * self.distant_count() as Float) * bvh.pmf(origin, idx),
+      None => 1.0 / self.light_count() as Float,
+    };
+    if pmf <= 0.0 {
+      return 0.0;
+    }
+    pmf * self.lights[idx].pdf_value(origin, direction)
   }
 
   pub fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
     self.objects.hit(ray, t_min, t_max)
   }
   /// Pack existing objects with BVH strategy.
+  /// Also builds the light BVH, see `sample_light`.
   pub fn bvh_finalize(&mut self) {
-    self.objects.bvh_accelerate();
+    self.bvh_finalize_with(BvhOptions::default());
   }
+  pub fn bvh_finalize_with(&mut self, options: BvhOptions) {
+    self.objects.bvh_accelerate(options);
+    if self.light_bvh.is_none() {
+      self.build_light_bvh();
+    }
+  }
+
+  fn build_light_bvh(&mut self) {
+    let objects = self.lights.iter().map(|light| light::LightBounds::of_object(light.as_ref()));
+    let sources: Vec<_> = self.light_sources.iter().map(|light| light.bounds()).collect();
+    let bounds: Vec<_> = objects.chain(sources.iter().copied()).collect();
+    self.distant_sources = (0..sources.len()).filter(|&idx| sources[idx].is_none()).collect();
+    self.light_bvh = Some(light::LightBvh::build(&bounds));
+  }
+}
+
+// materials are shared by `Arc`, so the address tells them apart.
+fn material_key(material: &Arc<dyn Material>) -> usize {
+  Arc::as_ptr(material) as *const () as usize
 }
 
 impl Default for World {